@@ -0,0 +1,93 @@
+//! Brute-force scoring: the old per-query cosine + full sort against unit
+//! vectors scored with the SIMD dot kernel, in parallel, into a top-k heap,
+//! and the int8 / PQ / binary code scans of a quantized index.
+//!
+//! Run with `cargo bench --bench scoring`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use tapssp_project::query::cosine_similarity;
+use tapssp_project::index::Quantizer;
+use tapssp_project::quantize::ProductQuantizer;
+use tapssp_project::score::{dot, normalize, top_k_by, top_k_dot};
+
+const DIM: usize = 384;
+const TOP_K: usize = 10;
+
+/// Deterministic pseudo-random vectors (xorshift), no extra dependencies.
+fn vectors(n: usize, dim: usize, seed: u64) -> Vec<Vec<f32>> {
+    let mut state = seed;
+    (0..n)
+        .map(|_| {
+            (0..dim)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    (state % 2000) as f32 / 1000.0 - 1.0
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn cosine_full_sort(query: &[f32], corpus: &[Vec<f32>], k: usize) -> Vec<(usize, f32)> {
+    let mut scored: Vec<(usize, f32)> = corpus
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (i, cosine_similarity(query, v)))
+        .collect();
+    scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+    scored.truncate(k);
+    scored
+}
+
+fn pair_kernels(c: &mut Criterion) {
+    let v = vectors(2, DIM, 7);
+    let mut group = c.benchmark_group("pair");
+    group.bench_function("cosine_similarity", |b| {
+        b.iter(|| cosine_similarity(black_box(&v[0]), black_box(&v[1])))
+    });
+    group.bench_function("dot_simd", |b| {
+        b.iter(|| dot(black_box(&v[0]), black_box(&v[1])))
+    });
+    group.finish();
+}
+
+fn corpus_scan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scan");
+    group.sample_size(20);
+    for n in [10_000, 100_000] {
+        let corpus = vectors(n, DIM, 42);
+        let mut unit = corpus.clone();
+        unit.iter_mut().for_each(|v| normalize(v));
+        let mut query = vectors(1, DIM, 99).remove(0);
+        normalize(&mut query);
+
+        group.bench_with_input(BenchmarkId::new("cosine_full_sort", n), &n, |b, _| {
+            b.iter(|| cosine_full_sort(black_box(&query), &corpus, TOP_K))
+        });
+        group.bench_with_input(BenchmarkId::new("parallel_dot_top_k", n), &n, |b, _| {
+            b.iter(|| top_k_dot(black_box(&query), &unit, TOP_K))
+        });
+
+        let pq = ProductQuantizer::train(&unit[..2_000], 48, 5).unwrap();
+        for (name, quantizer) in [
+            ("int8_top_k", Quantizer::Int8),
+            ("pq48_top_k", Quantizer::Pq(pq)),
+            ("binary_top_k", Quantizer::Binary),
+        ] {
+            let codes: Vec<Vec<u8>> = unit.iter().map(|v| quantizer.encode(v).unwrap()).collect();
+            group.bench_with_input(BenchmarkId::new(name, n), &n, |b, _| {
+                b.iter(|| {
+                    let prepared = quantizer.prepare(black_box(&query)).unwrap();
+                    top_k_by(codes.len(), TOP_K, |i| prepared.score(&codes[i]))
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, pair_kernels, corpus_scan);
+criterion_main!(benches);
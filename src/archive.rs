@@ -0,0 +1,172 @@
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use flate2::read::GzDecoder;
+
+/// Archive formats `rag ingest` can read entries from directly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+/// Detect an archive from its file name.
+pub fn archive_kind(path: &Path) -> Option<ArchiveKind> {
+    let name = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+    if name.ends_with(".zip") {
+        Some(ArchiveKind::Zip)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(ArchiveKind::TarGz)
+    } else if name.ends_with(".tar") {
+        Some(ArchiveKind::Tar)
+    } else {
+        None
+    }
+}
+
+/// Display path for an archive member, e.g. `bundle.zip!/docs/a.md`.
+pub fn entry_path(archive: &Path, entry: &str) -> String {
+    format!("{}!/{}", archive.display(), entry.trim_start_matches("./"))
+}
+
+/// Largest archive member read by default (256 MiB).
+pub const DEFAULT_MAX_ENTRY_BYTES: u64 = 256 << 20;
+
+/// Stream every regular file in the archive through `f` as (entry name, bytes).
+/// Entries are read into memory one at a time; nothing is extracted to disk.
+/// Entries larger than `max_entry_bytes` are skipped and returned as
+/// warnings. Sizes in entry headers are not trusted.
+pub fn for_each_entry<F>(
+    path: &Path,
+    kind: ArchiveKind,
+    max_entry_bytes: u64,
+    mut f: F,
+) -> Result<Vec<String>>
+where
+    F: FnMut(&str, Vec<u8>) -> Result<()>,
+{
+    let mut skipped = Vec::new();
+    let file = BufReader::new(File::open(path)?);
+    match kind {
+        ArchiveKind::Zip => {
+            let mut archive = zip::ZipArchive::new(file)
+                .map_err(|e| anyhow!("invalid zip {}: {}", path.display(), e))?;
+            for i in 0..archive.len() {
+                let mut entry = archive.by_index(i)?;
+                if !entry.is_file() {
+                    continue;
+                }
+                let name = entry.name()?.to_string();
+                match read_limited(&mut entry, max_entry_bytes)? {
+                    Some(bytes) => f(&name, bytes)?,
+                    None => skipped.push(too_large(&name, max_entry_bytes)),
+                }
+            }
+        }
+        ArchiveKind::Tar => {
+            for_each_tar_entry(tar::Archive::new(file), max_entry_bytes, &mut skipped, f)?
+        }
+        ArchiveKind::TarGz => for_each_tar_entry(
+            tar::Archive::new(GzDecoder::new(file)),
+            max_entry_bytes,
+            &mut skipped,
+            f,
+        )?,
+    }
+    Ok(skipped)
+}
+
+fn for_each_tar_entry<R: Read, F>(
+    mut archive: tar::Archive<R>,
+    max_entry_bytes: u64,
+    skipped: &mut Vec<String>,
+    mut f: F,
+) -> Result<()>
+where
+    F: FnMut(&str, Vec<u8>) -> Result<()>,
+{
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().to_string();
+        match read_limited(&mut entry, max_entry_bytes)? {
+            Some(bytes) => f(&name, bytes)?,
+            None => skipped.push(too_large(&name, max_entry_bytes)),
+        }
+    }
+    Ok(())
+}
+
+/// Read at most `limit` bytes; `None` if the reader holds more.
+fn read_limited<R: Read>(reader: &mut R, limit: u64) -> Result<Option<Vec<u8>>> {
+    let mut bytes = Vec::new();
+    reader
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut bytes)?;
+    Ok((bytes.len() as u64 <= limit).then_some(bytes))
+}
+
+fn too_large(name: &str, limit: u64) -> String {
+    format!("skipped {}: larger than {} bytes", name, limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn archive_kind_from_file_name() {
+        assert_eq!(archive_kind(Path::new("a/b.ZIP")), Some(ArchiveKind::Zip));
+        assert_eq!(
+            archive_kind(Path::new("docs.tar.gz")),
+            Some(ArchiveKind::TarGz)
+        );
+        assert_eq!(
+            archive_kind(Path::new("docs.tgz")),
+            Some(ArchiveKind::TarGz)
+        );
+        assert_eq!(archive_kind(Path::new("docs.tar")), Some(ArchiveKind::Tar));
+        assert_eq!(archive_kind(Path::new("book.epub")), None);
+        assert_eq!(
+            entry_path(Path::new("bundle.zip"), "./docs/a.md"),
+            "bundle.zip!/docs/a.md"
+        );
+    }
+
+    #[test]
+    fn entries_over_the_limit_are_skipped_whatever_the_header_claims() {
+        let path = std::env::temp_dir().join("rag_test_archive_limit.tar");
+        let mut builder = tar::Builder::new(File::create(&path).unwrap());
+        for (name, body) in [("small.md", &b"tiny"[..]), ("big.md", &[b'x'; 64][..])] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(body.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, body).unwrap();
+        }
+        builder.finish().unwrap();
+
+        let mut read = Vec::new();
+        let skipped = for_each_entry(&path, ArchiveKind::Tar, 16, |name, bytes| {
+            read.push((name.to_string(), bytes.len()));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(read, vec![("small.md".to_string(), 4)]);
+        assert_eq!(skipped, vec!["skipped big.md: larger than 16 bytes"]);
+
+        // A header claiming an absurd size must not be trusted up front.
+        let mut claimed = Cursor::new(vec![b'x'; 8]);
+        assert_eq!(
+            read_limited(&mut claimed, u64::MAX).unwrap().unwrap().len(),
+            8
+        );
+        let _ = std::fs::remove_file(path);
+    }
+}
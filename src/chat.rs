@@ -0,0 +1,430 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Result};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use uuid::Uuid;
+
+use crate::context::{pack_context, ContextOptions};
+use crate::embedder::Embedder;
+use crate::filter::QueryFilter;
+use crate::generate::Generator;
+use crate::models::{Answer, ChatMessage, SearchResult};
+use crate::query::{run_query_with, QueryOptions};
+use crate::store::Store;
+use crate::template::PromptTemplate;
+use crate::text::content_terms;
+
+/// Knobs for `rag chat`.
+#[derive(Debug, Clone)]
+pub struct ChatOptions {
+    /// Number of chunks to retrieve per question (changed with `/topk`)
+    pub top_k: usize,
+    pub context: ContextOptions,
+    /// How many previous question/answer pairs the query rewriter sees
+    pub history_turns: usize,
+    /// Format of the sources listed under each answer
+    pub template: PromptTemplate,
+}
+
+impl Default for ChatOptions {
+    fn default() -> Self {
+        Self {
+            top_k: 6,
+            context: ContextOptions::default(),
+            history_turns: 3,
+            template: PromptTemplate::default(),
+        }
+    }
+}
+
+/// A slash command typed at the chat prompt.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Sources,
+    TopK(usize),
+    /// Restricts retrieval; an empty filter clears it
+    Filter(QueryFilter),
+    Save(Option<PathBuf>),
+    Help,
+    Quit,
+}
+
+const HELP: &str = "\
+/sources            show the passages behind the last answer
+/topk N             retrieve N chunks per question
+/filter EXPR        restrict retrieval, e.g. path:docs/** ext:md tag:team-a since:2026-01-01
+                    (keys: path, ext, since, tag, doc; no argument clears)
+/save [FILE]        write the transcript as Markdown (default chat-<session>.md)
+/quit               leave the chat (Ctrl-D works too)";
+
+/// Parse a line starting with `/`.
+pub fn parse_command(line: &str) -> Result<Command> {
+    let mut parts = line.trim().splitn(2, char::is_whitespace);
+    let name = parts.next().unwrap_or_default();
+    let arg = parts.next().map(str::trim).filter(|a| !a.is_empty());
+    Ok(match name {
+        "/sources" => Command::Sources,
+        "/topk" => {
+            let n: usize = arg
+                .ok_or_else(|| anyhow!("usage: /topk N"))?
+                .parse()
+                .map_err(|_| anyhow!("/topk expects a positive number"))?;
+            if n == 0 {
+                bail!("/topk expects a positive number");
+            }
+            Command::TopK(n)
+        }
+        "/filter" => Command::Filter(QueryFilter::parse(arg.unwrap_or_default())?),
+        "/save" => Command::Save(arg.map(PathBuf::from)),
+        "/help" | "/?" => Command::Help,
+        "/quit" | "/exit" => Command::Quit,
+        other => bail!("unknown command {} (try /help)", other),
+    })
+}
+
+/// A multi-turn conversation over the store. Every question and answer is
+/// persisted, so a session can be resumed later by id.
+pub struct ChatSession<'a> {
+    store: &'a Store,
+    embedder: &'a dyn Embedder,
+    generator: &'a dyn Generator,
+    /// LLM used to rewrite follow-ups; without one a keyword heuristic is used
+    rewriter: Option<&'a dyn Generator>,
+    pub id: Uuid,
+    pub opts: ChatOptions,
+    filter: QueryFilter,
+    history: Vec<ChatMessage>,
+    last_context: Vec<SearchResult>,
+}
+
+impl<'a> ChatSession<'a> {
+    pub fn start(
+        store: &'a Store,
+        embedder: &'a dyn Embedder,
+        generator: &'a dyn Generator,
+        rewriter: Option<&'a dyn Generator>,
+        opts: ChatOptions,
+    ) -> Result<Self> {
+        let id = Uuid::new_v4();
+        store.create_chat_session(id)?;
+        Ok(Self {
+            store,
+            embedder,
+            generator,
+            rewriter,
+            id,
+            opts,
+            filter: QueryFilter::default(),
+            history: Vec::new(),
+            last_context: Vec::new(),
+        })
+    }
+
+    /// Continue a stored session with its previous messages as history.
+    pub fn resume(
+        store: &'a Store,
+        embedder: &'a dyn Embedder,
+        generator: &'a dyn Generator,
+        rewriter: Option<&'a dyn Generator>,
+        opts: ChatOptions,
+        id: Uuid,
+    ) -> Result<Self> {
+        if !store.chat_session_exists(id)? {
+            bail!("no chat session {}", id);
+        }
+        Ok(Self {
+            store,
+            embedder,
+            generator,
+            rewriter,
+            id,
+            opts,
+            filter: QueryFilter::default(),
+            history: store.chat_messages(id)?,
+            last_context: Vec::new(),
+        })
+    }
+
+    pub fn history(&self) -> &[ChatMessage] {
+        &self.history
+    }
+
+    /// Passages the last answer was generated from.
+    pub fn last_context(&self) -> &[SearchResult] {
+        &self.last_context
+    }
+
+    pub fn set_filter(&mut self, filter: QueryFilter) {
+        self.filter = filter;
+    }
+
+    /// Rewrite a follow-up question into a query that stands on its own,
+    /// e.g. "how fast is it?" after a question about Rust.
+    pub fn standalone_query(&self, question: &str) -> Result<String> {
+        let turns = recent_turns(&self.history, self.opts.history_turns);
+        if turns.is_empty() {
+            return Ok(question.to_string());
+        }
+        let Some(rewriter) = self.rewriter else {
+            let previous = turns.last().map(|(q, _)| q.as_str()).unwrap_or_default();
+            return Ok(heuristic_rewrite(previous, question));
+        };
+
+        let mut user = String::from("Conversation:\n");
+        for (q, a) in &turns {
+            user.push_str(&format!("User: {}\nAssistant: {}\n", q, a));
+        }
+        user.push_str(&format!(
+            "\nFollow-up question: {}\nStandalone question:",
+            question
+        ));
+        let rewritten = rewriter.complete(REWRITE_PROMPT, &user)?;
+        let rewritten = rewritten.trim().trim_matches('"').trim();
+        Ok(if rewritten.is_empty() {
+            question.to_string()
+        } else {
+            rewritten.to_string()
+        })
+    }
+
+    /// Answer one question: rewrite, retrieve, generate, and persist the turn.
+    pub fn ask(&mut self, question: &str) -> Result<Answer> {
+        let query = self.standalone_query(question)?;
+        let results = self.retrieve(&query)?;
+        let context = pack_context(&results, &self.opts.context);
+        let answer = self.generator.generate(&query, &context)?;
+
+        let user = ChatMessage {
+            role: "user".to_string(),
+            content: question.to_string(),
+            query: Some(query),
+        };
+        let assistant = ChatMessage {
+            role: "assistant".to_string(),
+            content: answer.text.clone(),
+            query: None,
+        };
+        self.store.insert_chat_message(self.id, &user)?;
+        self.store.insert_chat_message(self.id, &assistant)?;
+        self.history.push(user);
+        self.history.push(assistant);
+        self.last_context = context;
+        Ok(answer)
+    }
+
+    fn retrieve(&self, query: &str) -> Result<Vec<SearchResult>> {
+        let opts = QueryOptions {
+            top_k: self.opts.top_k,
+            filter: self.filter.clone(),
+            ..QueryOptions::default()
+        };
+        run_query_with(self.store, self.embedder, query, &opts)
+    }
+
+    /// The conversation as Markdown.
+    pub fn transcript(&self) -> String {
+        let mut out = format!("# Chat session {}\n", self.id);
+        for m in &self.history {
+            match m.role.as_str() {
+                "user" => out.push_str(&format!("\n**You:** {}\n", m.content)),
+                _ => out.push_str(&format!("\n{}\n", m.content)),
+            }
+        }
+        out
+    }
+}
+
+const REWRITE_PROMPT: &str = "Rewrite the user's follow-up question as a standalone question \
+that can be understood without the conversation. Resolve pronouns and references. \
+Reply with the question only.";
+
+/// Words that refer back to something said earlier.
+const REFERRING: &[&str] = &[
+    "it", "its", "they", "them", "their", "this", "that", "these", "those", "he", "she", "his",
+    "her", "there", "one", "ones",
+];
+
+/// The last `n` (standalone query, answer) pairs.
+fn recent_turns(history: &[ChatMessage], n: usize) -> Vec<(String, String)> {
+    let mut turns = Vec::new();
+    for pair in history.chunks(2) {
+        if let [q, a] = pair {
+            let query = q.query.clone().unwrap_or_else(|| q.content.clone());
+            turns.push((query, a.content.clone()));
+        }
+    }
+    let skip = turns.len().saturating_sub(n);
+    turns.split_off(skip)
+}
+
+/// Without an LLM: if the follow-up leans on earlier context (a referring
+/// word, or almost no content terms of its own), carry over the previous
+/// query's content terms.
+fn heuristic_rewrite(previous: &str, question: &str) -> String {
+    let own = content_terms(question);
+    let refers = question
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|w| REFERRING.contains(&w.to_lowercase().as_str()));
+    if !refers && own.len() >= 2 {
+        return question.to_string();
+    }
+    let own: HashSet<String> = own.into_iter().collect();
+    let mut carried: Vec<String> = Vec::new();
+    for term in content_terms(previous) {
+        if !own.contains(&term) && !carried.contains(&term) {
+            carried.push(term);
+        }
+    }
+    if carried.is_empty() {
+        question.to_string()
+    } else {
+        format!("{} ({})", question, carried.join(" "))
+    }
+}
+
+/// Interactive loop for `rag chat`, with line editing and input history
+/// kept in `history_file`.
+pub fn run_chat(mut session: ChatSession, history_file: &Path) -> Result<()> {
+    let mut editor = DefaultEditor::new()?;
+    let _ = editor.load_history(history_file);
+
+    println!("Chat session {} (type /help for commands)", session.id);
+    loop {
+        let line = match editor.readline("rag> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line)?;
+
+        if line.starts_with('/') {
+            match parse_command(line) {
+                Ok(Command::Quit) => break,
+                Ok(cmd) => {
+                    if let Err(e) = handle_command(&mut session, cmd) {
+                        println!("{}", e);
+                    }
+                }
+                Err(e) => println!("{}", e),
+            }
+            continue;
+        }
+
+        match session.ask(line) {
+            Ok(answer) => {
+                if answer.text.is_empty() {
+                    println!("(No answer could be extracted from the retrieved context.)");
+                } else {
+                    println!("{}", answer.text);
+                }
+                for c in &answer.citations {
+                    println!("  {}", session.opts.template.render_citation(c));
+                }
+                println!();
+            }
+            Err(e) => println!("error: {:#}", e),
+        }
+    }
+
+    if let Some(parent) = history_file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    editor.save_history(history_file)?;
+    println!(
+        "Session saved; resume with `rag chat --session {}`",
+        session.id
+    );
+    Ok(())
+}
+
+fn handle_command(session: &mut ChatSession, cmd: Command) -> Result<()> {
+    match cmd {
+        Command::Sources => {
+            if session.last_context().is_empty() {
+                println!("(no sources yet)");
+            }
+            for (i, r) in session.last_context().iter().enumerate() {
+                let preview: String = r.chunk.text.trim().chars().take(160).collect();
+                println!(
+                    "[{}] {} ({}..{}) score {:.4}\n    {}",
+                    i + 1,
+                    r.source_label(),
+                    r.chunk.start_char,
+                    r.chunk.end_char,
+                    r.score,
+                    preview.replace('\n', " ")
+                );
+            }
+        }
+        Command::TopK(n) => {
+            session.opts.top_k = n;
+            println!("Retrieving {} chunks per question.", n);
+        }
+        Command::Filter(filter) => {
+            if filter.is_empty() {
+                println!("Filter cleared.");
+            } else {
+                println!("Filter set.");
+            }
+            session.set_filter(filter);
+        }
+        Command::Save(path) => {
+            let path = path.unwrap_or_else(|| {
+                PathBuf::from(format!("chat-{}.md", &session.id.to_string()[..8]))
+            });
+            fs::write(&path, session.transcript())?;
+            println!("Transcript written to {}.", path.display());
+        }
+        Command::Help => println!("{}", HELP),
+        Command::Quit => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_slash_commands() {
+        assert_eq!(parse_command("/topk 10").unwrap(), Command::TopK(10));
+        assert_eq!(
+            parse_command("/filter path:docs/**").unwrap(),
+            Command::Filter(QueryFilter {
+                paths: vec!["docs/**".to_string()],
+                ..QueryFilter::default()
+            })
+        );
+        assert_eq!(
+            parse_command("/filter").unwrap(),
+            Command::Filter(QueryFilter::default())
+        );
+        assert_eq!(parse_command("/save").unwrap(), Command::Save(None));
+        assert!(parse_command("/topk zero").is_err());
+        assert!(parse_command("/filter docs/**").is_err());
+        assert!(parse_command("/bogus").is_err());
+    }
+
+    #[test]
+    fn heuristic_rewrite_carries_topic_into_follow_ups() {
+        assert_eq!(
+            heuristic_rewrite("what is the borrow checker?", "why does it reject my code?"),
+            "why does it reject my code? (borrow checker)"
+        );
+        assert_eq!(
+            heuristic_rewrite(
+                "what is the borrow checker?",
+                "how do cargo workspaces work?"
+            ),
+            "how do cargo workspaces work?"
+        );
+    }
+}
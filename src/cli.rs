@@ -1,5 +1,17 @@
 use std::path::PathBuf;
-use clap::{Parser, Subcommand};
+
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use uuid::Uuid;
+
+use crate::context::ContextOrder;
+use crate::embedder::EmbedderSpec;
+use crate::expand::ExpansionKind;
+use crate::filter::{parse_doc_prefix, parse_key_value, parse_since};
+use crate::index::{IndexKind, DEFAULT_NPROBE};
+use crate::output::OutputFormat;
+use crate::rerank::RerankKind;
+use crate::store::DEFAULT_COLLECTION;
 
 /// Rust RAG CLI - Retrieval-Augmented Generation over local documents.
 #[derive(Parser, Debug)]
@@ -9,6 +21,11 @@ pub struct Cli {
     #[arg(long, global = true, default_value = "data/rag.db")]
     pub db: PathBuf,
 
+    /// Collection to read and write; `query` also takes several
+    /// (comma-separated) or `all`, and merges their results
+    #[arg(long, global = true, value_delimiter = ',', default_value = DEFAULT_COLLECTION)]
+    pub collection: Vec<String>,
+
     /// Use OpenAI embeddings instead of the local hash-based embedder
     #[arg(long, global = true)]
     pub openai_api_key: Option<String>,
@@ -17,15 +34,61 @@ pub struct Cli {
     #[arg(long, global = true, default_value = "text-embedding-3-small")]
     pub openai_model: String,
 
+    /// Generate answers with an LLM backend instead of printing snippets
+    #[arg(long, global = true, value_enum)]
+    pub llm: Option<LlmBackend>,
+
+    /// Chat API base URL [default: https://api.openai.com/v1 for openai,
+    /// http://localhost:11434 for ollama]
+    #[arg(long, global = true)]
+    pub llm_base_url: Option<String>,
+
+    /// Chat model name [default: gpt-4o-mini for openai, llama3.1 for ollama]
+    #[arg(long, global = true)]
+    pub llm_model: Option<String>,
+
+    /// Sampling temperature for answer generation
+    #[arg(long, global = true, default_value_t = 0.2)]
+    pub temperature: f32,
+
+    /// Maximum number of tokens to generate
+    #[arg(long, global = true, default_value_t = 512)]
+    pub max_tokens: u32,
+
+    /// Seconds to wait for a non-streamed LLM response (0 waits indefinitely)
+    #[arg(long, global = true, default_value_t = 120)]
+    pub llm_timeout: u64,
+
+    /// Prompt template preset used for generated answers and source lists
+    #[arg(long, global = true, default_value = "default")]
+    pub template: String,
+
+    /// Directory holding prompt template presets
+    /// [default: $XDG_CONFIG_HOME/rag/templates]
+    #[arg(long, global = true)]
+    pub template_dir: Option<PathBuf>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
+/// Chat backends usable for answer generation.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LlmBackend {
+    /// Any OpenAI-compatible `/chat/completions` endpoint
+    Openai,
+    /// Ollama `/api/chat`
+    Ollama,
+}
+
+// Parsed once per run; boxing the large `Query` variant buys nothing.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Ingest documents into the vector store
     Ingest {
-        /// Files or directories to ingest (recursively walks directories)
+        /// Files, directories (walked recursively) or archives (.zip, .tar,
+        /// .tar.gz) to ingest; `-` reads a single document from stdin
         #[arg(required = true)]
         paths: Vec<PathBuf>,
 
@@ -36,6 +99,23 @@ pub enum Commands {
         /// Overlap between consecutive chunks in characters
         #[arg(long, default_value_t = 64)]
         overlap: usize,
+
+        /// Document name to record for stdin input (`-`)
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Tag to attach to every ingested document (repeatable)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+
+        /// Metadata field to set on every ingested document, as key=value;
+        /// overrides Markdown front matter (repeatable)
+        #[arg(long = "meta", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+        meta: Vec<(String, String)>,
+
+        /// Skip archive members larger than this many MiB
+        #[arg(long, value_name = "MIB", default_value_t = 256)]
+        max_entry_mb: u64,
     },
 
     /// Query the corpus
@@ -50,8 +130,310 @@ pub enum Commands {
         /// Only show raw chunks, no synthesized answer
         #[arg(long)]
         raw_only: bool,
+
+        /// Approximate token budget for the context passed to the answer generator
+        #[arg(long, default_value_t = 2000)]
+        context_tokens: usize,
+
+        /// Order of context passages given to the answer generator
+        #[arg(long, value_enum, default_value_t = ContextOrder::Relevance)]
+        context_order: ContextOrder,
+
+        /// Print the answer as it is generated (Ctrl-C cancels the request)
+        #[arg(long)]
+        stream: bool,
+
+        /// Output format; json and jsonl follow a versioned schema
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+
+        /// Only search documents whose path matches this glob (repeatable)
+        #[arg(long = "path")]
+        paths: Vec<String>,
+
+        /// Only search documents with this file extension (repeatable)
+        #[arg(long = "ext")]
+        extensions: Vec<String>,
+
+        /// Only search documents ingested on or after this date (YYYY-MM-DD or RFC 3339)
+        #[arg(long, value_parser = parse_since)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only search documents carrying this tag (repeatable)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+
+        /// Only search documents whose id starts with this prefix; a short
+        /// prefix includes every document it matches (repeatable)
+        #[arg(long = "doc", value_parser = parse_doc_prefix)]
+        doc_ids: Vec<String>,
+
+        /// Only search documents whose metadata field has this value, as
+        /// key=value (repeatable; values of one key are alternatives)
+        #[arg(long = "meta", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+        meta: Vec<(String, String)>,
+
+        /// Diversify results with maximal marginal relevance (1.0 = relevance
+        /// only, lower = more diverse)
+        #[arg(long, value_name = "LAMBDA", value_parser = parse_unit_interval)]
+        mmr: Option<f32>,
+
+        /// Return at most N chunks from any one document
+        #[arg(long, value_name = "N")]
+        max_per_doc: Option<usize>,
+
+        /// Drop chunks whose calibrated score (0 = unrelated, 1 =
+        /// near-duplicate) is below this
+        #[arg(long, value_name = "SCORE")]
+        min_score: Option<f32>,
+
+        /// Drop chunks scoring below this fraction of the best chunk
+        #[arg(long, value_name = "RATIO", value_parser = parse_unit_interval)]
+        relative_cutoff: Option<f32>,
+
+        /// Exit with status 3 when no relevant context is found
+        #[arg(long)]
+        fail_if_empty: bool,
+
+        /// Show how each hit was scored: vector and BM25 scores, matched
+        /// terms, fusion and rerank stages, filters and hashed-token overlap
+        #[arg(long)]
+        explain: bool,
+
+        /// Rescore the best first-stage candidates with a reranker
+        #[arg(long, value_enum)]
+        rerank: Option<RerankKind>,
+
+        /// Number of first-stage candidates passed to the reranker
+        #[arg(long, default_value_t = 50)]
+        rerank_candidates: usize,
+
+        /// Base URL of the `/rerank` endpoint (e.g. https://api.cohere.com/v2);
+        /// the API key is read from RERANK_API_KEY
+        #[arg(long)]
+        rerank_url: Option<String>,
+
+        /// Model name sent to the `/rerank` endpoint
+        #[arg(long, default_value = "rerank-v3.5")]
+        rerank_model: String,
+
+        /// Directory with `model.onnx` and `tokenizer.json` for the cross-encoder
+        #[arg(long)]
+        rerank_model_dir: Option<PathBuf>,
+
+        /// Also search with expanded query variants and fuse the rankings
+        /// (comma-separated; rewrite and hyde need --llm)
+        #[arg(long, value_enum, value_delimiter = ',')]
+        expand: Vec<ExpansionKind>,
+
+        /// Extra `term: alternative, ...` entries for --expand synonyms
+        #[arg(long, value_name = "FILE")]
+        synonyms: Option<PathBuf>,
+
+        /// Number of LLM rewrites for --expand rewrite
+        #[arg(long, default_value_t = 3)]
+        expand_rewrites: usize,
+
+        /// Number of top hits used as feedback for --expand prf
+        #[arg(long, default_value_t = 3)]
+        prf_docs: usize,
+
+        /// Scan full-precision embeddings even if a quantized index is built
+        #[arg(long)]
+        exact: bool,
+
+        /// Rescore the best N quantized-index hits with full-precision
+        /// embeddings (binary indexes default to 10 per requested chunk)
+        #[arg(long, value_name = "N")]
+        rescore: Option<usize>,
+
+        /// IVF lists to scan (default: the index's --nprobe)
+        #[arg(long, value_name = "N")]
+        nprobe: Option<usize>,
+    },
+
+    /// Interactive multi-turn chat over the corpus
+    Chat {
+        /// Number of chunks to retrieve per question (change with /topk)
+        #[arg(long, default_value_t = 6)]
+        top_k: usize,
+
+        /// Approximate token budget for the context passed to the answer generator
+        #[arg(long, default_value_t = 2000)]
+        context_tokens: usize,
+
+        /// Order of context passages given to the answer generator
+        #[arg(long, value_enum, default_value_t = ContextOrder::Relevance)]
+        context_order: ContextOrder,
+
+        /// Resume a previous session by id
+        #[arg(long)]
+        session: Option<Uuid>,
+    },
+
+    /// Watch directories and keep the store in sync as files change
+    Watch {
+        /// Directories to watch recursively
+        #[arg(required = true)]
+        dirs: Vec<PathBuf>,
+
+        /// Target chunk size in characters
+        #[arg(long, default_value_t = 512)]
+        chunk_size: usize,
+
+        /// Overlap between consecutive chunks in characters
+        #[arg(long, default_value_t = 64)]
+        overlap: usize,
+
+        /// Quiet period (ms) after the last change before re-ingesting
+        #[arg(long, default_value_t = 500)]
+        debounce_ms: u64,
+
+        /// Re-ingest everything under the directories before watching
+        #[arg(long)]
+        initial: bool,
     },
 
     /// Show corpus statistics
     Stats {},
+
+    /// Build or evaluate the vector index
+    Index {
+        #[command(subcommand)]
+        action: IndexCommand,
+    },
+
+    /// Edit document tags and metadata
+    Docs {
+        #[command(subcommand)]
+        action: DocsCommand,
+    },
+
+    /// List and manage collections
+    Collections {
+        #[command(subcommand)]
+        action: CollectionsCommand,
+    },
+}
+
+/// `rag docs` subcommands.
+#[derive(Subcommand, Debug)]
+pub enum DocsCommand {
+    /// Add or remove tags and metadata fields of the selected documents
+    Tag {
+        /// Select documents whose path matches this glob (repeatable)
+        #[arg(long = "path")]
+        paths: Vec<String>,
+
+        /// Select the one document whose id starts with this prefix; an
+        /// ambiguous prefix is an error (repeatable)
+        #[arg(long = "doc", value_parser = parse_doc_prefix)]
+        doc_ids: Vec<String>,
+
+        /// Tag to add (repeatable)
+        #[arg(long = "add")]
+        add: Vec<String>,
+
+        /// Tag to remove (repeatable)
+        #[arg(long = "remove")]
+        remove: Vec<String>,
+
+        /// Metadata field to set, as key=value (repeatable)
+        #[arg(long = "meta", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+        meta: Vec<(String, String)>,
+
+        /// Metadata field to remove (repeatable)
+        #[arg(long = "unset", value_name = "KEY")]
+        unset: Vec<String>,
+    },
+}
+
+/// `rag collections` subcommands.
+#[derive(Subcommand, Debug)]
+pub enum CollectionsCommand {
+    /// List collections with their embedder, index and size
+    List {},
+
+    /// Create an empty collection
+    Create {
+        name: String,
+
+        /// Embedder for this collection: `local[:DIM]` or `openai[:MODEL]`
+        /// [default: the command-line embedder]
+        #[arg(long)]
+        embedder: Option<EmbedderSpec>,
+    },
+
+    /// Delete a collection and its documents
+    Drop { name: String },
+
+    /// Rename a collection
+    Rename { from: String, to: String },
+}
+
+/// `rag index` subcommands.
+#[derive(Subcommand, Debug)]
+pub enum IndexCommand {
+    /// Train the index (if needed) and encode every chunk
+    Build {
+        #[arg(long, value_enum)]
+        kind: IndexKind,
+
+        /// PQ subspaces, i.e. code bytes per vector; must divide the
+        /// embedding dimension
+        #[arg(long, default_value_t = 8)]
+        subspaces: usize,
+
+        /// k-means iterations when training
+        #[arg(long, default_value_t = 25)]
+        iterations: usize,
+
+        /// IVF lists (default: square root of the chunk count)
+        #[arg(long, value_name = "N")]
+        lists: Option<usize>,
+
+        /// IVF lists scanned per query by default
+        #[arg(long, default_value_t = DEFAULT_NPROBE)]
+        nprobe: usize,
+    },
+
+    /// Measure recall@k of the index against exact search, using stored
+    /// embeddings as queries
+    Eval {
+        /// Number of sample queries
+        #[arg(long, default_value_t = 100)]
+        queries: usize,
+
+        #[arg(long, default_value_t = 10)]
+        top_k: usize,
+
+        /// Rescore the best N index hits with full-precision embeddings
+        #[arg(long, value_name = "N")]
+        rescore: Option<usize>,
+
+        /// IVF lists to scan (default: the index's --nprobe)
+        #[arg(long, value_name = "N")]
+        nprobe: Option<usize>,
+    },
+
+    /// Retrain the IVF index if the corpus drifted since it was built
+    Retrain {
+        /// Retrain when the chunk count or the mean distance to the
+        /// assigned centroid changed by more than this fraction
+        #[arg(long, default_value_t = 0.2)]
+        threshold: f32,
+
+        /// Retrain even if the corpus has not drifted
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+fn parse_unit_interval(s: &str) -> Result<f32, String> {
+    let v: f32 = s.parse().map_err(|_| format!("{:?} is not a number", s))?;
+    if (0.0..=1.0).contains(&v) {
+        Ok(v)
+    } else {
+        Err(format!("{} is not between 0 and 1", v))
+    }
 }
@@ -0,0 +1,161 @@
+use anyhow::{bail, Result};
+
+use crate::cli::CollectionsCommand;
+use crate::embedder::Embedder;
+use crate::expand::{reciprocal_rank_fusion, RRF_K};
+use crate::models::SearchResult;
+use crate::query::{run_query_with, QueryOptions};
+use crate::store::Store;
+
+/// `--collection` value that selects every collection.
+pub const ALL_COLLECTIONS: &str = "all";
+
+/// Expand `all` and check that every named collection exists. Names keep
+/// their first position; duplicates are dropped.
+pub fn resolve_collections(store: &Store, names: &[String]) -> Result<Vec<String>> {
+    let mut out: Vec<String> = Vec::new();
+    for name in names {
+        let expanded = if name == ALL_COLLECTIONS {
+            store.collections()?.into_iter().map(|c| c.name).collect()
+        } else if store.collection_exists(name)? {
+            vec![name.clone()]
+        } else {
+            bail!("no collection `{}`; see `rag collections list`", name);
+        };
+        for name in expanded {
+            if !out.contains(&name) {
+                out.push(name);
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Run `question` against each collection with its own embedder (and
+/// index) and merge the results by rank. The store is left on its original
+/// collection.
+pub fn query_collections(
+    store: &mut Store,
+    names: &[String],
+    question: &str,
+    opts: &QueryOptions,
+    mut embedder_for: impl FnMut(&Store) -> Result<Box<dyn Embedder>>,
+) -> Result<Vec<SearchResult>> {
+    let original = store.collection().to_string();
+    let mut run_all = || -> Result<Vec<Vec<SearchResult>>> {
+        let mut lists = Vec::with_capacity(names.len());
+        for name in names {
+            store.use_collection(name)?;
+            let embedder = embedder_for(store)?;
+            lists.push(run_query_with(store, embedder.as_ref(), question, opts)?);
+        }
+        Ok(lists)
+    };
+    // Switch back even when a collection fails.
+    let lists = run_all();
+    store.use_collection(&original)?;
+    Ok(merge_results(lists?, opts.top_k))
+}
+
+/// The best `top_k` results across per-collection lists, fused by
+/// reciprocal rank. Scores from different embedders, or a mix of rerank and
+/// first-stage scores, are not on one scale, but each list's order is
+/// meaningful. Equal ranks keep the order of the lists.
+pub fn merge_results(lists: Vec<Vec<SearchResult>>, top_k: usize) -> Vec<SearchResult> {
+    let mut offset = 0;
+    let indexed: Vec<Vec<usize>> = lists
+        .iter()
+        .map(|list| {
+            let ids = (offset..offset + list.len()).collect();
+            offset += list.len();
+            ids
+        })
+        .collect();
+    let mut all: Vec<Option<SearchResult>> = lists.into_iter().flatten().map(Some).collect();
+    reciprocal_rank_fusion(&indexed, RRF_K)
+        .into_iter()
+        .take(top_k)
+        .filter_map(|(i, _)| all[i].take())
+        .collect()
+}
+
+/// Run a `rag collections` subcommand, printing to stdout.
+pub fn run_collections(store: &mut Store, action: &CollectionsCommand) -> Result<()> {
+    match action {
+        CollectionsCommand::List {} => {
+            println!(
+                "{:<20} {:>9} {:>8}  {:<8} {:<28} Created",
+                "Name", "Documents", "Chunks", "Index", "Embedder"
+            );
+            for c in store.collections()? {
+                let embedder = c
+                    .embedder
+                    .or(c.embedder_name)
+                    .unwrap_or_else(|| "(default)".to_string());
+                println!(
+                    "{:<20} {:>9} {:>8}  {:<8} {:<28} {}",
+                    c.name,
+                    c.documents,
+                    c.chunks,
+                    c.index_kind.name(),
+                    embedder,
+                    c.created_at.format("%Y-%m-%d %H:%M")
+                );
+            }
+        }
+        CollectionsCommand::Create { name, embedder } => {
+            let spec = embedder.as_ref().map(|e| e.to_string());
+            store.create_collection(name, spec.as_deref())?;
+            println!("Created collection `{}`", name);
+        }
+        CollectionsCommand::Drop { name } => {
+            let deleted = store.drop_collection(name)?;
+            println!("Dropped collection `{}` ({} documents)", name, deleted);
+        }
+        CollectionsCommand::Rename { from, to } => {
+            store.rename_collection(from, to)?;
+            println!("Renamed collection `{}` to `{}`", from, to);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Chunk;
+    use uuid::Uuid;
+
+    fn result(collection: &str, score: f32, rerank_score: Option<f32>) -> SearchResult {
+        SearchResult {
+            rerank_score,
+            collection: collection.to_string(),
+            ..SearchResult::new(
+                Chunk::new(Uuid::new_v4(), 0, "", 0),
+                format!("{}.md", collection),
+                score,
+            )
+        }
+    }
+
+    #[test]
+    fn merge_interleaves_collections_by_rank() {
+        // b's scores are on another scale; only the order within each list counts.
+        let merged = merge_results(
+            vec![
+                vec![
+                    result("a", 0.9, None),
+                    result("a", 0.5, None),
+                    result("a", 0.4, None),
+                ],
+                vec![result("b", 0.2, Some(9.5)), result("b", 0.7, Some(3.0))],
+            ],
+            4,
+        );
+        let order: Vec<(&str, f32)> = merged
+            .iter()
+            .map(|r| (r.collection.as_str(), r.relevance()))
+            .collect();
+        assert_eq!(order, [("a", 0.9), ("b", 9.5), ("a", 0.5), ("b", 3.0)]);
+    }
+}
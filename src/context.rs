@@ -0,0 +1,193 @@
+use std::collections::{HashMap, HashSet};
+
+use clap::ValueEnum;
+use uuid::Uuid;
+
+use crate::models::SearchResult;
+
+/// How packed context passages are ordered for the generator.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContextOrder {
+    /// Most relevant passage first
+    Relevance,
+    /// Grouped by document (best document first), in reading order
+    Position,
+}
+
+/// Knobs for building generator context from search results.
+#[derive(Debug, Clone)]
+pub struct ContextOptions {
+    /// Approximate token budget for all passages together
+    pub token_budget: usize,
+    pub order: ContextOrder,
+}
+
+impl Default for ContextOptions {
+    fn default() -> Self {
+        Self {
+            token_budget: 2000,
+            order: ContextOrder::Relevance,
+        }
+    }
+}
+
+/// Rough token count (~4 characters per token for English text).
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Turn ranked chunks into generator context: overlapping or adjacent
+/// chunks of the same document are merged into one passage, duplicate
+/// passages are dropped, and the most relevant passages that fit in the
+/// token budget are kept.
+///
+/// Merged passages keep the best member's id and scores, the first member's
+/// `chunk_index`, and a span covering all members.
+pub fn pack_context(results: &[SearchResult], opts: &ContextOptions) -> Vec<SearchResult> {
+    let mut passages = dedup(merge_neighbours(results));
+    passages.sort_by(|a, b| {
+        b.relevance()
+            .partial_cmp(&a.relevance())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let mut packed = Vec::new();
+    let mut used = 0usize;
+    for mut p in passages {
+        let remaining = opts.token_budget.saturating_sub(used);
+        if remaining == 0 {
+            break;
+        }
+        let cost = estimate_tokens(&p.chunk.text);
+        if cost > remaining {
+            if !packed.is_empty() {
+                continue; // a smaller passage may still fit
+            }
+            truncate_to_tokens(&mut p, remaining);
+        }
+        used += estimate_tokens(&p.chunk.text);
+        packed.push(p);
+    }
+
+    if opts.order == ContextOrder::Position {
+        // `packed` is in relevance order, so first appearance = best rank.
+        let mut doc_rank: HashMap<Uuid, usize> = HashMap::new();
+        for p in &packed {
+            let next = doc_rank.len();
+            doc_rank.entry(p.chunk.doc_id).or_insert(next);
+        }
+        packed.sort_by_key(|p| (doc_rank[&p.chunk.doc_id], p.chunk.start_char));
+    }
+    packed
+}
+
+fn merge_neighbours(results: &[SearchResult]) -> Vec<SearchResult> {
+    let mut sorted: Vec<&SearchResult> = results.iter().collect();
+    sorted.sort_by_key(|r| (r.chunk.doc_id, r.chunk.start_char));
+
+    let mut merged: Vec<SearchResult> = Vec::new();
+    for r in sorted {
+        match merged.last_mut() {
+            Some(cur) if touches(cur, r) => absorb(cur, r),
+            _ => merged.push(r.clone()),
+        }
+    }
+    merged
+}
+
+/// Same document and page, and `next` starts inside or right after `cur`.
+fn touches(cur: &SearchResult, next: &SearchResult) -> bool {
+    cur.chunk.doc_id == next.chunk.doc_id
+        && cur.chunk.page == next.chunk.page
+        && next.chunk.start_char <= cur.chunk.end_char
+}
+
+fn absorb(cur: &mut SearchResult, next: &SearchResult) {
+    if next.chunk.end_char > cur.chunk.end_char {
+        let overlap = (cur.chunk.end_char - next.chunk.start_char).max(0) as usize;
+        let tail: String = next.chunk.text.chars().skip(overlap).collect();
+        cur.chunk.text.push_str(&tail);
+        cur.chunk.end_char = next.chunk.end_char;
+    }
+    if next.relevance() > cur.relevance() {
+        cur.score = next.score;
+        cur.fused_score = next.fused_score;
+        cur.rerank_score = next.rerank_score;
+        cur.chunk.id = next.chunk.id;
+    }
+}
+
+/// Drop passages whose text repeats a better-scored one (e.g. the same file
+/// ingested twice under different paths).
+fn dedup(mut passages: Vec<SearchResult>) -> Vec<SearchResult> {
+    passages.sort_by(|a, b| {
+        b.relevance()
+            .partial_cmp(&a.relevance())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let mut seen = HashSet::new();
+    passages.retain(|p| {
+        let key = p.chunk.text.split_whitespace().collect::<Vec<_>>().join(" ");
+        seen.insert(key)
+    });
+    passages
+}
+
+fn truncate_to_tokens(p: &mut SearchResult, tokens: usize) {
+    let keep = tokens * 4;
+    p.chunk.text = p.chunk.text.chars().take(keep).collect();
+    p.chunk.end_char = p.chunk.start_char + keep as i32;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Chunk;
+
+    fn result(doc_id: Uuid, index: i32, start: i32, text: &str, score: f32) -> SearchResult {
+        SearchResult::new(Chunk::new(doc_id, index, text, start), format!("{}.md", doc_id), score)
+    }
+
+    #[test]
+    fn merges_overlapping_windows_of_the_same_document() {
+        let doc = Uuid::new_v4();
+        let other = Uuid::new_v4();
+        let results = vec![
+            result(doc, 1, 6, "ghijklmnop", 0.9),
+            result(doc, 0, 0, "abcdefghij", 0.5),
+            result(doc, 5, 40, "far away", 0.3),
+            result(other, 0, 0, "far   away", 0.2),
+        ];
+
+        let packed = pack_context(&results, &ContextOptions::default());
+
+        assert_eq!(packed.len(), 2);
+        assert_eq!(packed[0].chunk.text, "abcdefghijklmnop");
+        assert_eq!((packed[0].chunk.start_char, packed[0].chunk.end_char), (0, 16));
+        assert_eq!(packed[0].chunk.chunk_index, 0);
+        assert_eq!(packed[0].score, 0.9);
+        assert_eq!(packed[1].chunk.text, "far away");
+        assert_eq!(packed[1].chunk.doc_id, doc);
+    }
+
+    #[test]
+    fn respects_token_budget_and_position_order() {
+        let a = Uuid::new_v4();
+        let b = Uuid::new_v4();
+        let results = vec![
+            result(a, 3, 300, &"x".repeat(40), 0.9),
+            result(b, 0, 0, &"y".repeat(400), 0.8),
+            result(a, 0, 0, &"z".repeat(20), 0.7),
+        ];
+        let opts = ContextOptions {
+            token_budget: 20,
+            order: ContextOrder::Position,
+        };
+
+        let packed = pack_context(&results, &opts);
+
+        let starts: Vec<i32> = packed.iter().map(|p| p.chunk.start_char).collect();
+        assert_eq!(starts, vec![0, 300]);
+        assert!(packed.iter().all(|p| p.chunk.doc_id == a));
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::{bail, Result};
+
+use crate::cli::DocsCommand;
+use crate::filter::QueryFilter;
+use crate::store::Store;
+
+/// Run a `rag docs` subcommand, printing to stdout.
+pub fn run_docs(store: &Store, action: &DocsCommand) -> Result<()> {
+    match action {
+        DocsCommand::Tag {
+            paths,
+            doc_ids,
+            add,
+            remove,
+            meta,
+            unset,
+        } => {
+            // An empty selection would match every document.
+            if paths.is_empty() && doc_ids.is_empty() {
+                bail!("select documents with --path or --doc");
+            }
+            let filter = QueryFilter {
+                paths: paths.clone(),
+                doc_ids: resolve_doc_ids(store, doc_ids)?,
+                ..QueryFilter::default()
+            };
+            let docs = store.documents_matching(&filter)?;
+            if docs.is_empty() {
+                bail!("no documents match {}", filter);
+            }
+            let fields = meta
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone().into()))
+                .collect();
+            for (id, _) in &docs {
+                store.remove_document_tags(*id, remove)?;
+                store.add_document_tags(*id, add)?;
+                store.remove_document_fields(*id, unset)?;
+                store.set_document_fields(*id, &fields)?;
+            }
+
+            let ids: Vec<_> = docs.iter().map(|(id, _)| *id).collect();
+            let metadata = store.document_metadata(&ids)?;
+            for (id, path) in &docs {
+                let m = metadata.get(id).cloned().unwrap_or_default();
+                let fields: Vec<String> = m
+                    .fields
+                    .iter()
+                    .map(|(k, v)| format!("{}={}", k, v))
+                    .collect();
+                println!("{} {}", id, path);
+                println!("  tags : {}", m.tags.join(", "));
+                println!("  meta : {}", fields.join(", "));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Full ids of the documents named by `prefixes`. Each prefix must match
+/// exactly one document, so a mutating command never touches more than
+/// the caller meant.
+fn resolve_doc_ids(store: &Store, prefixes: &[String]) -> Result<Vec<String>> {
+    let mut ids = Vec::new();
+    for prefix in prefixes {
+        if prefix.trim().is_empty() {
+            bail!("--doc needs a document id or id prefix");
+        }
+        let filter = QueryFilter {
+            doc_ids: vec![prefix.clone()],
+            ..QueryFilter::default()
+        };
+        let matches = store.documents_matching(&filter)?;
+        match matches.as_slice() {
+            [] => bail!("no document id starts with {:?}", prefix),
+            [(id, _)] => ids.push(id.to_string()),
+            _ => {
+                let candidates: Vec<String> = matches
+                    .iter()
+                    .map(|(id, path)| format!("  {} {}", id, path))
+                    .collect();
+                bail!(
+                    "document id prefix {:?} is ambiguous; candidates:\n{}",
+                    prefix,
+                    candidates.join("\n")
+                );
+            }
+        }
+    }
+    Ok(ids)
+}
@@ -1,3 +1,7 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
 use anyhow::{anyhow, Result};
 use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
@@ -6,6 +10,102 @@ use serde::{Deserialize, Serialize};
 pub trait Embedder {
     fn name(&self) -> &'static str;
     fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>>;
+
+    /// How this embedder's cosine similarities map onto query scores.
+    fn calibration(&self) -> Calibration {
+        Calibration::IDENTITY
+    }
+
+    /// Which features of `query` and `text` share vector dimensions, for
+    /// embedders where that is meaningful.
+    fn token_overlap(&self, _query: &str, _text: &str) -> Option<Vec<TokenOverlap>> {
+        None
+    }
+}
+
+/// Query and passage tokens hashed into the same `LocalEmbedder` bucket.
+/// Different tokens in one bucket are hash collisions.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TokenOverlap {
+    pub bucket: usize,
+    pub query_tokens: Vec<String>,
+    pub text_tokens: Vec<String>,
+}
+
+/// Linear map from raw cosine similarity to a score where 0 means
+/// "unrelated" and 1 "near-duplicate", so `--min-score` means the same
+/// thing whichever embedder built the index.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Calibration {
+    /// Typical similarity of unrelated texts
+    pub floor: f32,
+    /// Typical similarity of paraphrases
+    pub ceiling: f32,
+}
+
+impl Calibration {
+    pub const IDENTITY: Self = Self {
+        floor: 0.0,
+        ceiling: 1.0,
+    };
+
+    pub fn apply(&self, similarity: f32) -> f32 {
+        (similarity - self.floor) / (self.ceiling - self.floor)
+    }
+
+    /// The cosine similarity a calibrated score came from.
+    pub fn similarity(&self, score: f32) -> f32 {
+        self.floor + score * (self.ceiling - self.floor)
+    }
+}
+
+/// Which embedder a collection uses: `local[:DIM]` or `openai[:MODEL]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmbedderSpec {
+    Local { dim: usize },
+    OpenAi { model: String },
+}
+
+impl EmbedderSpec {
+    pub const DEFAULT_LOCAL_DIM: usize = 256;
+    pub const DEFAULT_OPENAI_MODEL: &'static str = "text-embedding-3-small";
+}
+
+impl FromStr for EmbedderSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, arg) = match s.split_once(':') {
+            Some((kind, arg)) => (kind, Some(arg)),
+            None => (s, None),
+        };
+        match kind {
+            "local" => {
+                let dim = match arg {
+                    Some(d) => d
+                        .parse()
+                        .ok()
+                        .filter(|&d| d > 0)
+                        .ok_or_else(|| format!("invalid dimension {:?}", d))?,
+                    None => Self::DEFAULT_LOCAL_DIM,
+                };
+                Ok(Self::Local { dim })
+            }
+            "openai" => Ok(Self::OpenAi {
+                model: arg.filter(|m| !m.is_empty()).unwrap_or(Self::DEFAULT_OPENAI_MODEL).to_string(),
+            }),
+            _ => Err(format!("unknown embedder {:?}; expected local[:DIM] or openai[:MODEL]", s)),
+        }
+    }
+}
+
+impl fmt::Display for EmbedderSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Local { dim } => write!(f, "local:{}", dim),
+            Self::OpenAi { model } => write!(f, "openai:{}", model),
+        }
+    }
 }
 
 /// Simple local hash-based embedder (bag-of-words → fixed-size vector).
@@ -32,6 +132,41 @@ impl Embedder for LocalEmbedder {
         "local-hash-embedding-256"
     }
 
+    /// Bag-of-words counts: unrelated passages share little beyond stop
+    /// words (~0.05), close paraphrases reuse about half their words (~0.6).
+    fn calibration(&self) -> Calibration {
+        Calibration {
+            floor: 0.05,
+            ceiling: 0.6,
+        }
+    }
+
+    fn token_overlap(&self, query: &str, text: &str) -> Option<Vec<TokenOverlap>> {
+        let buckets = |s: &str| {
+            let mut map: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+            for token in s.split_whitespace() {
+                let tokens = map.entry(self.hash_token(token)).or_default();
+                if !tokens.iter().any(|t| t == token) {
+                    tokens.push(token.to_string());
+                }
+            }
+            map
+        };
+        let mut text_buckets = buckets(text);
+        Some(
+            buckets(query)
+                .into_iter()
+                .filter_map(|(bucket, query_tokens)| {
+                    Some(TokenOverlap {
+                        bucket,
+                        query_tokens,
+                        text_tokens: text_buckets.remove(&bucket)?,
+                    })
+                })
+                .collect(),
+        )
+    }
+
     fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
         let mut all = Vec::with_capacity(texts.len());
         for t in texts {
@@ -85,6 +220,15 @@ impl Embedder for OpenAIEmbedder {
         "openai-embeddings"
     }
 
+    /// `text-embedding-3-*` rarely scores unrelated text below ~0.1 and
+    /// close paraphrases above ~0.85.
+    fn calibration(&self) -> Calibration {
+        Calibration {
+            floor: 0.1,
+            ceiling: 0.85,
+        }
+    }
+
     fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
         if texts.is_empty() {
             return Ok(vec![]);
@@ -131,4 +275,56 @@ mod tests {
         assert_eq!(out[0].len(), 32);
         assert_eq!(out[1].len(), 32);
     }
+
+    #[test]
+    fn calibration_rescales_similarity() {
+        let c = Calibration {
+            floor: 0.1,
+            ceiling: 0.85,
+        };
+        assert!(c.apply(0.1).abs() < 1e-6);
+        assert!((c.apply(0.85) - 1.0).abs() < 1e-6);
+        assert!(c.apply(0.0) < 0.0);
+        assert_eq!(Calibration::IDENTITY.apply(0.42), 0.42);
+        assert!((c.similarity(c.apply(0.3)) - 0.3).abs() < 1e-6);
+    }
+
+    #[test]
+    fn local_embedder_calibration_separates_paraphrases_from_unrelated_text() {
+        let emb = LocalEmbedder::new(256);
+        let texts = [
+            "Rust guarantees memory safety without a garbage collector.",
+            "Rust keeps memory safe without garbage collection.",
+            "The cat sat on the mat.",
+        ]
+        .map(String::from);
+        let v = emb.embed(&texts).unwrap();
+        let cos = |a: &[f32], b: &[f32]| {
+            let norm = |x: &[f32]| x.iter().map(|y| y * y).sum::<f32>().sqrt();
+            a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>() / (norm(a) * norm(b))
+        };
+        let c = emb.calibration();
+        assert!(c.apply(cos(&v[0], &v[1])) > 0.8);
+        assert!(c.apply(cos(&v[0], &v[2])) < 0.2);
+    }
+
+    #[test]
+    fn local_embedder_reports_shared_buckets() {
+        let emb = LocalEmbedder::new(1024);
+        let overlap = emb.token_overlap("what is rust", "rust is fast").unwrap();
+        let shared: Vec<&str> = overlap.iter().map(|o| o.query_tokens[0].as_str()).collect();
+        assert!(shared.contains(&"rust") && shared.contains(&"is"));
+        assert!(overlap.iter().all(|o| o.query_tokens == o.text_tokens));
+        assert!(LocalEmbedder::new(1).token_overlap("a", "b").unwrap()[0].text_tokens == ["b"]);
+    }
+
+    #[test]
+    fn embedder_specs_parse_and_print() {
+        assert_eq!("local".parse(), Ok(EmbedderSpec::Local { dim: 256 }));
+        assert_eq!("local:64".parse::<EmbedderSpec>().unwrap().to_string(), "local:64");
+        let openai: EmbedderSpec = "openai".parse().unwrap();
+        assert_eq!(openai.to_string(), "openai:text-embedding-3-small");
+        assert!("local:0".parse::<EmbedderSpec>().is_err());
+        assert!("cohere".parse::<EmbedderSpec>().is_err());
+    }
 }
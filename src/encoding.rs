@@ -0,0 +1,105 @@
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE};
+use unicode_normalization::UnicodeNormalization;
+
+/// Decode raw bytes to a `String`, returning the name of the detected encoding.
+///
+/// Order: byte-order mark, BOM-less UTF-16 heuristic, strict UTF-8, then a
+/// statistical guess among legacy encodings (Latin-1/Windows-1252, Shift_JIS, ...).
+/// The BOM itself is never part of the returned text.
+pub fn decode_text(bytes: &[u8]) -> (String, &'static str) {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+        return (text.into_owned(), encoding.name());
+    }
+    if let Some(encoding) = guess_utf16(bytes) {
+        let (text, _) = encoding.decode_without_bom_handling(bytes);
+        return (text.into_owned(), encoding.name());
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return (text.to_string(), "UTF-8");
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    let encoding = detector.guess(None, true);
+    let (text, _) = encoding.decode_without_bom_handling(bytes);
+    (text.into_owned(), encoding.name())
+}
+
+/// True if the bytes carry a UTF-16 BOM or look like BOM-less UTF-16 text.
+pub fn is_utf16(bytes: &[u8]) -> bool {
+    matches!(
+        Encoding::for_bom(bytes),
+        Some((e, _)) if e == UTF_16LE || e == UTF_16BE
+    ) || guess_utf16(bytes).is_some()
+}
+
+/// BOM-less UTF-16 shows up as mostly-ASCII text with every other byte zero.
+fn guess_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    let sample = &bytes[..bytes.len().min(4096) & !1];
+    if sample.len() < 4 {
+        return None;
+    }
+    let pairs = sample.len() / 2;
+    let even_zeros = sample.iter().step_by(2).filter(|b| **b == 0).count();
+    let odd_zeros = sample
+        .iter()
+        .skip(1)
+        .step_by(2)
+        .filter(|b| **b == 0)
+        .count();
+
+    if odd_zeros * 10 >= pairs * 8 && even_zeros * 10 < pairs {
+        Some(UTF_16LE)
+    } else if even_zeros * 10 >= pairs * 8 && odd_zeros * 10 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Clean decoded text before chunking: NFC normalization, `\r\n`/`\r` to
+/// `\n`, and removal of control characters other than newline and tab
+/// (including stray BOMs / zero-width no-break spaces).
+pub fn normalize_text(text: &str) -> String {
+    let unified = text.replace("\r\n", "\n").replace('\r', "\n");
+    unified
+        .nfc()
+        .filter(|c| *c == '\n' || *c == '\t' || !(c.is_control() || *c == '\u{feff}'))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_latin1_utf16_and_strips_boms() {
+        let latin1 = b"Caf\xe9 cr\xe8me br\xfbl\xe9e, na\xefve fa\xe7ade";
+        let (text, enc) = decode_text(latin1);
+        assert_eq!(text, "Café crème brûlée, naïve façade");
+        assert_eq!(enc, "windows-1252");
+
+        let mut utf16: Vec<u8> = vec![0xFF, 0xFE];
+        utf16.extend("Hello".encode_utf16().flat_map(|u| u.to_le_bytes()));
+        assert_eq!(decode_text(&utf16), ("Hello".to_string(), "UTF-16LE"));
+
+        let bomless: Vec<u8> = "Hello"
+            .encode_utf16()
+            .flat_map(|u| u.to_be_bytes())
+            .collect();
+        assert_eq!(decode_text(&bomless), ("Hello".to_string(), "UTF-16BE"));
+
+        assert_eq!(
+            decode_text(b"\xEF\xBB\xBFplain"),
+            ("plain".to_string(), "UTF-8")
+        );
+    }
+
+    #[test]
+    fn normalizes_to_nfc_and_drops_control_chars() {
+        let decomposed = "Cafe\u{301}\r\nline\u{0}two\u{7}\tend\u{feff}";
+        assert_eq!(normalize_text(decomposed), "Caf\u{e9}\nlinetwo\tend");
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::generate::Generator;
+use crate::score::normalize;
+
+/// Query expansion strategies selectable with `rag query --expand`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExpansionKind {
+    /// Append synonyms / spelled-out abbreviations from a dictionary
+    Synonyms,
+    /// Pseudo-relevance feedback: move the query vector towards the top hits
+    Prf,
+    /// Ask the LLM for alternative phrasings of the question
+    Rewrite,
+    /// Ask the LLM for a hypothetical answer passage and search with it
+    Hyde,
+}
+
+/// Term -> alternatives used for synonym expansion.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SynonymMap {
+    entries: HashMap<String, Vec<String>>,
+}
+
+/// Common abbreviations in technical corpora.
+const BUILTIN_SYNONYMS: &str = "\
+api: application programming interface
+cli: command line interface
+db: database
+k8s: kubernetes
+llm: large language model
+ml: machine learning
+os: operating system
+rag: retrieval augmented generation
+repo: repository
+config: configuration
+auth: authentication
+perf: performance
+deps: dependencies
+env: environment
+";
+
+impl SynonymMap {
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN_SYNONYMS).expect("built-in synonyms")
+    }
+
+    /// One `term: alternative, alternative` entry per line; `#` starts a comment.
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut map = Self::default();
+        for (n, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (term, alts) = line
+                .split_once(':')
+                .ok_or_else(|| anyhow!("line {}: expected `term: alternative, ...`", n + 1))?;
+            let alts = alts
+                .split(',')
+                .map(|a| a.trim().to_lowercase())
+                .filter(|a| !a.is_empty());
+            map.entries
+                .entry(term.trim().to_lowercase())
+                .or_default()
+                .extend(alts);
+        }
+        Ok(map)
+    }
+
+    /// Built-in entries plus those in `path`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let source =
+            fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        let mut map = Self::builtin();
+        let extra = Self::parse(&source).with_context(|| format!("in {}", path.display()))?;
+        for (term, alts) in extra.entries {
+            map.entries.entry(term).or_default().extend(alts);
+        }
+        Ok(map)
+    }
+
+    /// The question with alternatives for its known terms appended, or
+    /// `None` if no term has an entry.
+    pub fn expand(&self, question: &str) -> Option<String> {
+        let lower = question.to_lowercase();
+        let mut added: Vec<&str> = Vec::new();
+        for word in lower.split(|c: char| !c.is_alphanumeric()) {
+            for alt in self.entries.get(word).into_iter().flatten() {
+                if !lower.contains(alt.as_str()) && !added.contains(&alt.as_str()) {
+                    added.push(alt);
+                }
+            }
+        }
+        if added.is_empty() {
+            None
+        } else {
+            Some(format!("{} {}", question, added.join(" ")))
+        }
+    }
+}
+
+/// One query a search ran with, and where it came from (`question`,
+/// `synonyms`, `rewrite`, `hyde` or `prf`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QueryVariant {
+    pub kind: &'static str,
+    pub text: String,
+}
+
+impl QueryVariant {
+    pub fn new(kind: &'static str, text: &str) -> Self {
+        Self {
+            kind,
+            text: text.to_string(),
+        }
+    }
+}
+
+/// Settings for multi-query retrieval. With no strategy enabled, queries
+/// run as a single vector search.
+#[derive(Clone, Default)]
+pub struct QueryExpansion {
+    pub synonyms: Option<SynonymMap>,
+    /// Number of top hits used as pseudo-relevant feedback (Rocchio)
+    pub prf_docs: Option<usize>,
+    /// LLM used for rewrites and HyDE
+    pub generator: Option<Arc<dyn Generator>>,
+    /// Number of LLM rewrites to request (0 disables)
+    pub rewrites: usize,
+    pub hyde: bool,
+}
+
+impl fmt::Debug for QueryExpansion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QueryExpansion")
+            .field("synonyms", &self.synonyms.is_some())
+            .field("prf_docs", &self.prf_docs)
+            .field("generator", &self.generator.as_ref().map(|g| g.name()))
+            .field("rewrites", &self.rewrites)
+            .field("hyde", &self.hyde)
+            .finish()
+    }
+}
+
+const REWRITE_PROMPT: &str = "You rewrite search queries. Reply with alternative phrasings \
+of the user's question that use different words and spell out abbreviations, one per line, \
+without numbering or commentary.";
+
+const HYDE_PROMPT: &str = "Write a short factual passage (two or three sentences) that \
+answers the user's question, as it might appear in documentation. Do not mention that the \
+passage is hypothetical.";
+
+impl QueryExpansion {
+    pub fn is_enabled(&self) -> bool {
+        self.synonyms.is_some() || self.prf_docs.is_some() || self.rewrites > 0 || self.hyde
+    }
+
+    /// Texts to embed and search with; the question itself comes first.
+    pub fn text_variants(&self, question: &str) -> Result<Vec<QueryVariant>> {
+        let mut variants = vec![QueryVariant::new("question", question)];
+        if let Some(v) = self.synonyms.as_ref().and_then(|s| s.expand(question)) {
+            variants.push(QueryVariant::new("synonyms", &v));
+        }
+        if self.rewrites > 0 || self.hyde {
+            let generator = self
+                .generator
+                .as_ref()
+                .ok_or_else(|| anyhow!("LLM query rewrites and HyDE need --llm"))?;
+            if self.rewrites > 0 {
+                let user = format!("Give {} alternatives for: {}", self.rewrites, question);
+                let reply = generator.complete(REWRITE_PROMPT, &user)?;
+                variants.extend(
+                    parse_rewrites(&reply, self.rewrites)
+                        .iter()
+                        .map(|r| QueryVariant::new("rewrite", r)),
+                );
+            }
+            if self.hyde {
+                let passage = generator.complete(HYDE_PROMPT, question)?;
+                if !passage.trim().is_empty() {
+                    variants.push(QueryVariant::new("hyde", passage.trim()));
+                }
+            }
+        }
+        Ok(variants)
+    }
+}
+
+/// Up to `n` non-empty lines, with list markers and quotes removed.
+fn parse_rewrites(reply: &str, n: usize) -> Vec<String> {
+    reply
+        .lines()
+        .map(|l| {
+            strip_list_marker(l.trim())
+                .trim_matches('"')
+                .trim()
+                .to_string()
+        })
+        .filter(|l| !l.is_empty())
+        .take(n)
+        .collect()
+}
+
+/// `line` without a leading `-`, `*`, `1.` or `1)` marker. A marker must be
+/// followed by whitespace, so text that merely starts with a number (`2024
+/// tax rules`, `3D printing`) is kept whole.
+fn strip_list_marker(line: &str) -> &str {
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let marker = match line[digits..].chars().next() {
+        Some('.' | ')') if digits > 0 => digits + 1,
+        Some('-' | '*') if digits == 0 => 1,
+        _ => return line,
+    };
+    match line[marker..].strip_prefix(char::is_whitespace) {
+        Some(rest) => rest.trim_start(),
+        None => line,
+    }
+}
+
+/// Rocchio relevance feedback without negatives: `alpha * q + beta * mean(d)`,
+/// over unit-length vectors so both terms are on the same scale.
+pub fn rocchio(query: &[f32], feedback: &[&[f32]], alpha: f32, beta: f32) -> Vec<f32> {
+    let mut out = query.to_vec();
+    normalize(&mut out);
+    out.iter_mut().for_each(|x| *x *= alpha);
+    if feedback.is_empty() {
+        return out;
+    }
+    let weight = beta / feedback.len() as f32;
+    for d in feedback {
+        let mut d = d.to_vec();
+        normalize(&mut d);
+        for (o, x) in out.iter_mut().zip(d) {
+            *o += weight * x;
+        }
+    }
+    out
+}
+
+/// Default Rocchio weights for the query and the feedback centroid.
+pub const ROCCHIO_ALPHA: f32 = 1.0;
+pub const ROCCHIO_BETA: f32 = 0.75;
+
+/// Constant from the original RRF paper; dampens the head of each list.
+pub const RRF_K: f32 = 60.0;
+
+/// Reciprocal rank fusion: each item scores `sum(1 / (k + rank))` over the
+/// ranked lists it appears in (ranks are 1-based). Best first.
+pub fn reciprocal_rank_fusion(lists: &[Vec<usize>], k: f32) -> Vec<(usize, f32)> {
+    let mut scores: HashMap<usize, f32> = HashMap::new();
+    for list in lists {
+        for (rank, &item) in list.iter().enumerate() {
+            *scores.entry(item).or_default() += 1.0 / (k + rank as f32 + 1.0);
+        }
+    }
+    let mut fused: Vec<(usize, f32)> = scores.into_iter().collect();
+    fused.sort_by(|a, b| {
+        b.1.partial_cmp(&a.1)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(a.0.cmp(&b.0))
+    });
+    fused
+}
+
+/// Reject combinations that cannot work before any retrieval happens.
+pub fn check_kinds(kinds: &[ExpansionKind], has_llm: bool) -> Result<()> {
+    let needs_llm = kinds
+        .iter()
+        .any(|k| matches!(k, ExpansionKind::Rewrite | ExpansionKind::Hyde));
+    if needs_llm && !has_llm {
+        bail!("--expand rewrite/hyde need an LLM backend (--llm)");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn synonyms_spell_out_known_terms() {
+        let map = SynonymMap::parse("db: database, datastore\n# comment\nk8s: kubernetes").unwrap();
+        assert_eq!(
+            map.expand("How do I back up the DB on k8s?").unwrap(),
+            "How do I back up the DB on k8s? database datastore kubernetes"
+        );
+        assert_eq!(map.expand("the database is slow"), None);
+        assert!(SynonymMap::parse("no separator").is_err());
+    }
+
+    #[test]
+    fn parses_rewrite_lists() {
+        let reply = "1. What is the Rust language?\n- \"Rust programming overview\"\n\n3) extra";
+        assert_eq!(
+            parse_rewrites(reply, 2),
+            vec!["What is the Rust language?", "Rust programming overview"]
+        );
+
+        let reply =
+            "1. 2024 tax filing rules\n3D printing basics\n2) 10.5 release notes\n-5 degrees";
+        assert_eq!(
+            parse_rewrites(reply, 4),
+            vec![
+                "2024 tax filing rules",
+                "3D printing basics",
+                "10.5 release notes",
+                "-5 degrees"
+            ]
+        );
+    }
+
+    #[test]
+    fn rocchio_moves_query_towards_feedback() {
+        let q = rocchio(&[2.0, 0.0], &[&[0.0, 5.0]], 1.0, 0.5);
+        assert_eq!(q, vec![1.0, 0.5]);
+    }
+
+    #[test]
+    fn rrf_rewards_items_ranked_well_in_several_lists() {
+        let fused = reciprocal_rank_fusion(&[vec![1, 2, 3], vec![2, 3, 1], vec![2]], 60.0);
+        assert_eq!(fused[0].0, 2);
+        assert!((fused[0].1 - (1.0 / 62.0 + 2.0 / 61.0)).abs() < 1e-6);
+    }
+}
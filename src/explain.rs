@@ -0,0 +1,294 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::embedder::{Calibration, Embedder, TokenOverlap};
+use crate::expand::QueryVariant;
+use crate::models::SearchResult;
+use crate::query::{QueryOptions, QueryTrace};
+use crate::store::Store;
+use crate::text::content_terms;
+
+/// BM25 term-frequency saturation and length normalisation.
+const BM25_K1: f32 = 1.2;
+const BM25_B: f32 = 0.75;
+/// Chunk texts read per batch while collecting BM25 statistics.
+const TEXT_BATCH: usize = 1024;
+
+/// Why `rag query --explain` returned what it did.
+#[derive(Debug, Serialize)]
+pub struct QueryExplanation {
+    /// Active filter in `QueryFilter::parse` syntax (empty if none)
+    pub filter: String,
+    pub min_score: Option<f32>,
+    pub relative_cutoff: Option<f32>,
+    pub mmr_lambda: Option<f32>,
+    pub max_per_doc: Option<usize>,
+    pub calibration: Calibration,
+    pub variants: Vec<QueryVariant>,
+    /// Chunks scored after filtering
+    pub scored: usize,
+    /// Candidates left after the score thresholds
+    pub passed_thresholds: usize,
+    pub reranker: Option<&'static str>,
+    /// One entry per result, in result order
+    pub hits: Vec<HitExplanation>,
+}
+
+/// Score breakdown for one result.
+#[derive(Debug, Serialize)]
+pub struct HitExplanation {
+    pub chunk_id: Uuid,
+    /// Raw cosine similarity to the question
+    pub cosine: f32,
+    /// `cosine` after calibration; what thresholds compare against
+    pub score: f32,
+    /// Okapi BM25 of the question against the chunk, over the filtered
+    /// corpus. Diagnostic only: retrieval does not use it.
+    pub bm25: f32,
+    /// Question terms (stopwords removed) found in the chunk
+    pub matched_terms: Vec<String>,
+    /// 1-based rank in each query variant's list; empty without expansion
+    pub variant_ranks: Vec<Option<usize>>,
+    pub fused_score: Option<f32>,
+    /// Rank before reranking, when a reranker ran
+    pub first_stage_rank: Option<usize>,
+    pub rerank_score: Option<f32>,
+    /// Shared hash buckets, for embedders that report them
+    pub token_overlap: Option<Vec<TokenOverlap>>,
+}
+
+/// Explain `results` of a traced query run. Rescans the filtered corpus
+/// for BM25 statistics.
+pub fn explain(
+    store: &Store,
+    embedder: &dyn Embedder,
+    question: &str,
+    opts: &QueryOptions,
+    results: &[SearchResult],
+    trace: &QueryTrace,
+) -> Result<QueryExplanation> {
+    let mut bm25 = Bm25::default();
+    store.scan_texts(&opts.filter, TEXT_BATCH, |_, texts| {
+        for text in texts {
+            bm25.add(&content_terms(text));
+        }
+        Ok(())
+    })?;
+    let q_terms = unique(content_terms(question));
+    let calibration = embedder.calibration();
+
+    let hits = results
+        .iter()
+        .map(|r| {
+            let terms = content_terms(&r.chunk.text);
+            let present: HashSet<&str> = terms.iter().map(String::as_str).collect();
+            HitExplanation {
+                chunk_id: r.chunk.id,
+                cosine: calibration.similarity(r.score),
+                score: r.score,
+                bm25: bm25.score(&q_terms, &terms),
+                matched_terms: q_terms
+                    .iter()
+                    .filter(|t| present.contains(t.as_str()))
+                    .cloned()
+                    .collect(),
+                variant_ranks: trace
+                    .variant_ranks
+                    .get(&r.chunk.id)
+                    .cloned()
+                    .unwrap_or_default(),
+                fused_score: r.fused_score,
+                first_stage_rank: trace.first_stage_ranks.get(&r.chunk.id).copied(),
+                rerank_score: r.rerank_score,
+                token_overlap: embedder.token_overlap(question, &r.chunk.text),
+            }
+        })
+        .collect();
+
+    Ok(QueryExplanation {
+        filter: opts.filter.to_string(),
+        min_score: opts.min_score,
+        relative_cutoff: opts.relative_cutoff,
+        mmr_lambda: opts.mmr_lambda,
+        max_per_doc: opts.max_per_doc,
+        calibration,
+        variants: trace.variants.clone(),
+        scored: trace.scored,
+        passed_thresholds: trace.passed_thresholds,
+        reranker: opts.reranker.as_ref().map(|r| r.name()),
+        hits,
+    })
+}
+
+impl QueryExplanation {
+    /// Query-wide part of the explanation, one fact per line.
+    pub fn summary_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "filter: {}",
+            if self.filter.is_empty() {
+                "none"
+            } else {
+                &self.filter
+            }
+        )];
+        lines.push(format!(
+            "scored {} chunks, {} passed thresholds (min-score {}, relative-cutoff {})",
+            self.scored,
+            self.passed_thresholds,
+            opt(self.min_score),
+            opt(self.relative_cutoff)
+        ));
+        lines.push(format!(
+            "calibration: floor {:.2}, ceiling {:.2}",
+            self.calibration.floor, self.calibration.ceiling
+        ));
+        if self.variants.len() > 1 {
+            for (i, v) in self.variants.iter().enumerate() {
+                lines.push(format!("variant {}: {}: {}", i + 1, v.kind, v.text));
+            }
+        }
+        if let Some(name) = self.reranker {
+            lines.push(format!("reranker: {}", name));
+        }
+        if self.mmr_lambda.is_some() || self.max_per_doc.is_some() {
+            lines.push(format!(
+                "diversity: mmr {}, max-per-doc {}",
+                opt(self.mmr_lambda),
+                self.max_per_doc.map_or("-".to_string(), |n| n.to_string())
+            ));
+        }
+        lines
+    }
+}
+
+impl HitExplanation {
+    /// Per-hit part of the explanation, one stage per line.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "vector: cosine {:.4} -> score {:.4}",
+            self.cosine, self.score
+        )];
+        lines.push(format!(
+            "bm25: {:.4} matched [{}]",
+            self.bm25,
+            self.matched_terms.join(", ")
+        ));
+        if !self.variant_ranks.is_empty() {
+            let ranks: Vec<String> = self
+                .variant_ranks
+                .iter()
+                .map(|r| r.map_or("-".to_string(), |r| format!("#{}", r)))
+                .collect();
+            lines.push(format!(
+                "fusion: ranks {} -> fused {}",
+                ranks.join(" "),
+                opt(self.fused_score)
+            ));
+        }
+        if let Some(score) = self.rerank_score {
+            lines.push(format!(
+                "rerank: {:.4} (first stage #{})",
+                score,
+                self.first_stage_rank
+                    .map_or("?".to_string(), |r| r.to_string())
+            ));
+        }
+        if let Some(overlap) = &self.token_overlap {
+            let parts: Vec<String> = overlap
+                .iter()
+                .map(|o| {
+                    if o.query_tokens == o.text_tokens {
+                        o.query_tokens.join("/")
+                    } else {
+                        format!(
+                            "{}~{} (collision, bucket {})",
+                            o.query_tokens.join("/"),
+                            o.text_tokens.join("/"),
+                            o.bucket
+                        )
+                    }
+                })
+                .collect();
+            lines.push(format!("hashed tokens: [{}]", parts.join(", ")));
+        }
+        lines
+    }
+}
+
+fn opt(v: Option<f32>) -> String {
+    v.map_or("-".to_string(), |v| format!("{:.4}", v))
+}
+
+fn unique(terms: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    terms
+        .into_iter()
+        .filter(|t| seen.insert(t.clone()))
+        .collect()
+}
+
+/// Document frequencies and lengths of a tokenised corpus, accumulated one
+/// document at a time.
+#[derive(Default)]
+struct Bm25 {
+    docs: usize,
+    total_len: usize,
+    df: HashMap<String, usize>,
+}
+
+impl Bm25 {
+    fn add(&mut self, doc: &[String]) {
+        for term in doc.iter().collect::<HashSet<_>>() {
+            *self.df.entry(term.clone()).or_default() += 1;
+        }
+        self.docs += 1;
+        self.total_len += doc.len();
+    }
+
+    fn score(&self, query: &[String], doc: &[String]) -> f32 {
+        let avg_len = self.total_len as f32 / self.docs.max(1) as f32;
+        let docs = self.docs as f32;
+        let len_norm = 1.0 - BM25_B + BM25_B * doc.len() as f32 / avg_len.max(1.0);
+        query
+            .iter()
+            .map(|term| {
+                let tf = doc.iter().filter(|t| *t == term).count() as f32;
+                if tf == 0.0 {
+                    return 0.0;
+                }
+                let df = self.df.get(term).copied().unwrap_or(0) as f32;
+                let idf = (1.0 + (docs - df + 0.5) / (df + 0.5)).ln();
+                idf * tf * (BM25_K1 + 1.0) / (tf + BM25_K1 * len_norm)
+            })
+            .fold(0.0, |acc, s| acc + s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(s: &str) -> Vec<String> {
+        content_terms(s)
+    }
+
+    #[test]
+    fn bm25_prefers_rare_terms_and_repeats() {
+        let corpus = vec![
+            terms("rust borrow checker"),
+            terms("rust cargo crates"),
+            terms("rust rust compiler"),
+        ];
+        let mut bm25 = Bm25::default();
+        for doc in &corpus {
+            bm25.add(doc);
+        }
+        let q = terms("rust borrow");
+        assert!(bm25.score(&q, &corpus[0]) > bm25.score(&q, &corpus[1]));
+        assert!(bm25.score(&q, &corpus[2]) > bm25.score(&q, &corpus[1]));
+        assert_eq!(bm25.score(&terms("python"), &corpus[0]), 0.0);
+    }
+}
@@ -0,0 +1,221 @@
+use std::fmt;
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use rusqlite::types::Value;
+
+/// Restricts which documents a query searches. Values of one field are
+/// alternatives (any may match); different fields must all match.
+///
+/// Filters are evaluated in SQL while chunks are read, so excluded
+/// documents are never scored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryFilter {
+    /// Globs over the stored document path, e.g. `src/**`
+    pub paths: Vec<String>,
+    /// File extensions without the dot, compared case-insensitively
+    pub extensions: Vec<String>,
+    /// Only documents ingested at or after this time
+    pub since: Option<DateTime<Utc>>,
+    pub tags: Vec<String>,
+    /// Document id prefixes (a full id is its own prefix); every document
+    /// whose id starts with one of them is included
+    pub doc_ids: Vec<String>,
+    /// Metadata `(key, value)` pairs; values of one key are alternatives
+    pub meta: Vec<(String, String)>,
+}
+
+impl QueryFilter {
+    /// Parse a filter expression such as `path:src/** ext:rs since:2026-01-01`.
+    /// Keys are `path`, `ext`, `since`, `tag`, `doc` and `meta` (as
+    /// `meta:key=value`); repeat a key for alternatives.
+    pub fn parse(expr: &str) -> Result<Self> {
+        let mut filter = Self::default();
+        for term in expr.split_whitespace() {
+            let (key, value) = term
+                .split_once(':')
+                .filter(|(_, v)| !v.is_empty())
+                .ok_or_else(|| anyhow!("expected key:value in filter, got {:?}", term))?;
+            match key {
+                "path" => filter.paths.push(value.to_string()),
+                "ext" => filter.extensions.push(value.to_string()),
+                "since" => filter.since = Some(parse_since(value)?),
+                "tag" => filter.tags.push(value.to_string()),
+                "doc" => filter.doc_ids.push(parse_doc_prefix(value)?),
+                "meta" => filter.meta.push(parse_key_value(value)?),
+                other => bail!("unknown filter key {:?} (use path, ext, since, tag, doc or meta)", other),
+            }
+        }
+        filter.path_globs()?;
+        Ok(filter)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Compiled path globs, or `None` when paths are unrestricted.
+    pub fn path_globs(&self) -> Result<Option<GlobSet>> {
+        if self.paths.is_empty() {
+            return Ok(None);
+        }
+        let mut builder = GlobSetBuilder::new();
+        for p in &self.paths {
+            builder.add(Glob::new(p)?);
+        }
+        Ok(Some(builder.build()?))
+    }
+
+    /// `WHERE` conditions over `documents d` and their parameters. Path
+    /// globs call the `rag_path_match(path)` SQL function, which the store
+    /// registers from `path_globs`.
+    pub(crate) fn sql_where(&self) -> (String, Vec<Value>) {
+        let mut conds = Vec::new();
+        let mut params = Vec::new();
+
+        if !self.paths.is_empty() {
+            conds.push("rag_path_match(d.path)".to_string());
+        }
+        // Suffix and prefix tests use `substr` rather than LIKE so `_` and
+        // `%` in user input match literally.
+        if !self.extensions.is_empty() {
+            let alts: Vec<&str> = self.extensions.iter().map(|_| "lower(substr(d.path, -?)) = ?").collect();
+            conds.push(format!("({})", alts.join(" OR ")));
+            for ext in &self.extensions {
+                let suffix = format!(".{}", ext.trim_start_matches('.').to_ascii_lowercase());
+                params.push(Value::Integer(suffix.chars().count() as i64));
+                params.push(Value::Text(suffix));
+            }
+        }
+        if let Some(since) = self.since {
+            conds.push("d.created_at >= ?".to_string());
+            params.push(Value::Text(since.to_rfc3339()));
+        }
+        if !self.tags.is_empty() {
+            let marks = vec!["?"; self.tags.len()].join(", ");
+            conds.push(format!(
+                "EXISTS (SELECT 1 FROM document_tags t WHERE t.doc_id = d.id AND t.tag IN ({}))",
+                marks
+            ));
+            params.extend(self.tags.iter().cloned().map(Value::Text));
+        }
+        if !self.doc_ids.is_empty() {
+            let alts: Vec<&str> = self.doc_ids.iter().map(|_| "substr(d.id, 1, ?) = ?").collect();
+            conds.push(format!("({})", alts.join(" OR ")));
+            for id in &self.doc_ids {
+                params.push(Value::Integer(id.chars().count() as i64));
+                params.push(Value::Text(id.to_lowercase()));
+            }
+        }
+        let mut keys: Vec<&str> = self.meta.iter().map(|(k, _)| k.as_str()).collect();
+        keys.sort_unstable();
+        keys.dedup();
+        for key in keys {
+            let values: Vec<&String> = self.meta.iter().filter(|(k, _)| k == key).map(|(_, v)| v).collect();
+            conds.push(format!(
+                "EXISTS (SELECT 1 FROM document_metadata m WHERE m.doc_id = d.id AND m.key = ? AND m.value IN ({}))",
+                vec!["?"; values.len()].join(", ")
+            ));
+            params.push(Value::Text(key.to_string()));
+            params.extend(values.into_iter().cloned().map(Value::Text));
+        }
+
+        if conds.is_empty() {
+            ("1".to_string(), params)
+        } else {
+            (conds.join(" AND "), params)
+        }
+    }
+}
+
+/// The filter in `parse` syntax; empty when unrestricted.
+impl fmt::Display for QueryFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut terms: Vec<String> = Vec::new();
+        terms.extend(self.paths.iter().map(|p| format!("path:{}", p)));
+        terms.extend(self.extensions.iter().map(|e| format!("ext:{}", e)));
+        terms.extend(self.since.map(|s| format!("since:{}", s.to_rfc3339())));
+        terms.extend(self.tags.iter().map(|t| format!("tag:{}", t)));
+        terms.extend(self.doc_ids.iter().map(|d| format!("doc:{}", d)));
+        terms.extend(self.meta.iter().map(|(k, v)| format!("meta:{}={}", k, v)));
+        write!(f, "{}", terms.join(" "))
+    }
+}
+
+/// Parse `key=value` metadata; the key must be non-empty.
+pub fn parse_key_value(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.trim().to_string()))
+        }
+        _ => bail!("expected key=value, got {:?}", s),
+    }
+}
+
+/// A document id prefix, lowercased; an empty prefix would match every
+/// document and is rejected.
+pub fn parse_doc_prefix(s: &str) -> Result<String> {
+    let prefix = s.trim();
+    if prefix.is_empty() {
+        bail!("document id prefix must not be empty");
+    }
+    Ok(prefix.to_lowercase())
+}
+
+/// Accepts an RFC 3339 timestamp or a plain `YYYY-MM-DD` date (midnight UTC).
+pub fn parse_since(s: &str) -> Result<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map_err(|_| anyhow!("invalid date {:?}; use YYYY-MM-DD or RFC 3339", s))?;
+    Ok(date.and_hms_opt(0, 0, 0).expect("midnight").and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_filter_expressions() {
+        let f = QueryFilter::parse("path:src/** ext:rs ext:md since:2026-01-01 tag:team-a")
+            .unwrap();
+        assert_eq!(f.paths, vec!["src/**"]);
+        assert_eq!(f.extensions, vec!["rs", "md"]);
+        assert_eq!(f.since.unwrap().to_rfc3339(), "2026-01-01T00:00:00+00:00");
+        assert_eq!(f.tags, vec!["team-a"]);
+
+        assert!(QueryFilter::parse("").unwrap().is_empty());
+        assert!(QueryFilter::parse("src/**").is_err());
+        assert!(QueryFilter::parse("owner:me").is_err());
+        assert!(QueryFilter::parse("since:yesterday").is_err());
+        assert!(QueryFilter::parse("path:src/[").is_err());
+
+        assert!(QueryFilter::parse("meta:owner").is_err());
+        assert!(parse_doc_prefix(" ").is_err());
+        assert_eq!(parse_doc_prefix("1F2E").unwrap(), "1f2e");
+
+        let expr = "path:src/** ext:rs since:2026-01-01T00:00:00+00:00 tag:team-a doc:1f2e meta:owner=me";
+        assert_eq!(QueryFilter::parse(expr).unwrap().to_string(), expr);
+    }
+
+    #[test]
+    fn builds_sql_with_one_parameter_per_value() {
+        let f = QueryFilter {
+            extensions: vec![".rs".to_string()],
+            tags: vec!["a".to_string(), "b".to_string()],
+            meta: vec![
+                ("owner".to_string(), "me".to_string()),
+                ("status".to_string(), "draft".to_string()),
+                ("owner".to_string(), "you".to_string()),
+            ],
+            ..QueryFilter::default()
+        };
+        let (sql, params) = f.sql_where();
+        assert_eq!(sql.matches('?').count(), params.len());
+        assert_eq!(params[..2], [Value::Integer(3), Value::Text(".rs".to_string())]);
+        assert_eq!(sql.matches("document_metadata").count(), 2);
+        assert_eq!(QueryFilter::default().sql_where().0, "1");
+    }
+}
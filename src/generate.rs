@@ -0,0 +1,418 @@
+use std::collections::BTreeSet;
+use std::io::{BufRead, BufReader};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Answer, Citation, SearchResult};
+use crate::template::PromptTemplate;
+
+/// Generic answer-generation interface: question + retrieved context in,
+/// grounded answer with citations out.
+///
+/// Chat backends only implement `complete` (and optionally
+/// `complete_stream`); the grounded prompt and citation handling come from
+/// the provided `generate*` methods.
+pub trait Generator {
+    fn name(&self) -> &'static str;
+
+    /// The (system, user) messages for a grounded answer.
+    fn prompt(&self, question: &str, context: &[SearchResult]) -> (String, String) {
+        build_prompt(question, context)
+    }
+
+    /// Single chat completion for a system + user message pair.
+    fn complete(&self, system: &str, user: &str) -> Result<String>;
+
+    /// Like `complete`, but hands each piece of text to `on_token` as it
+    /// arrives and stops reading once `cancel` is set, returning the text so far.
+    fn complete_stream(
+        &self,
+        system: &str,
+        user: &str,
+        on_token: &mut dyn FnMut(&str),
+        _cancel: &AtomicBool,
+    ) -> Result<String> {
+        let text = self.complete(system, user)?;
+        on_token(&text);
+        Ok(text)
+    }
+
+    fn generate(&self, question: &str, context: &[SearchResult]) -> Result<Answer> {
+        let (system, user) = self.prompt(question, context);
+        let text = self.complete(&system, &user)?;
+        Ok(attach_citations(text.trim().to_string(), context))
+    }
+
+    fn generate_stream(
+        &self,
+        question: &str,
+        context: &[SearchResult],
+        on_token: &mut dyn FnMut(&str),
+        cancel: &AtomicBool,
+    ) -> Result<Answer> {
+        let (system, user) = self.prompt(question, context);
+        let text = self.complete_stream(&system, &user, on_token, cancel)?;
+        Ok(attach_citations(text.trim().to_string(), context))
+    }
+}
+
+/// Connection and sampling settings shared by the HTTP chat backends.
+#[derive(Debug, Clone)]
+pub struct GeneratorConfig {
+    pub base_url: String,
+    pub model: String,
+    pub temperature: f32,
+    pub max_tokens: u32,
+    pub api_key: Option<String>,
+    pub template: PromptTemplate,
+    /// Limit on a whole non-streaming request; `None` waits indefinitely.
+    /// Streamed answers have no total limit and are stopped with `cancel`.
+    pub timeout: Option<Duration>,
+}
+
+/// How long to wait for the chat server to accept a connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a stream waiting on the server re-checks its `cancel` flag.
+const CANCEL_POLL: Duration = Duration::from_millis(100);
+
+/// Build the (system, user) messages for a grounded answer with the
+/// built-in template.
+pub fn build_prompt(question: &str, context: &[SearchResult]) -> (String, String) {
+    PromptTemplate::default().render_prompt(question, context)
+}
+
+/// Pair answer text with citations for the `[n]` markers it uses.
+/// If the model cited nothing, every context passage is listed instead.
+pub fn attach_citations(text: String, context: &[SearchResult]) -> Answer {
+    let cited = cited_indices(&text);
+    let citations = context
+        .iter()
+        .enumerate()
+        .map(|(i, r)| Citation::from_result(i + 1, r))
+        .filter(|c| cited.is_empty() || cited.contains(&c.index))
+        .collect();
+    Answer { text, citations }
+}
+
+/// Numbers appearing as `[n]` markers in the text.
+fn cited_indices(text: &str) -> BTreeSet<usize> {
+    let mut out = BTreeSet::new();
+    let mut rest = text;
+    while let Some(open) = rest.find('[') {
+        rest = &rest[open + 1..];
+        if let Some(close) = rest.find(']') {
+            if let Ok(n) = rest[..close].trim().parse::<usize>() {
+                out.insert(n);
+            }
+        }
+    }
+    out
+}
+
+#[derive(Debug, Serialize)]
+struct ChatMessage<'a> {
+    role: &'a str,
+    content: &'a str,
+}
+
+fn messages<'a>(system: &'a str, user: &'a str) -> Vec<ChatMessage<'a>> {
+    vec![
+        ChatMessage {
+            role: "system",
+            content: system,
+        },
+        ChatMessage {
+            role: "user",
+            content: user,
+        },
+    ]
+}
+
+/// HTTP client without reqwest's default 30 s total timeout, which would cut
+/// off slow generations and long streams; limits are set per request.
+fn http_client() -> Result<Client> {
+    Ok(Client::builder().timeout(None).connect_timeout(CONNECT_TIMEOUT).build()?)
+}
+
+/// Send a non-streaming request with the configured total timeout.
+fn send_request(req: RequestBuilder, config: &GeneratorConfig) -> Result<Response> {
+    let req = match config.timeout {
+        Some(timeout) => req.timeout(timeout),
+        None => req,
+    };
+    Ok(req.send()?.error_for_status()?)
+}
+
+/// Send a streaming request and feed each line of the body to `handle` until
+/// it reports the end of the stream (`Ok(true)`), the body ends, or `cancel`
+/// is set. The request runs on a worker thread so `cancel` is noticed even
+/// while the server is silent, e.g. before the first token; once we stop
+/// listening the worker drops the response on its next line, closing the
+/// connection.
+fn read_stream_lines(
+    req: RequestBuilder,
+    cancel: &AtomicBool,
+    mut handle: impl FnMut(&str) -> Result<bool>,
+) -> Result<()> {
+    let (tx, rx) = channel();
+    thread::spawn(move || {
+        let resp = match req.send().and_then(Response::error_for_status) {
+            Ok(resp) => resp,
+            Err(e) => {
+                let _ = tx.send(Err(anyhow::Error::from(e)));
+                return;
+            }
+        };
+        for line in BufReader::new(resp).lines() {
+            let failed = line.is_err();
+            if tx.send(line.map_err(anyhow::Error::from)).is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    while !cancel.load(Ordering::SeqCst) {
+        match rx.recv_timeout(CANCEL_POLL) {
+            Ok(line) => {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                if handle(line.trim())? {
+                    break;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    Ok(())
+}
+
+/// OpenAI-compatible `/chat/completions` backend (OpenAI, vLLM, llama.cpp
+/// server, LM Studio, ...).
+pub struct OpenAIGenerator {
+    client: Client,
+    config: GeneratorConfig,
+}
+
+#[derive(Debug, Serialize)]
+struct OpenAIChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<ChatMessage<'a>>,
+    temperature: f32,
+    max_tokens: u32,
+    stream: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIChatResponse {
+    choices: Vec<OpenAIChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIChoice {
+    message: OpenAIMessage,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIMessage {
+    content: Option<String>,
+}
+
+/// One server-sent event of a streamed chat completion.
+#[derive(Debug, Deserialize)]
+struct OpenAIStreamChunk {
+    choices: Vec<OpenAIStreamChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIStreamChoice {
+    delta: OpenAIMessage,
+}
+
+impl OpenAIGenerator {
+    pub fn new(config: GeneratorConfig) -> Result<Self> {
+        Ok(Self {
+            client: http_client()?,
+            config,
+        })
+    }
+
+    fn request(&self, system: &str, user: &str, stream: bool) -> RequestBuilder {
+        let url = format!("{}/chat/completions", self.config.base_url.trim_end_matches('/'));
+        let body = OpenAIChatRequest {
+            model: &self.config.model,
+            messages: messages(system, user),
+            temperature: self.config.temperature,
+            max_tokens: self.config.max_tokens,
+            stream,
+        };
+
+        let req = self.client.post(url).json(&body);
+        match &self.config.api_key {
+            Some(key) => req.bearer_auth(key),
+            None => req,
+        }
+    }
+}
+
+impl Generator for OpenAIGenerator {
+    fn name(&self) -> &'static str {
+        "openai-chat"
+    }
+
+    fn prompt(&self, question: &str, context: &[SearchResult]) -> (String, String) {
+        self.config.template.render_prompt(question, context)
+    }
+
+    fn complete(&self, system: &str, user: &str) -> Result<String> {
+        let parsed: OpenAIChatResponse = send_request(self.request(system, user, false), &self.config)?.json()?;
+        parsed
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|c| c.message.content)
+            .ok_or_else(|| anyhow!("chat completion returned no content"))
+    }
+
+    /// Server-sent events: `data: {json}` lines, terminated by `data: [DONE]`.
+    fn complete_stream(
+        &self,
+        system: &str,
+        user: &str,
+        on_token: &mut dyn FnMut(&str),
+        cancel: &AtomicBool,
+    ) -> Result<String> {
+        let mut text = String::new();
+        read_stream_lines(self.request(system, user, true), cancel, |line| {
+            let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+                return Ok(false); // comments, `event:` and `id:` fields
+            };
+            if data == "[DONE]" {
+                return Ok(true);
+            }
+            let chunk: OpenAIStreamChunk = serde_json::from_str(data)?;
+            if let Some(piece) = chunk.choices.into_iter().next().and_then(|c| c.delta.content) {
+                on_token(&piece);
+                text.push_str(&piece);
+            }
+            Ok(false)
+        })?;
+        Ok(text)
+    }
+}
+
+/// Ollama `/api/chat` backend.
+pub struct OllamaGenerator {
+    client: Client,
+    config: GeneratorConfig,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<ChatMessage<'a>>,
+    stream: bool,
+    options: OllamaOptions,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaOptions {
+    temperature: f32,
+    num_predict: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaChatResponse {
+    message: OllamaMessage,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaMessage {
+    content: String,
+}
+
+/// One line of Ollama's newline-delimited JSON stream.
+#[derive(Debug, Deserialize)]
+struct OllamaStreamChunk {
+    message: Option<OllamaMessage>,
+    #[serde(default)]
+    done: bool,
+}
+
+impl OllamaGenerator {
+    pub fn new(config: GeneratorConfig) -> Result<Self> {
+        Ok(Self {
+            client: http_client()?,
+            config,
+        })
+    }
+
+    fn request(&self, system: &str, user: &str, stream: bool) -> RequestBuilder {
+        let url = format!("{}/api/chat", self.config.base_url.trim_end_matches('/'));
+        let body = OllamaChatRequest {
+            model: &self.config.model,
+            messages: messages(system, user),
+            stream,
+            options: OllamaOptions {
+                temperature: self.config.temperature,
+                num_predict: self.config.max_tokens,
+            },
+        };
+        self.client.post(url).json(&body)
+    }
+}
+
+impl Generator for OllamaGenerator {
+    fn name(&self) -> &'static str {
+        "ollama-chat"
+    }
+
+    fn prompt(&self, question: &str, context: &[SearchResult]) -> (String, String) {
+        self.config.template.render_prompt(question, context)
+    }
+
+    fn complete(&self, system: &str, user: &str) -> Result<String> {
+        let parsed: OllamaChatResponse = send_request(self.request(system, user, false), &self.config)?.json()?;
+        Ok(parsed.message.content)
+    }
+
+    /// Newline-delimited JSON objects, the last one has `"done": true`.
+    fn complete_stream(
+        &self,
+        system: &str,
+        user: &str,
+        on_token: &mut dyn FnMut(&str),
+        cancel: &AtomicBool,
+    ) -> Result<String> {
+        let mut text = String::new();
+        read_stream_lines(self.request(system, user, true), cancel, |line| {
+            let chunk: OllamaStreamChunk = serde_json::from_str(line)?;
+            if let Some(message) = chunk.message {
+                on_token(&message.content);
+                text.push_str(&message.content);
+            }
+            Ok(chunk.done)
+        })?;
+        Ok(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cited_indices_ignores_non_numeric_brackets() {
+        let found = cited_indices("Rust is safe [1][3]. See [docs] and [ 2 ].");
+        assert_eq!(found.into_iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,638 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result};
+use clap::ValueEnum;
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::filter::QueryFilter;
+use crate::quantize::{
+    binary_encode, hamming, int8_dot, int8_encode, kmeans, nearest, squared_distance,
+    ProductQuantizer,
+};
+use crate::query::{scan_top_k, QueryOptions};
+use crate::store::Store;
+
+/// Vector index kinds selectable with `rag index build --kind`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexKind {
+    /// Exact scan over the f32 embeddings
+    Flat,
+    /// Scalar int8 codes, about 4x smaller than f32
+    Int8,
+    /// Product quantization: one byte per subspace
+    Pq,
+    /// Inverted file: k-means lists, queries scan the `nprobe` nearest
+    Ivf,
+    /// One sign bit per dimension (32x smaller), Hamming distance first
+    /// pass, exact cosine rescoring
+    Binary,
+}
+
+impl IndexKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IndexKind::Flat => "flat",
+            IndexKind::Int8 => "int8",
+            IndexKind::Pq => "pq",
+            IndexKind::Ivf => "ivf",
+            IndexKind::Binary => "binary",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "flat" => Ok(IndexKind::Flat),
+            "int8" => Ok(IndexKind::Int8),
+            "pq" => Ok(IndexKind::Pq),
+            "ivf" => Ok(IndexKind::Ivf),
+            "binary" => Ok(IndexKind::Binary),
+            other => bail!("unknown vector index kind `{}`", other),
+        }
+    }
+}
+
+/// The vector index a store scans, as recorded in its `index_meta`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum VectorIndex {
+    #[default]
+    Flat,
+    Quantized(Quantizer),
+    Ivf(IvfIndex),
+}
+
+impl VectorIndex {
+    /// Rebuild the index from its stored kind and trained state.
+    pub fn from_meta(kind: IndexKind, model: Option<&[u8]>) -> Result<Self> {
+        match (kind, model) {
+            (IndexKind::Flat, _) => Ok(VectorIndex::Flat),
+            (IndexKind::Int8, _) => Ok(VectorIndex::Quantized(Quantizer::Int8)),
+            (IndexKind::Binary, _) => Ok(VectorIndex::Quantized(Quantizer::Binary)),
+            (IndexKind::Pq, Some(bytes)) => Ok(VectorIndex::Quantized(Quantizer::Pq(
+                ProductQuantizer::from_bytes(bytes)?,
+            ))),
+            (IndexKind::Ivf, Some(bytes)) => Ok(VectorIndex::Ivf(IvfIndex::from_bytes(bytes)?)),
+            (kind, None) => bail!(
+                "{} index has no trained state; rebuild it with `rag index build`",
+                kind.name()
+            ),
+        }
+    }
+
+    pub fn kind(&self) -> IndexKind {
+        match self {
+            VectorIndex::Flat => IndexKind::Flat,
+            VectorIndex::Quantized(q) => q.kind(),
+            VectorIndex::Ivf(_) => IndexKind::Ivf,
+        }
+    }
+
+    /// Trained state to persist (PQ codebooks, IVF centroids).
+    pub fn model(&self) -> Option<Vec<u8>> {
+        match self {
+            VectorIndex::Flat
+            | VectorIndex::Quantized(Quantizer::Int8)
+            | VectorIndex::Quantized(Quantizer::Binary) => None,
+            VectorIndex::Quantized(Quantizer::Pq(pq)) => Some(pq.to_bytes()),
+            VectorIndex::Ivf(ivf) => Some(ivf.to_bytes()),
+        }
+    }
+
+    pub fn quantizer(&self) -> Option<&Quantizer> {
+        match self {
+            VectorIndex::Quantized(q) => Some(q),
+            _ => None,
+        }
+    }
+
+    pub fn ivf(&self) -> Option<&IvfIndex> {
+        match self {
+            VectorIndex::Ivf(ivf) => Some(ivf),
+            _ => None,
+        }
+    }
+}
+
+/// Encodes embeddings into the compact codes a quantized index scans.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Quantizer {
+    Int8,
+    Pq(ProductQuantizer),
+    Binary,
+}
+
+impl Quantizer {
+    pub fn kind(&self) -> IndexKind {
+        match self {
+            Quantizer::Int8 => IndexKind::Int8,
+            Quantizer::Pq(_) => IndexKind::Pq,
+            Quantizer::Binary => IndexKind::Binary,
+        }
+    }
+
+    pub fn encode(&self, v: &[f32]) -> Result<Vec<u8>> {
+        match self {
+            Quantizer::Int8 => Ok(int8_encode(v)),
+            Quantizer::Pq(pq) => pq.encode(v),
+            Quantizer::Binary => Ok(binary_encode(v)),
+        }
+    }
+
+    /// Candidates rescored with full vectors when the query does not ask
+    /// for a number: binary codes only pre-filter, so they always are.
+    pub fn default_rescore(&self, depth: usize) -> Option<usize> {
+        match self {
+            Quantizer::Binary => Some(depth.saturating_mul(BINARY_RESCORE_PER_RESULT)),
+            Quantizer::Int8 | Quantizer::Pq(_) => None,
+        }
+    }
+
+    /// Per-query state for scoring codes (the PQ lookup table).
+    pub fn prepare(&self, query: &[f32]) -> Result<PreparedQuery> {
+        Ok(match self {
+            Quantizer::Int8 => PreparedQuery::Int8(query.to_vec()),
+            Quantizer::Pq(pq) => PreparedQuery::Pq(pq.lookup_table(query)?),
+            Quantizer::Binary => PreparedQuery::Binary {
+                bits: binary_encode(query),
+                dim: query.len(),
+            },
+        })
+    }
+}
+
+/// Binary-code hits rescored per requested hit by default.
+pub const BINARY_RESCORE_PER_RESULT: usize = 10;
+
+/// A query ready to be scored against codes.
+#[derive(Debug, Clone)]
+pub enum PreparedQuery {
+    Int8(Vec<f32>),
+    Pq(Vec<f32>),
+    Binary { bits: Vec<u8>, dim: usize },
+}
+
+impl PreparedQuery {
+    /// Approximate dot product of the query with a code (for binary codes,
+    /// the fraction of matching signs rescaled to [-1, 1]).
+    pub fn score(&self, code: &[u8]) -> f32 {
+        match self {
+            PreparedQuery::Int8(query) => int8_dot(query, code),
+            PreparedQuery::Pq(table) => ProductQuantizer::adc(table, code),
+            // 1 for identical signs, -1 for opposite ones.
+            PreparedQuery::Binary { bits, dim } => {
+                1.0 - 2.0 * hamming(bits, code) as f32 / (*dim).max(1) as f32
+            }
+        }
+    }
+}
+
+/// Inverted file index: chunks are assigned to the nearest of `lists`
+/// k-means centroids (stored in `chunks.ivf_list`) and a query scans only
+/// the lists of its `nprobe` nearest centroids.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IvfIndex {
+    pub centroids: Vec<Vec<f32>>,
+    /// Lists scanned per query unless the query overrides it
+    pub nprobe: usize,
+    /// Chunks in the store when the index was trained
+    pub trained_chunks: usize,
+    /// Mean squared distance of those chunks to their centroid
+    pub train_error: f32,
+}
+
+impl IvfIndex {
+    pub fn lists(&self) -> usize {
+        self.centroids.len()
+    }
+
+    /// List a vector belongs to.
+    pub fn assign(&self, v: &[f32]) -> usize {
+        nearest(&self.centroids, v)
+    }
+
+    /// The `nprobe` lists nearest to `query`, nearest first.
+    pub fn probe(&self, query: &[f32], nprobe: usize) -> Vec<usize> {
+        let mut lists: Vec<(usize, f32)> = self
+            .centroids
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (i, squared_distance(c, query)))
+            .collect();
+        lists.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        lists
+            .into_iter()
+            .take(nprobe.max(1))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// `dim`, list count, `nprobe` and `trained_chunks` as little-endian
+    /// u32s, `train_error` as f32, then every centroid's f32s.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let dim = self.centroids.first().map_or(0, Vec::len);
+        let mut out = Vec::new();
+        for n in [dim, self.lists(), self.nprobe, self.trained_chunks] {
+            out.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+        out.extend_from_slice(&self.train_error.to_le_bytes());
+        for x in self.centroids.iter().flatten() {
+            out.extend_from_slice(&x.to_le_bytes());
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 20 || !bytes.len().is_multiple_of(4) {
+            bail!("corrupt IVF index");
+        }
+        let words: Vec<[u8; 4]> = bytes
+            .chunks_exact(4)
+            .map(|b| b.try_into().expect("4 bytes"))
+            .collect();
+        let [dim, lists, nprobe, trained_chunks] =
+            [0, 1, 2, 3].map(|i| u32::from_le_bytes(words[i]) as usize);
+        let floats: Vec<f32> = words[5..].iter().map(|w| f32::from_le_bytes(*w)).collect();
+        if dim == 0 || floats.len() != dim * lists {
+            bail!("IVF centroids do not match their header");
+        }
+        Ok(Self {
+            centroids: floats.chunks(dim).map(<[f32]>::to_vec).collect(),
+            nprobe,
+            trained_chunks,
+            train_error: f32::from_le_bytes(words[4]),
+        })
+    }
+}
+
+/// Lists scanned per query by default.
+pub const DEFAULT_NPROBE: usize = 8;
+
+/// Settings for `rag index build`.
+#[derive(Debug, Clone)]
+pub struct BuildOptions {
+    pub kind: IndexKind,
+    /// PQ subspaces (code bytes per vector); must divide the dimension
+    pub subspaces: usize,
+    /// k-means iterations when training
+    pub iterations: usize,
+    /// IVF lists; defaults to the square root of the chunk count
+    pub lists: Option<usize>,
+    pub nprobe: usize,
+    /// At most this many embeddings are used for training
+    pub training_sample: usize,
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        Self {
+            kind: IndexKind::Flat,
+            subspaces: 8,
+            iterations: 25,
+            lists: None,
+            nprobe: DEFAULT_NPROBE,
+            training_sample: 20_000,
+        }
+    }
+}
+
+/// Train the index (if its kind needs training) and encode or assign every
+/// chunk. Returns the number of chunks encoded or assigned.
+pub fn build_index(store: &mut Store, opts: &BuildOptions) -> Result<usize> {
+    match opts.kind {
+        IndexKind::Flat => {
+            store.set_index(VectorIndex::Flat)?;
+            Ok(0)
+        }
+        IndexKind::Int8 => encode_all(store, Quantizer::Int8),
+        IndexKind::Binary => encode_all(store, Quantizer::Binary),
+        IndexKind::Pq => {
+            let sample = sample_embeddings(store, opts.training_sample)?;
+            let pq = ProductQuantizer::train(&sample, opts.subspaces, opts.iterations)?;
+            encode_all(store, Quantizer::Pq(pq))
+        }
+        IndexKind::Ivf => build_ivf(store, opts),
+    }
+}
+
+fn encode_all(store: &mut Store, quantizer: Quantizer) -> Result<usize> {
+    store.set_index(VectorIndex::Quantized(quantizer.clone()))?;
+    let mut codes = Vec::new();
+    store.scan_embeddings(&QueryFilter::default(), 4096, |rowids, embeddings| {
+        let batch: Vec<(i64, Vec<u8>)> = rowids
+            .par_iter()
+            .zip(embeddings)
+            .map(|(&rowid, v)| Ok((rowid, quantizer.encode(v)?)))
+            .collect::<Result<_>>()?;
+        codes.extend(batch);
+        Ok(())
+    })?;
+    store.write_codes(&codes)?;
+    Ok(codes.len())
+}
+
+fn build_ivf(store: &mut Store, opts: &BuildOptions) -> Result<usize> {
+    let (_, chunks, _) = store.corpus_stats()?;
+    let lists = opts
+        .lists
+        .unwrap_or_else(|| (chunks as f64).sqrt().round() as usize)
+        .max(1);
+    let sample = sample_embeddings(store, opts.training_sample.max(lists))?;
+    if sample.is_empty() {
+        bail!("no chunks to train an IVF index on");
+    }
+    let points: Vec<&[f32]> = sample.iter().map(Vec::as_slice).collect();
+    let mut ivf = IvfIndex {
+        centroids: kmeans(&points, lists, opts.iterations),
+        nprobe: opts.nprobe,
+        trained_chunks: 0,
+        train_error: 0.0,
+    };
+
+    let mut assignments = Vec::new();
+    let mut error = 0.0f64;
+    store.scan_embeddings(&QueryFilter::default(), 4096, |rowids, embeddings| {
+        let assigned: Vec<(i64, usize, f32)> = rowids
+            .par_iter()
+            .zip(embeddings)
+            .map(|(&rowid, v)| {
+                let list = ivf.assign(v);
+                (rowid, list, squared_distance(&ivf.centroids[list], v))
+            })
+            .collect();
+        for (rowid, list, distance) in assigned {
+            assignments.push((rowid, list));
+            error += distance as f64;
+        }
+        Ok(())
+    })?;
+    ivf.trained_chunks = assignments.len();
+    ivf.train_error = (error / assignments.len().max(1) as f64) as f32;
+    store.set_index(VectorIndex::Ivf(ivf))?;
+    store.write_lists(&assignments)?;
+    Ok(assignments.len())
+}
+
+/// How far the corpus has moved away from an IVF index's training.
+#[derive(Debug, Clone, Serialize)]
+pub struct IvfDrift {
+    pub trained_chunks: usize,
+    pub chunks: usize,
+    pub train_error: f32,
+    /// Mean squared distance of the current chunks to their centroid
+    pub current_error: f32,
+}
+
+impl IvfDrift {
+    /// Relative change in chunk count since training.
+    pub fn size_change(&self) -> f32 {
+        (self.chunks as f32 - self.trained_chunks as f32).abs() / self.trained_chunks.max(1) as f32
+    }
+
+    /// Relative growth of the mean distance to the assigned centroid.
+    pub fn error_growth(&self) -> f32 {
+        if self.train_error > 0.0 {
+            self.current_error / self.train_error - 1.0
+        } else if self.current_error > 0.0 {
+            f32::INFINITY
+        } else {
+            0.0
+        }
+    }
+
+    pub fn drifted(&self, threshold: f32) -> bool {
+        self.size_change() > threshold || self.error_growth() > threshold
+    }
+}
+
+/// Drift of the store's IVF index, if it has one. Scans every embedding.
+pub fn ivf_drift(store: &Store) -> Result<Option<IvfDrift>> {
+    let Some(ivf) = store.vector_index().ivf() else {
+        return Ok(None);
+    };
+    let mut chunks = 0;
+    let mut error = 0.0f64;
+    store.scan_list_assignments(4096, |lists, embeddings| {
+        for (list, v) in lists.iter().zip(embeddings) {
+            let list = list.unwrap_or_else(|| ivf.assign(v));
+            error += ivf
+                .centroids
+                .get(list)
+                .map_or(0.0, |c| squared_distance(c, v)) as f64;
+        }
+        chunks += lists.len();
+        Ok(())
+    })?;
+    Ok(Some(IvfDrift {
+        trained_chunks: ivf.trained_chunks,
+        chunks,
+        train_error: ivf.train_error,
+        current_error: (error / chunks.max(1) as f64) as f32,
+    }))
+}
+
+/// Retrain the IVF index with its current list count and `nprobe` if the
+/// corpus drifted past `threshold` (or `force` is set). Returns the drift
+/// measured and whether the index was rebuilt.
+pub fn retrain_ivf(store: &mut Store, threshold: f32, force: bool) -> Result<(IvfDrift, bool)> {
+    let drift = ivf_drift(store)?.ok_or_else(|| anyhow!("the vector index is not IVF"))?;
+    if !force && !drift.drifted(threshold) {
+        return Ok((drift, false));
+    }
+    let ivf = store.vector_index().ivf().expect("checked above").clone();
+    let opts = BuildOptions {
+        kind: IndexKind::Ivf,
+        lists: Some(ivf.lists()),
+        nprobe: ivf.nprobe,
+        ..BuildOptions::default()
+    };
+    build_index(store, &opts)?;
+    Ok((drift, true))
+}
+
+/// Up to `n` stored embeddings, evenly spaced through the store.
+pub fn sample_embeddings(store: &Store, n: usize) -> Result<Vec<Vec<f32>>> {
+    let (_, chunks, _) = store.corpus_stats()?;
+    let stride = chunks.div_ceil(n.max(1)).max(1);
+    let mut sample = Vec::with_capacity(n.min(chunks));
+    let mut seen = 0;
+    store.scan_embeddings(&QueryFilter::default(), 4096, |_, embeddings| {
+        for v in embeddings {
+            if seen % stride == 0 && sample.len() < n {
+                sample.push(v.clone());
+            }
+            seen += 1;
+        }
+        Ok(())
+    })?;
+    Ok(sample)
+}
+
+/// Recall of the current index against an exact scan.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecallReport {
+    pub kind: IndexKind,
+    pub queries: usize,
+    pub top_k: usize,
+    pub rescore: Option<usize>,
+    pub nprobe: Option<usize>,
+    /// Chunks scanned per query by the index
+    pub scanned: usize,
+    /// Fraction of the exact top-k the index also returned
+    pub recall: f32,
+    pub exact_time: Duration,
+    pub index_time: Duration,
+}
+
+/// Compare top-k from the index, searched with `opts`, with exact search,
+/// using a sample of the stored embeddings as queries.
+pub fn evaluate_recall(
+    store: &Store,
+    queries: usize,
+    top_k: usize,
+    opts: &QueryOptions,
+) -> Result<RecallReport> {
+    let sample = sample_embeddings(store, queries)?;
+    let exact_opts = QueryOptions {
+        exact: true,
+        ..opts.clone()
+    };
+
+    // One query at a time, as `rag query` runs them, so IVF probes only
+    // the lists of that query.
+    let search = |opts: &QueryOptions| -> Result<(Vec<Vec<i64>>, usize, Duration)> {
+        let start = Instant::now();
+        let mut hits = Vec::with_capacity(sample.len());
+        let mut scanned = 0;
+        for query in &sample {
+            let (mut lists, n) =
+                scan_top_k(store, opts, std::slice::from_ref(query), top_k, false)?;
+            hits.append(&mut lists);
+            scanned += n;
+        }
+        Ok((hits, scanned, start.elapsed()))
+    };
+    let (exact, _, exact_time) = search(&exact_opts)?;
+    let (approx, scanned, index_time) = search(opts)?;
+
+    let (found, wanted) = exact
+        .iter()
+        .zip(&approx)
+        .fold((0, 0), |(found, wanted), (e, a)| {
+            (
+                found + e.iter().filter(|id| a.contains(id)).count(),
+                wanted + e.len(),
+            )
+        });
+    Ok(RecallReport {
+        kind: store.vector_index().kind(),
+        queries: sample.len(),
+        top_k,
+        rescore: opts.rescore,
+        nprobe: opts.nprobe,
+        scanned: scanned / sample.len().max(1),
+        recall: if wanted == 0 {
+            1.0
+        } else {
+            found as f32 / wanted as f32
+        },
+        exact_time,
+        index_time,
+    })
+}
+
+/// Size of the stored codes relative to the f32 embeddings, and IVF drift.
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexStats {
+    pub kind: IndexKind,
+    pub dim: usize,
+    /// Chunks that have a code
+    pub coded: usize,
+    pub code_bytes: f64,
+    pub ivf_lists: Option<usize>,
+    pub drift: Option<IvfDrift>,
+}
+
+impl IndexStats {
+    pub fn load(store: &Store) -> Result<Self> {
+        let (dim, coded, code_bytes) = store.code_stats()?;
+        Ok(Self {
+            kind: store.vector_index().kind(),
+            dim,
+            coded,
+            code_bytes,
+            ivf_lists: store.vector_index().ivf().map(IvfIndex::lists),
+            drift: ivf_drift(store)?,
+        })
+    }
+
+    /// f32 bytes per vector over code bytes per vector.
+    pub fn compression_ratio(&self) -> Option<f64> {
+        (self.code_bytes > 0.0).then(|| (self.dim * 4) as f64 / self.code_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kinds_round_trip_through_names() {
+        for kind in [
+            IndexKind::Flat,
+            IndexKind::Int8,
+            IndexKind::Pq,
+            IndexKind::Ivf,
+            IndexKind::Binary,
+        ] {
+            assert_eq!(IndexKind::from_name(kind.name()).unwrap(), kind);
+        }
+        assert!(IndexKind::from_name("hnsw").is_err());
+        assert!(VectorIndex::from_meta(IndexKind::Pq, None).is_err());
+        assert!(VectorIndex::from_meta(IndexKind::Ivf, None).is_err());
+        assert_eq!(
+            VectorIndex::from_meta(IndexKind::Flat, None).unwrap(),
+            VectorIndex::Flat
+        );
+        assert_eq!(
+            VectorIndex::from_meta(IndexKind::Binary, None).unwrap().kind(),
+            IndexKind::Binary
+        );
+    }
+
+    #[test]
+    fn ivf_probes_nearest_lists_and_round_trips() {
+        let ivf = IvfIndex {
+            centroids: vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![-1.0, 0.0]],
+            nprobe: 2,
+            trained_chunks: 30,
+            train_error: 0.25,
+        };
+        assert_eq!(ivf.assign(&[0.1, 0.9]), 1);
+        assert_eq!(ivf.probe(&[0.8, 0.6], 2), vec![0, 1]);
+        assert_eq!(ivf.probe(&[0.8, 0.6], 0), vec![0]);
+        assert_eq!(IvfIndex::from_bytes(&ivf.to_bytes()).unwrap(), ivf);
+        assert!(IvfIndex::from_bytes(&ivf.to_bytes()[..24]).is_err());
+    }
+
+    #[test]
+    fn drift_compares_size_and_error_with_training() {
+        let drift = IvfDrift {
+            trained_chunks: 100,
+            chunks: 110,
+            train_error: 0.2,
+            current_error: 0.21,
+        };
+        assert!(!drift.drifted(0.2));
+        assert!(IvfDrift {
+            chunks: 130,
+            ..drift.clone()
+        }
+        .drifted(0.2));
+        assert!(IvfDrift {
+            current_error: 0.3,
+            ..drift
+        }
+        .drifted(0.2));
+    }
+}
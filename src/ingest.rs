@@ -1,13 +1,17 @@
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
 
 use anyhow::{anyhow, Result};
 use chrono::Utc;
 use uuid::Uuid;
 use walkdir::WalkDir;
 
+use crate::archive::{self, archive_kind, entry_path};
 use crate::embedder::Embedder;
-use crate::models::{Chunk, Document};
+use crate::loader::LoaderRegistry;
+use crate::models::{Chunk, Document, DocumentMetadata};
 use crate::store::Store;
 
 /// Internal struct to hold chunk metadata before we have embeddings.
@@ -16,9 +20,47 @@ struct PendingChunk {
     chunk_index: i32,
     start_char: i32,
     end_char: i32,
+    page: Option<u32>,
     text: String,
 }
 
+/// Separator assumed between sections when computing document-wide spans.
+const SECTION_SEPARATOR: &str = "\n\n";
+
+/// Path argument that means "read one document from stdin".
+pub const STDIN_PATH: &str = "-";
+
+/// Knobs for an ingest run.
+#[derive(Debug, Clone)]
+pub struct IngestOptions {
+    /// Target chunk size in characters
+    pub chunk_size: usize,
+    /// Overlap between consecutive chunks in characters
+    pub overlap: usize,
+    /// Document path recorded for stdin input (defaults to `stdin`)
+    pub stdin_name: Option<String>,
+    /// Tags attached to every ingested document
+    pub tags: Vec<String>,
+    /// Metadata fields set on every ingested document; they override
+    /// front matter values of the same key
+    pub fields: BTreeMap<String, String>,
+    /// Archive members larger than this are skipped
+    pub max_entry_bytes: u64,
+}
+
+impl Default for IngestOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: 512,
+            overlap: 64,
+            stdin_name: None,
+            tags: Vec::new(),
+            fields: BTreeMap::new(),
+            max_entry_bytes: archive::DEFAULT_MAX_ENTRY_BYTES,
+        }
+    }
+}
+
 /// Entry point used from CLI.
 /// Now batches ALL chunks from ALL files into a single embedder call.
 pub fn run_ingest(
@@ -27,96 +69,198 @@ pub fn run_ingest(
     paths: &[PathBuf],
     chunk_size: usize,
     overlap: usize,
+) -> Result<()> {
+    let opts = IngestOptions {
+        chunk_size,
+        overlap,
+        ..IngestOptions::default()
+    };
+    run_ingest_with(store, embedder, paths, &opts)
+}
+
+/// Ingest files, directories, archives (`.zip`, `.tar`, `.tar.gz`) and
+/// stdin (`-`), embedding all chunks in a single batch.
+pub fn run_ingest_with(
+    store: &Store,
+    embedder: &dyn Embedder,
+    paths: &[PathBuf],
+    opts: &IngestOptions,
 ) -> Result<()> {
     println!(
         "[ingest] Using embedder: {} | chunk_size={} overlap={}",
         embedder.name(),
-        chunk_size,
-        overlap
+        opts.chunk_size,
+        opts.overlap
     );
 
     let files = collect_files(paths)?;
     println!("[ingest] Found {} files to ingest", files.len());
 
     // Collect all chunks from all files before calling embedder.
-    let mut pending_chunks: Vec<PendingChunk> = Vec::new();
+    let mut batch = IngestBatch::new(store, opts);
 
     for path in files {
-        let content = fs::read_to_string(&path)?;
-        if content.trim().is_empty() {
-            println!("[ingest] Skipping empty file {}", path.display());
-            continue;
+        if path.as_os_str() == STDIN_PATH {
+            let mut bytes = Vec::new();
+            io::stdin().read_to_end(&mut bytes)?;
+            let name = opts.stdin_name.as_deref().unwrap_or("stdin");
+            batch.add_document(name, Path::new(name), &bytes)?;
+        } else if let Some(kind) = archive_kind(&path) {
+            // Store errors abort the ingest; a corrupt archive is skipped.
+            let mut store_failed = false;
+            let read = archive::for_each_entry(&path, kind, opts.max_entry_bytes, |entry, bytes| {
+                batch
+                    .add_document(&entry_path(&path, entry), Path::new(entry), &bytes)
+                    .inspect_err(|_| store_failed = true)
+            });
+            match read {
+                Err(e) if store_failed => return Err(e),
+                Ok(skipped) => {
+                    for warning in skipped {
+                        println!("[ingest] {}: {}", path.display(), warning);
+                    }
+                }
+                Err(e) => println!("[ingest] Skipping {}: {}", path.display(), e),
+            }
+        } else {
+            let bytes = fs::read(&path)?;
+            batch.add_document(&path.to_string_lossy(), &path, &bytes)?;
+        }
+    }
+
+    batch.finish(embedder)
+}
+
+/// Chunks accumulated across documents, embedded together by `finish`.
+struct IngestBatch<'a> {
+    store: &'a Store,
+    opts: &'a IngestOptions,
+    loaders: LoaderRegistry,
+    pending: Vec<PendingChunk>,
+}
+
+impl<'a> IngestBatch<'a> {
+    fn new(store: &'a Store, opts: &'a IngestOptions) -> Self {
+        Self {
+            store,
+            opts,
+            loaders: LoaderRegistry::default(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Extract, register and chunk one document. `doc_path` is what gets
+    /// stored; `loader_path` only guides loader selection by extension.
+    /// Unloadable documents are reported and skipped.
+    fn add_document(&mut self, doc_path: &str, loader_path: &Path, bytes: &[u8]) -> Result<()> {
+        let loaded = match self.loaders.load(loader_path, bytes) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                println!("[ingest] Skipping {}: {}", doc_path, e);
+                return Ok(());
+            }
+        };
+        for warning in &loaded.warnings {
+            println!("[ingest] {}: {}", doc_path, warning);
+        }
+        let sections = loaded.sections;
+        if sections.iter().all(|s| s.text.trim().is_empty()) {
+            println!("[ingest] Skipping empty file {}", doc_path);
+            return Ok(());
         }
 
+        let mut metadata = loaded.metadata;
+        metadata.merge(&DocumentMetadata {
+            tags: self.opts.tags.clone(),
+            fields: self.opts.fields.iter().map(|(k, v)| (k.clone(), v.clone().into())).collect(),
+        });
         let doc = Document {
             id: Uuid::new_v4(),
-            path: path.to_string_lossy().to_string(),
+            path: doc_path.to_string(),
             created_at: Utc::now(),
+            encoding: loaded.encoding,
+            metadata,
         };
-        store.insert_document(&doc)?;
-
-        let chunks_text = chunk_text(&content, chunk_size, overlap);
-        let num_chunks = chunks_text.len();
-
-        for (idx, (start, end, text)) in chunks_text.into_iter().enumerate() {
-            pending_chunks.push(PendingChunk {
-                doc_id: doc.id,
-                chunk_index: idx as i32,
-                start_char: start as i32,
-                end_char: end as i32,
-                text,
-            });
+        self.store.insert_document(&doc)?;
+
+        // Chunks never straddle sections, so each one maps to a single page.
+        // Spans are offsets into the sections joined by SECTION_SEPARATOR.
+        let mut num_chunks = 0usize;
+        let mut offset = 0usize;
+        for section in &sections {
+            for (start, end, text) in
+                chunk_text(&section.text, self.opts.chunk_size, self.opts.overlap)
+            {
+                self.pending.push(PendingChunk {
+                    doc_id: doc.id,
+                    chunk_index: num_chunks as i32,
+                    start_char: (offset + start) as i32,
+                    end_char: (offset + end) as i32,
+                    page: section.page,
+                    text,
+                });
+                num_chunks += 1;
+            }
+            offset += section.text.chars().count() + SECTION_SEPARATOR.len();
         }
 
-        println!(
-            "[ingest] {} -> {} chunks",
-            doc.path,
-            num_chunks
-        );
+        match doc.encoding.as_deref() {
+            Some(enc) if enc != "UTF-8" => {
+                println!("[ingest] {} -> {} chunks (decoded from {})", doc.path, num_chunks, enc)
+            }
+            _ => println!("[ingest] {} -> {} chunks", doc.path, num_chunks),
+        }
+        Ok(())
     }
 
-    if pending_chunks.is_empty() {
-        println!("[ingest] No chunks to embed; nothing to do.");
-        return Ok(());
-    }
+    /// Embed every pending chunk in one call and store the results.
+    #[allow(clippy::useless_conversion)]
+    fn finish(self, embedder: &dyn Embedder) -> Result<()> {
+        let pending_chunks = self.pending;
+        if pending_chunks.is_empty() {
+            println!("[ingest] No chunks to embed; nothing to do.");
+            return Ok(());
+        }
 
-    // Build a single batch of all chunk texts.
-    let texts: Vec<String> = pending_chunks.iter().map(|c| c.text.clone()).collect();
-
-    // SINGLE embedding call for ALL chunks across ALL files.
-    let embeddings = embedder.embed(&texts)?;
-    if embeddings.len() != pending_chunks.len() {
-        return Err(anyhow!(
-            "embedder returned {} vectors for {} texts",
-            embeddings.len(),
-            pending_chunks.len()
-        ));
-    }
+        // Build a single batch of all chunk texts.
+        let texts: Vec<String> = pending_chunks.iter().map(|c| c.text.clone()).collect();
 
-    // Insert chunks with embeddings into the store.
-    for (pending, emb) in pending_chunks.into_iter().zip(embeddings.into_iter()) {
-        let chunk = Chunk {
-            id: Uuid::new_v4(),
-            doc_id: pending.doc_id,
-            chunk_index: pending.chunk_index,
-            text: pending.text,
-            embedding: emb,
-            start_char: pending.start_char,
-            end_char: pending.end_char,
-        };
-        store.insert_chunk(&chunk)?;
-    }
+        // SINGLE embedding call for ALL chunks across ALL files.
+        let embeddings = embedder.embed(&texts)?;
+        if embeddings.len() != pending_chunks.len() {
+            return Err(anyhow!(
+                "embedder returned {} vectors for {} texts",
+                embeddings.len(),
+                pending_chunks.len()
+            ));
+        }
 
-    println!("[ingest] Done embedding and storing all chunks.");
+        // Insert chunks with embeddings into the store.
+        for (pending, emb) in pending_chunks.into_iter().zip(embeddings.into_iter()) {
+            let chunk = Chunk {
+                id: Uuid::new_v4(),
+                doc_id: pending.doc_id,
+                chunk_index: pending.chunk_index,
+                text: pending.text,
+                embedding: emb,
+                start_char: pending.start_char,
+                end_char: pending.end_char,
+                page: pending.page,
+            };
+            self.store.insert_chunk(&chunk)?;
+        }
+
+        println!("[ingest] Done embedding and storing all chunks.");
 
-    Ok(())
+        Ok(())
+    }
 }
 
 /// Recursively collect files from given paths.
-fn collect_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+pub(crate) fn collect_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
     let mut files = Vec::new();
     for p in paths {
-        if p.is_file() {
+        if p.as_os_str() == STDIN_PATH || p.is_file() {
             files.push(p.clone());
         } else if p.is_dir() {
             for entry in WalkDir::new(p)
@@ -135,6 +279,7 @@ fn collect_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
 
 /// Chunk text into overlapping windows (by character).
 /// Returns Vec<(start_char, end_char, text)>
+#[allow(clippy::implicit_saturating_sub)]
 fn chunk_text(text: &str, chunk_size: usize, overlap: usize) -> Vec<(usize, usize, String)> {
     let chars: Vec<char> = text.chars().collect();
     let len = chars.len();
@@ -1,7 +1,27 @@
+pub mod archive;
+pub mod chat;
 pub mod cli;
+pub mod collections;
+pub mod context;
+pub mod docs;
 pub mod embedder;
+pub mod encoding;
+pub mod expand;
+pub mod explain;
+pub mod filter;
+pub mod generate;
+pub mod index;
 pub mod ingest;
+pub mod loader;
 pub mod models;
+pub mod output;
+pub mod quantize;
 pub mod query;
+pub mod rerank;
+pub mod score;
 pub mod stats;
 pub mod store;
+pub mod summarize;
+pub mod template;
+pub mod text;
+pub mod watch;
@@ -0,0 +1,375 @@
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use zip::ZipArchive;
+
+use crate::encoding::{decode_text, is_utf16, normalize_text};
+use crate::models::DocumentMetadata;
+
+mod docx;
+mod epub;
+mod html;
+mod ipynb;
+mod markdown;
+mod markup;
+#[cfg(feature = "pdf")]
+mod pdf;
+
+pub use docx::DocxLoader;
+pub use epub::EpubLoader;
+pub use html::{html_to_text, HtmlLoader};
+pub use ipynb::NotebookLoader;
+pub use markdown::{split_front_matter, MarkdownLoader};
+
+/// A contiguous piece of extracted document text.
+/// Paged formats (PDF) produce one section per page.
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub text: String,
+    pub page: Option<u32>,
+}
+
+impl Section {
+    pub fn new(text: String) -> Self {
+        Self { text, page: None }
+    }
+}
+
+/// Output of a loader: text sections plus the source character encoding
+/// for formats that are decoded from raw text, and any metadata the
+/// document declares about itself.
+#[derive(Debug, Clone)]
+pub struct LoadedDocument {
+    pub sections: Vec<Section>,
+    pub encoding: Option<String>,
+    pub metadata: DocumentMetadata,
+    /// Parts of the document that could not be loaded and were skipped
+    pub warnings: Vec<String>,
+}
+
+impl LoadedDocument {
+    pub fn new(sections: Vec<Section>) -> Self {
+        Self {
+            sections,
+            encoding: None,
+            metadata: DocumentMetadata::default(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Single-section document decoded from text with the given encoding.
+    pub fn decoded(text: String, encoding: &str) -> Self {
+        Self {
+            sections: vec![Section::new(text)],
+            encoding: Some(encoding.to_string()),
+            metadata: DocumentMetadata::default(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+/// Generic document loader interface: raw bytes in, text sections out.
+pub trait DocumentLoader {
+    fn name(&self) -> &'static str;
+    /// Lower-case file extensions (without the dot) this loader handles.
+    fn extensions(&self) -> &'static [&'static str];
+    /// MIME types this loader handles, matched against `sniff_mime`.
+    fn mime_types(&self) -> &'static [&'static str];
+    fn load(&self, bytes: &[u8]) -> Result<LoadedDocument>;
+}
+
+/// Loaders tried in registration order: by extension first, then by
+/// sniffed content type for unknown or missing extensions.
+pub struct LoaderRegistry {
+    loaders: Vec<Box<dyn DocumentLoader>>,
+}
+
+impl Default for LoaderRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(Box::new(MarkdownLoader));
+        registry.register(Box::new(PlainTextLoader));
+        registry.register(Box::new(PdfLoader));
+        registry.register(Box::new(HtmlLoader));
+        registry.register(Box::new(DocxLoader));
+        registry.register(Box::new(EpubLoader));
+        registry.register(Box::new(NotebookLoader));
+        registry
+    }
+}
+
+impl LoaderRegistry {
+    pub fn empty() -> Self {
+        Self {
+            loaders: Vec::new(),
+        }
+    }
+
+    pub fn register(&mut self, loader: Box<dyn DocumentLoader>) {
+        self.loaders.push(loader);
+    }
+
+    /// Pick the loader for a file, or explain why none applies.
+    pub fn loader_for(&self, path: &Path, bytes: &[u8]) -> Result<&dyn DocumentLoader> {
+        if let Some(ext) = extension(path) {
+            if let Some(loader) = self
+                .loaders
+                .iter()
+                .find(|l| l.extensions().contains(&ext.as_str()))
+            {
+                return Ok(loader.as_ref());
+            }
+        }
+
+        let mime = sniff_mime(bytes);
+        self.loaders
+            .iter()
+            .find(|l| l.mime_types().contains(&mime))
+            .map(|l| l.as_ref())
+            .ok_or_else(|| anyhow!("unsupported file type ({})", mime))
+    }
+
+    /// Turn raw file bytes into normalized text sections (see `normalize_text`).
+    pub fn load(&self, path: &Path, bytes: &[u8]) -> Result<LoadedDocument> {
+        let mut doc = self.loader_for(path, bytes)?.load(bytes)?;
+        for section in &mut doc.sections {
+            section.text = normalize_text(&section.text);
+        }
+        Ok(doc)
+    }
+}
+
+/// Guess a MIME type from file content (magic bytes and leading markup).
+pub fn sniff_mime(bytes: &[u8]) -> &'static str {
+    if bytes.starts_with(b"%PDF-") {
+        return "application/pdf";
+    }
+    if bytes.starts_with(b"PK\x03\x04") {
+        return sniff_zip(bytes);
+    }
+
+    let head = &bytes[..bytes.len().min(8192)];
+    let text = if is_utf16(head) {
+        decode_text(head).0
+    } else if head.contains(&0) {
+        return "application/octet-stream";
+    } else {
+        String::from_utf8_lossy(head).into_owned()
+    };
+    let start = text.trim_start_matches('\u{feff}').trim_start();
+    let lower = start
+        .get(..start.len().min(64))
+        .unwrap_or(start)
+        .to_ascii_lowercase();
+    if lower.starts_with("<!doctype html") || lower.starts_with("<html") {
+        "text/html"
+    } else if start.starts_with('{') && start.contains("\"cells\"") {
+        "application/x-ipynb+json"
+    } else {
+        "text/plain"
+    }
+}
+
+/// Tell EPUB and DOCX apart from other zip archives.
+fn sniff_zip(bytes: &[u8]) -> &'static str {
+    let Ok(mut archive) = open_zip(bytes) else {
+        return "application/zip";
+    };
+    if let Ok(mimetype) = read_zip_entry(&mut archive, "mimetype") {
+        if mimetype.trim() == "application/epub+zip" {
+            return "application/epub+zip";
+        }
+    }
+    if archive.by_name("word/document.xml").is_ok() {
+        return "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+    }
+    "application/zip"
+}
+
+fn open_zip(bytes: &[u8]) -> Result<ZipArchive<Cursor<&[u8]>>> {
+    ZipArchive::new(Cursor::new(bytes)).map_err(|e| anyhow!("invalid zip container: {}", e))
+}
+
+fn read_zip_entry(archive: &mut ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<String> {
+    let mut entry = archive
+        .by_name(name)
+        .map_err(|_| anyhow!("missing {} in archive", name))?;
+    let mut buf = Vec::new();
+    entry.read_to_end(&mut buf)?;
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+/// Lower-cased file extension, if any.
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+}
+
+/// Plain text files in any detectable encoding (see `decode_text`).
+pub struct PlainTextLoader;
+
+impl DocumentLoader for PlainTextLoader {
+    fn name(&self) -> &'static str {
+        "text"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["txt", "rst", "rs"]
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["text/plain"]
+    }
+
+    fn load(&self, bytes: &[u8]) -> Result<LoadedDocument> {
+        let (text, encoding) = decode_text(bytes);
+        Ok(LoadedDocument::decoded(text, encoding))
+    }
+}
+
+/// PDF files, one section per page. Requires the `pdf` feature.
+pub struct PdfLoader;
+
+impl DocumentLoader for PdfLoader {
+    fn name(&self) -> &'static str {
+        "pdf"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["pdf"]
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["application/pdf"]
+    }
+
+    #[cfg(feature = "pdf")]
+    fn load(&self, bytes: &[u8]) -> Result<LoadedDocument> {
+        pdf::extract_pages(bytes)
+    }
+
+    #[cfg(not(feature = "pdf"))]
+    fn load(&self, _bytes: &[u8]) -> Result<LoadedDocument> {
+        Err(anyhow!(
+            "PDF support not enabled (rebuild with `--features pdf`)"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    fn build_zip(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, body) in entries {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(body.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn plain_text_is_a_single_unpaged_section() {
+        let sections = LoaderRegistry::default()
+            .load(Path::new("notes.md"), b"# Title\nbody")
+            .unwrap()
+            .sections;
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[0].page, None);
+        assert_eq!(sections[0].text, "# Title\nbody");
+    }
+
+    #[test]
+    fn unknown_extensions_fall_back_to_content_sniffing() {
+        let registry = LoaderRegistry::default();
+        let html = b"<!DOCTYPE html><html><body><p>Hi</p></body></html>";
+        let docx = build_zip(&[(
+            "word/document.xml",
+            "<w:document><w:p><w:r><w:t>Hello docx</w:t></w:r></w:p></w:document>",
+        )]);
+
+        assert_eq!(
+            registry.loader_for(Path::new("page"), html).unwrap().name(),
+            "html"
+        );
+        assert_eq!(
+            registry
+                .loader_for(Path::new("report.bin"), &docx)
+                .unwrap()
+                .name(),
+            "docx"
+        );
+        assert_eq!(
+            registry.load(Path::new("report"), &docx).unwrap().sections[0].text,
+            "Hello docx"
+        );
+        let latin1 = registry
+            .load(Path::new("notes"), b"Caf\xe9 cr\xe8me, na\xefve fa\xe7ade\r\n")
+            .unwrap();
+        assert_eq!(latin1.sections[0].text, "Café crème, naïve façade\n");
+        assert_eq!(latin1.encoding.as_deref(), Some("windows-1252"));
+        assert!(registry
+            .load(Path::new("blob.dat"), b"\x00\x01\x02")
+            .is_err());
+    }
+
+    #[test]
+    fn epub_chapters_become_sections() {
+        let epub = build_zip(&[
+            ("mimetype", "application/epub+zip"),
+            (
+                "META-INF/container.xml",
+                r#"<container><rootfiles><rootfile full-path="OEBPS/content.opf"/></rootfiles></container>"#,
+            ),
+            (
+                "OEBPS/content.opf",
+                r#"<package><manifest><item id="a" href="a.xhtml"/><item id="b" href="b.xhtml"/></manifest>
+                   <spine><itemref idref="a"/><itemref idref="b"/></spine></package>"#,
+            ),
+            (
+                "OEBPS/a.xhtml",
+                "<html><body><h1>One</h1><p>First.</p></body></html>",
+            ),
+            ("OEBPS/b.xhtml", "<html><body><p>Second.</p></body></html>"),
+        ]);
+
+        assert_eq!(sniff_mime(&epub), "application/epub+zip");
+        let sections = LoaderRegistry::default()
+            .load(Path::new("book.epub"), &epub)
+            .unwrap()
+            .sections;
+        let texts: Vec<&str> = sections.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(texts, vec!["# One\n\nFirst.", "Second."]);
+    }
+
+    #[test]
+    fn epub_skips_missing_spine_items_with_a_warning() {
+        let epub = build_zip(&[
+            ("mimetype", "application/epub+zip"),
+            (
+                "META-INF/container.xml",
+                r#"<container><rootfiles><rootfile full-path="OEBPS/opf/content.opf"/></rootfiles></container>"#,
+            ),
+            (
+                "OEBPS/opf/content.opf",
+                r#"<package><manifest><item id="a" href="../text/a.xhtml#top"/><item id="gone" href="gone.xhtml"/></manifest>
+                   <spine><itemref idref="a"/><itemref idref="gone"/></spine></package>"#,
+            ),
+            ("OEBPS/text/a.xhtml", "<html><body><p>Only.</p></body></html>"),
+        ]);
+
+        let doc = LoaderRegistry::default().load(Path::new("book.epub"), &epub).unwrap();
+        assert_eq!(doc.sections.len(), 1);
+        assert_eq!(doc.sections[0].text, "Only.");
+        assert_eq!(doc.warnings.len(), 1);
+        assert!(doc.warnings[0].contains("gone.xhtml"));
+    }
+}
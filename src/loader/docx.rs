@@ -0,0 +1,103 @@
+use anyhow::Result;
+
+use super::markup::{attr, decode_entities, tokenize, Token};
+use super::{open_zip, read_zip_entry, DocumentLoader, LoadedDocument, Section};
+
+/// Word documents: paragraph text from `word/document.xml`, with heading
+/// styles rendered as Markdown-style `#` lines.
+pub struct DocxLoader;
+
+impl DocumentLoader for DocxLoader {
+    fn name(&self) -> &'static str {
+        "docx"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["docx"]
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["application/vnd.openxmlformats-officedocument.wordprocessingml.document"]
+    }
+
+    fn load(&self, bytes: &[u8]) -> Result<LoadedDocument> {
+        let mut archive = open_zip(bytes)?;
+        let xml = read_zip_entry(&mut archive, "word/document.xml")?;
+        Ok(LoadedDocument::new(vec![Section::new(document_xml_to_text(&xml))]))
+    }
+}
+
+fn document_xml_to_text(xml: &str) -> String {
+    let mut out = String::new();
+    let mut para = String::new();
+    let mut heading: Option<usize> = None;
+    let mut in_text = false;
+
+    for token in tokenize(xml) {
+        match token {
+            Token::Start {
+                name,
+                attrs,
+                self_closing,
+            } => match name.as_str() {
+                "w:p" => {
+                    para.clear();
+                    heading = None;
+                }
+                "w:pstyle" => heading = attr(attrs, "w:val").and_then(|v| style_level(&v)),
+                "w:t" => in_text = !self_closing,
+                "w:tab" => para.push('\t'),
+                "w:br" | "w:cr" => para.push('\n'),
+                _ => {}
+            },
+            Token::End { name } => match name.as_str() {
+                "w:t" => in_text = false,
+                "w:p" => {
+                    let text = para.trim();
+                    if !text.is_empty() {
+                        if let Some(level) = heading {
+                            out.push_str(&"#".repeat(level));
+                            out.push(' ');
+                        }
+                        out.push_str(text);
+                        out.push_str("\n\n");
+                    }
+                    para.clear();
+                }
+                _ => {}
+            },
+            Token::Text(text) if in_text => para.push_str(&decode_entities(text)),
+            Token::Text(_) => {}
+        }
+    }
+
+    out.trim_end().to_string()
+}
+
+/// Heading level for Word paragraph styles (`Title`, `Heading1`..`Heading9`).
+fn style_level(style: &str) -> Option<usize> {
+    if style.eq_ignore_ascii_case("title") {
+        return Some(1);
+    }
+    let digits = style
+        .strip_prefix("Heading")
+        .or_else(|| style.strip_prefix("heading"))?;
+    digits.parse::<usize>().ok().map(|n| n.clamp(1, 6))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_paragraphs_and_heading_styles() {
+        let xml = r#"<?xml version="1.0"?>
+            <w:document><w:body>
+              <w:p><w:pPr><w:pStyle w:val="Heading2"/></w:pPr><w:r><w:t>Setup</w:t></w:r></w:p>
+              <w:p><w:r><w:t xml:space="preserve">Run </w:t></w:r><w:r><w:t>cargo &amp; go</w:t></w:r></w:p>
+              <w:p></w:p>
+            </w:body></w:document>"#;
+
+        assert_eq!(document_xml_to_text(xml), "## Setup\n\nRun cargo & go");
+    }
+}
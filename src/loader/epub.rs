@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+
+use super::html::html_to_text;
+use super::markup::{attr, tokenize, Token};
+use super::{open_zip, read_zip_entry, DocumentLoader, LoadedDocument, Section};
+
+/// EPUB books: each XHTML document in the spine becomes one section.
+pub struct EpubLoader;
+
+impl DocumentLoader for EpubLoader {
+    fn name(&self) -> &'static str {
+        "epub"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["epub"]
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["application/epub+zip"]
+    }
+
+    fn load(&self, bytes: &[u8]) -> Result<LoadedDocument> {
+        let mut archive = open_zip(bytes)?;
+
+        let container = read_zip_entry(&mut archive, "META-INF/container.xml")?;
+        let opf_path = find_attr(&container, "rootfile", "full-path")
+            .ok_or_else(|| anyhow!("EPUB container has no rootfile"))?;
+        let opf = read_zip_entry(&mut archive, &opf_path)?;
+        let base = opf_path.rsplit_once('/').map_or("", |(dir, _)| dir);
+
+        let mut sections = Vec::new();
+        let mut warnings = Vec::new();
+        let mut seen: Vec<String> = Vec::new();
+        for href in spine_hrefs(&opf) {
+            // Several spine items may point into one file by fragment.
+            let path = resolve_href(base, &href);
+            if seen.contains(&path) {
+                continue;
+            }
+            seen.push(path.clone());
+            let xhtml = match read_zip_entry(&mut archive, &path) {
+                Ok(xhtml) => xhtml,
+                Err(e) => {
+                    warnings.push(format!("skipped spine item {}: {}", href, e));
+                    continue;
+                }
+            };
+            let text = html_to_text(&xhtml);
+            if !text.trim().is_empty() {
+                sections.push(Section::new(text));
+            }
+        }
+        let mut doc = LoadedDocument::new(sections);
+        doc.warnings = warnings;
+        Ok(doc)
+    }
+}
+
+/// Archive path of a manifest `href` relative to the OPF directory `base`:
+/// the fragment is dropped and `.` / `..` segments are resolved.
+fn resolve_href(base: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or_default();
+    let mut parts: Vec<&str> = base.split('/').filter(|p| !p.is_empty()).collect();
+    for part in href.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// Value of `attr_name` on the first `tag` element.
+fn find_attr(xml: &str, tag: &str, attr_name: &str) -> Option<String> {
+    tokenize(xml).into_iter().find_map(|t| match t {
+        Token::Start { name, attrs, .. } if name == tag => attr(attrs, attr_name),
+        _ => None,
+    })
+}
+
+/// Manifest hrefs of the spine items, in reading order.
+fn spine_hrefs(opf: &str) -> Vec<String> {
+    let mut manifest: HashMap<String, String> = HashMap::new();
+    let mut spine: Vec<String> = Vec::new();
+
+    for token in tokenize(opf) {
+        if let Token::Start { name, attrs, .. } = token {
+            // OPF files are sometimes namespace-prefixed (`opf:item`).
+            match name.rsplit(':').next().unwrap_or_default() {
+                "item" => {
+                    if let (Some(id), Some(href)) = (attr(attrs, "id"), attr(attrs, "href")) {
+                        manifest.insert(id, href);
+                    }
+                }
+                "itemref" => spine.extend(attr(attrs, "idref")),
+                _ => {}
+            }
+        }
+    }
+
+    spine
+        .iter()
+        .filter_map(|id| manifest.get(id))
+        .map(|href| href.replace("%20", " "))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spine_order_follows_itemrefs() {
+        let opf = r#"<package>
+            <manifest>
+              <item id="c2" href="text/ch2.xhtml" media-type="application/xhtml+xml"/>
+              <item id="c1" href="text/ch1.xhtml" media-type="application/xhtml+xml"/>
+            </manifest>
+            <spine><itemref idref="c1"/><itemref idref="c2"/></spine>
+        </package>"#;
+
+        assert_eq!(spine_hrefs(opf), vec!["text/ch1.xhtml", "text/ch2.xhtml"]);
+    }
+
+    #[test]
+    fn hrefs_resolve_against_the_opf_directory() {
+        assert_eq!(
+            resolve_href("OEBPS", "text/ch1.xhtml#start"),
+            "OEBPS/text/ch1.xhtml"
+        );
+        assert_eq!(
+            resolve_href("OEBPS/content", "../text/./ch2.xhtml"),
+            "OEBPS/text/ch2.xhtml"
+        );
+        assert_eq!(resolve_href("", "ch3.xhtml"), "ch3.xhtml");
+    }
+}
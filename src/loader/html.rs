@@ -0,0 +1,214 @@
+use anyhow::Result;
+
+use super::markup::{attr, decode_entities, tokenize, Token};
+use super::{DocumentLoader, LoadedDocument};
+use crate::encoding::decode_text;
+
+/// HTML pages: drops scripts, navigation and other boilerplate and keeps
+/// headings as Markdown-style `#` lines.
+pub struct HtmlLoader;
+
+impl DocumentLoader for HtmlLoader {
+    fn name(&self) -> &'static str {
+        "html"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["html", "htm", "xhtml"]
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["text/html"]
+    }
+
+    fn load(&self, bytes: &[u8]) -> Result<LoadedDocument> {
+        let (markup, encoding) = decode_text(bytes);
+        Ok(LoadedDocument::decoded(html_to_text(&markup), encoding))
+    }
+}
+
+/// Elements whose whole subtree is boilerplate rather than content.
+const SKIPPED: &[&str] = &[
+    "head", "script", "style", "noscript", "template", "svg", "iframe", "nav", "header", "footer",
+    "aside", "form", "button", "select",
+];
+
+/// ARIA landmark roles that mark boilerplate regions.
+const SKIPPED_ROLES: &[&str] = &["navigation", "banner", "contentinfo", "complementary"];
+
+const BLOCKS: &[&str] = &[
+    "p",
+    "div",
+    "section",
+    "article",
+    "main",
+    "ul",
+    "ol",
+    "table",
+    "tr",
+    "pre",
+    "blockquote",
+    "br",
+    "hr",
+    "dl",
+    "dt",
+    "dd",
+    "figure",
+    "figcaption",
+    "body",
+];
+
+const VOID: &[&str] = &[
+    "br", "hr", "img", "meta", "link", "input", "col", "wbr", "source",
+];
+
+/// Convert HTML markup into readable plain text.
+pub fn html_to_text(markup: &str) -> String {
+    let mut w = TextWriter::default();
+    // Stack of open boilerplate elements; text is dropped while non-empty.
+    let mut skipping: Vec<String> = Vec::new();
+    let mut pre_depth = 0usize;
+
+    for token in tokenize(markup) {
+        match token {
+            Token::Start {
+                name,
+                attrs,
+                self_closing,
+            } => {
+                if self_closing || VOID.contains(&name.as_str()) {
+                    if skipping.is_empty() && BLOCKS.contains(&name.as_str()) {
+                        w.end_block();
+                    }
+                    continue;
+                }
+                if let Some(top) = skipping.last() {
+                    if *top == name {
+                        skipping.push(name);
+                    }
+                    continue;
+                }
+                let role = attr(attrs, "role").unwrap_or_default();
+                if SKIPPED.contains(&name.as_str()) || SKIPPED_ROLES.contains(&role.as_str()) {
+                    skipping.push(name);
+                    continue;
+                }
+
+                if let Some(level) = heading_level(&name) {
+                    w.end_block();
+                    w.prefix = format!("{} ", "#".repeat(level));
+                } else if name == "li" {
+                    w.end_line();
+                    w.prefix = "- ".to_string();
+                } else if BLOCKS.contains(&name.as_str()) {
+                    w.end_block();
+                }
+                if name == "pre" {
+                    pre_depth += 1;
+                }
+            }
+            Token::End { name } => {
+                if let Some(top) = skipping.last() {
+                    if *top == name {
+                        skipping.pop();
+                    }
+                    continue;
+                }
+                if name == "pre" {
+                    pre_depth = pre_depth.saturating_sub(1);
+                }
+                if name == "li" {
+                    w.end_line();
+                } else if heading_level(&name).is_some() || BLOCKS.contains(&name.as_str()) {
+                    w.end_block();
+                }
+            }
+            Token::Text(text) => {
+                if skipping.is_empty() {
+                    w.push_text(&decode_entities(text), pre_depth > 0);
+                }
+            }
+        }
+    }
+
+    w.end_block();
+    w.out.trim_end().to_string()
+}
+
+fn heading_level(name: &str) -> Option<usize> {
+    match name.as_bytes() {
+        [b'h', d @ b'1'..=b'6'] => Some((d - b'0') as usize),
+        _ => None,
+    }
+}
+
+/// Accumulates lines, collapsing whitespace outside `<pre>`.
+#[derive(Default)]
+struct TextWriter {
+    out: String,
+    line: String,
+    prefix: String,
+}
+
+impl TextWriter {
+    fn push_text(&mut self, text: &str, preformatted: bool) {
+        if preformatted {
+            self.line.push_str(text);
+            return;
+        }
+        for c in text.chars() {
+            if c.is_whitespace() {
+                if !self.line.is_empty() && !self.line.ends_with(' ') {
+                    self.line.push(' ');
+                }
+            } else {
+                self.line.push(c);
+            }
+        }
+    }
+
+    /// Finish the current line (list items).
+    fn end_line(&mut self) {
+        let text = self.line.trim();
+        if !text.is_empty() {
+            self.out.push_str(&self.prefix);
+            self.out.push_str(text);
+            self.out.push('\n');
+        }
+        self.line.clear();
+        self.prefix.clear();
+    }
+
+    /// Finish the current paragraph, leaving a blank line after it.
+    fn end_block(&mut self) {
+        self.end_line();
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_boilerplate_and_keeps_headings() {
+        let html = r#"<!DOCTYPE html>
+            <html><head><title>T</title><style>p { color: red }</style></head>
+            <body>
+              <nav><a href="/">Home</a></nav>
+              <div role="banner">Site banner</div>
+              <h1>Ownership</h1>
+              <p>Each value has   an <b>owner</b>.</p>
+              <ul><li>One</li><li>Two &amp; three</li></ul>
+              <script>var x = "<p>hidden</p>";</script>
+              <footer>Copyright</footer>
+            </body></html>"#;
+
+        assert_eq!(
+            html_to_text(html),
+            "# Ownership\n\nEach value has an owner.\n\n- One\n- Two & three"
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+use super::{DocumentLoader, LoadedDocument};
+use crate::encoding::decode_text;
+
+/// Jupyter notebooks: cell sources labelled with their cell type.
+/// Outputs are skipped, they are usually noise for retrieval.
+pub struct NotebookLoader;
+
+impl DocumentLoader for NotebookLoader {
+    fn name(&self) -> &'static str {
+        "ipynb"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["ipynb"]
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["application/x-ipynb+json"]
+    }
+
+    fn load(&self, bytes: &[u8]) -> Result<LoadedDocument> {
+        let (json, encoding) = decode_text(bytes);
+        let notebook: Value = serde_json::from_str(&json)?;
+        let cells = notebook["cells"]
+            .as_array()
+            .ok_or_else(|| anyhow!("notebook has no cells"))?;
+
+        let mut parts = Vec::new();
+        for cell in cells {
+            let kind = cell["cell_type"].as_str().unwrap_or("unknown");
+            let source = match &cell["source"] {
+                Value::String(s) => s.clone(),
+                Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+                _ => String::new(),
+            };
+            if !source.trim().is_empty() {
+                parts.push(format!("[{} cell]\n{}", kind, source.trim_end()));
+            }
+        }
+        Ok(LoadedDocument::decoded(parts.join("\n\n"), encoding))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_cells_with_their_type() {
+        let nb = br##"{"nbformat": 4, "cells": [
+            {"cell_type": "markdown", "source": ["# Intro\n", "Text"]},
+            {"cell_type": "code", "source": "print(1)", "outputs": [{"text": "1"}]},
+            {"cell_type": "code", "source": []}
+        ]}"##;
+
+        let sections = NotebookLoader.load(nb).unwrap().sections;
+        assert_eq!(
+            sections[0].text,
+            "[markdown cell]\n# Intro\nText\n\n[code cell]\nprint(1)"
+        );
+    }
+}
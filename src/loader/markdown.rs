@@ -0,0 +1,150 @@
+use anyhow::Result;
+
+use super::{DocumentLoader, LoadedDocument};
+use crate::encoding::decode_text;
+use crate::models::{DocumentMetadata, FieldValue};
+
+/// Markdown files. YAML front matter becomes document metadata and is
+/// left out of the indexed text.
+pub struct MarkdownLoader;
+
+impl DocumentLoader for MarkdownLoader {
+    fn name(&self) -> &'static str {
+        "markdown"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["md", "markdown"]
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn load(&self, bytes: &[u8]) -> Result<LoadedDocument> {
+        let (text, encoding) = decode_text(bytes);
+        let (metadata, body) = split_front_matter(&text);
+        let mut doc = LoadedDocument::decoded(body.to_string(), encoding);
+        doc.metadata = metadata;
+        Ok(doc)
+    }
+}
+
+/// Split front matter (between `---` lines at the very start) from the
+/// body. Only the flat subset of YAML seen in practice is understood:
+/// `key: value` scalars, `[a, b]` inline lists and `- item` block lists;
+/// nested mappings are skipped. `tags` (or `tag`) become tags, other keys
+/// fields, with list items joined by `, `.
+pub fn split_front_matter(text: &str) -> (DocumentMetadata, &str) {
+    let mut metadata = DocumentMetadata::default();
+    let Some(rest) = text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))
+    else {
+        return (metadata, text);
+    };
+
+    let mut entries: Vec<(String, Vec<String>)> = Vec::new();
+    let mut offset = 0;
+    let mut body = None;
+    for line in rest.split_inclusive('\n') {
+        offset += line.len();
+        let line = line.trim_end();
+        if line == "---" || line == "..." {
+            body = Some(&rest[offset..]);
+            break;
+        }
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if trimmed.len() < line.len() {
+            // Indented: an item of the open block list, or nesting we skip.
+            if let (Some(item), Some((_, items))) = (trimmed.strip_prefix("- "), entries.last_mut())
+            {
+                items.push(unquote(item));
+            }
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        let items = match value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            Some(list) => list
+                .split(',')
+                .map(unquote)
+                .filter(|v| !v.is_empty())
+                .collect(),
+            None if value.is_empty() => Vec::new(),
+            None => vec![unquote(value)],
+        };
+        entries.push((key.trim().to_string(), items));
+    }
+    // Without a closing line this was not front matter.
+    let Some(body) = body else {
+        return (metadata, text);
+    };
+
+    for (key, items) in entries {
+        if key == "tags" || key == "tag" {
+            let tags = items.iter().flat_map(|t| t.split(',')).map(str::trim);
+            metadata.merge(&DocumentMetadata {
+                tags: tags.filter(|t| !t.is_empty()).map(String::from).collect(),
+                ..DocumentMetadata::default()
+            });
+        } else if let [value] = items.as_slice() {
+            metadata.fields.insert(key, FieldValue::One(value.clone()));
+        } else if !items.is_empty() {
+            metadata.fields.insert(key, FieldValue::List(items));
+        }
+    }
+    (metadata, body)
+}
+
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|v| v.strip_suffix(quote))
+        {
+            return inner.to_string();
+        }
+    }
+    value.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn front_matter_becomes_metadata() {
+        let text = "---\ntitle: \"Borrowing\"\ntags: [rust, 'memory']\nauthors:\n  - ann\n  - bo\nextra:\n  nested: skipped\n# comment\n---\n# Borrowing\nbody\n";
+        let (meta, body) = split_front_matter(text);
+        assert_eq!(body, "# Borrowing\nbody\n");
+        assert_eq!(meta.tags, ["rust", "memory"]);
+        assert_eq!(meta.fields["title"], FieldValue::One("Borrowing".to_string()));
+        assert_eq!(
+            meta.fields["authors"],
+            FieldValue::List(vec!["ann".to_string(), "bo".to_string()])
+        );
+        assert!(!meta.fields.contains_key("extra"));
+
+        let (meta, _) = split_front_matter("---\ntags:\n  - a\ntag: b, c\n---\n");
+        assert_eq!(meta.tags, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn text_without_front_matter_is_unchanged() {
+        for text in [
+            "# Title\n---\nkey: value\n---\n",
+            "---\nkey: value\nno closing line",
+        ] {
+            let (meta, body) = split_front_matter(text);
+            assert!(meta.is_empty());
+            assert_eq!(body, text);
+        }
+    }
+}
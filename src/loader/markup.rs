@@ -0,0 +1,228 @@
+/// A token from the tolerant tag scanner shared by the HTML, DOCX and EPUB loaders.
+/// Tag names are lower-cased; attribute text is left raw (see `attr`).
+#[derive(Debug, PartialEq)]
+pub enum Token<'a> {
+    Start {
+        name: String,
+        attrs: &'a str,
+        self_closing: bool,
+    },
+    End {
+        name: String,
+    },
+    Text(&'a str),
+}
+
+/// Split markup into tags and text. Comments, doctypes and processing
+/// instructions are dropped; CDATA and `<script>`/`<style>` bodies come
+/// back as plain text. Never fails: malformed input degrades to text.
+pub fn tokenize(input: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = input;
+
+    while !rest.is_empty() {
+        let Some(lt) = rest.find('<') else {
+            tokens.push(Token::Text(rest));
+            break;
+        };
+        if lt > 0 {
+            tokens.push(Token::Text(&rest[..lt]));
+            rest = &rest[lt..];
+        }
+
+        if let Some(body) = rest.strip_prefix("<!--") {
+            rest = body.find("-->").map_or("", |i| &body[i + 3..]);
+        } else if let Some(body) = rest.strip_prefix("<![CDATA[") {
+            let end = body.find("]]>").unwrap_or(body.len());
+            tokens.push(Token::Text(&body[..end]));
+            rest = body.get(end + 3..).unwrap_or("");
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            rest = rest.find('>').map_or("", |i| &rest[i + 1..]);
+        } else if let Some(body) = rest.strip_prefix("</") {
+            let end = body.find('>').unwrap_or(body.len());
+            let name = body[..end].trim().to_ascii_lowercase();
+            tokens.push(Token::End { name });
+            rest = body.get(end + 1..).unwrap_or("");
+        } else if rest[1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            let end = tag_end(rest);
+            let inner = &rest[1..end];
+            let self_closing = inner.ends_with('/');
+            let inner = inner.trim_end_matches('/');
+            let name_len = inner
+                .find(|c: char| c.is_whitespace())
+                .unwrap_or(inner.len());
+            let name = inner[..name_len].to_ascii_lowercase();
+            let attrs = &inner[name_len..];
+            rest = rest.get(end + 1..).unwrap_or("");
+
+            let raw_text = !self_closing && (name == "script" || name == "style");
+            tokens.push(Token::Start {
+                name: name.clone(),
+                attrs,
+                self_closing,
+            });
+            if raw_text {
+                let close = format!("</{}", name);
+                let end = find_ascii_ci(rest, &close).unwrap_or(rest.len());
+                tokens.push(Token::Text(&rest[..end]));
+                rest = &rest[end..];
+            }
+        } else {
+            tokens.push(Token::Text("<"));
+            rest = &rest[1..];
+        }
+    }
+
+    tokens
+}
+
+/// Index of the `>` closing the tag at the start of `s`, skipping quoted values.
+fn tag_end(s: &str) -> usize {
+    let mut quote: Option<char> = None;
+    for (i, c) in s.char_indices().skip(1) {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '>') => return i,
+            _ => {}
+        }
+    }
+    s.len()
+}
+
+fn find_ascii_ci(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|w| w.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// Look up an attribute value (case-insensitive key) in raw attribute text.
+pub fn attr(attrs: &str, key: &str) -> Option<String> {
+    let mut rest = attrs.trim_start();
+    while !rest.is_empty() {
+        let name_len = rest
+            .find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let name = &rest[..name_len];
+        rest = rest[name_len..].trim_start();
+
+        let mut value = "";
+        if let Some(after_eq) = rest.strip_prefix('=') {
+            let after_eq = after_eq.trim_start();
+            if let Some(q) = after_eq.chars().next().filter(|c| *c == '"' || *c == '\'') {
+                let body = &after_eq[1..];
+                let end = body.find(q).unwrap_or(body.len());
+                value = &body[..end];
+                rest = body.get(end + 1..).unwrap_or("");
+            } else {
+                let end = after_eq
+                    .find(|c: char| c.is_whitespace())
+                    .unwrap_or(after_eq.len());
+                value = &after_eq[..end];
+                rest = &after_eq[end..];
+            }
+        }
+
+        if name.eq_ignore_ascii_case(key) {
+            return Some(decode_entities(value));
+        }
+        rest = rest.trim_start();
+    }
+    None
+}
+
+/// Decode XML/HTML character references and the common named entities.
+pub fn decode_entities(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&semi| semi <= 10)
+            .and_then(|semi| decode_entity(&rest[1..=semi]).map(|c| (c, semi + 2)));
+        match decoded {
+            Some((c, consumed)) => {
+                out.push(c);
+                rest = &rest[consumed..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    if let Some(num) = name.strip_prefix('#') {
+        let code = match num.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => num.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    let c = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        "ndash" => '–',
+        "mdash" => '—',
+        "hellip" => '…',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "copy" => '©',
+        _ => return None,
+    };
+    Some(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenizes_tags_attributes_and_raw_script_text() {
+        let tokens = tokenize(r#"<p class="a>b">x &amp; y</p><script>if (a < b) {}</script>"#);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Start {
+                    name: "p".into(),
+                    attrs: r#" class="a>b""#,
+                    self_closing: false
+                },
+                Token::Text("x &amp; y"),
+                Token::End { name: "p".into() },
+                Token::Start {
+                    name: "script".into(),
+                    attrs: "",
+                    self_closing: false
+                },
+                Token::Text("if (a < b) {}"),
+                Token::End {
+                    name: "script".into()
+                },
+            ]
+        );
+        assert_eq!(attr(r#" class="a>b""#, "CLASS").as_deref(), Some("a>b"));
+        assert_eq!(
+            decode_entities("x &amp; y &#x41;&#66; &bogus;"),
+            "x & y AB &bogus;"
+        );
+    }
+}
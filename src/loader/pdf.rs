@@ -0,0 +1,140 @@
+use anyhow::{anyhow, Result};
+use lopdf::Document;
+
+use super::{LoadedDocument, Section};
+
+/// Extract text from a PDF, one section per page (1-based page numbers).
+/// Pages without any extractable text (e.g. scanned images) are dropped;
+/// pages that fail to extract are skipped with a warning. Loading fails only
+/// when extraction errors leave no page with text.
+pub fn extract_pages(bytes: &[u8]) -> Result<LoadedDocument> {
+    let doc = Document::load_mem(bytes).map_err(|e| anyhow!("invalid PDF: {}", e))?;
+
+    let mut sections = Vec::new();
+    let mut warnings = Vec::new();
+    for page_no in doc.get_pages().keys() {
+        let text = match doc.extract_text(&[*page_no]) {
+            Ok(text) => text,
+            Err(e) => {
+                warnings.push(format!("skipped page {}: {}", page_no, e));
+                continue;
+            }
+        };
+        if text.trim().is_empty() {
+            continue;
+        }
+        sections.push(Section {
+            text,
+            page: Some(*page_no),
+        });
+    }
+    if sections.is_empty() && !warnings.is_empty() {
+        return Err(anyhow!(
+            "failed to extract any page: {}",
+            warnings.join("; ")
+        ));
+    }
+    let mut loaded = LoadedDocument::new(sections);
+    loaded.warnings = warnings;
+    Ok(loaded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::content::{Content, Operation};
+    use lopdf::{dictionary, Object, Stream};
+
+    /// Build a minimal PDF with one line of Courier text per page.
+    fn build_pdf(pages: &[&str]) -> Vec<u8> {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Courier",
+        });
+        let resources_id = doc.add_object(dictionary! {
+            "Font" => dictionary! { "F1" => font_id },
+        });
+
+        let mut kids = Vec::new();
+        for text in pages {
+            let content = Content {
+                operations: vec![
+                    Operation::new("BT", vec![]),
+                    Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                    Operation::new("Td", vec![72.into(), 720.into()]),
+                    Operation::new("Tj", vec![Object::string_literal(*text)]),
+                    Operation::new("ET", vec![]),
+                ],
+            };
+            let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+            let page_id = doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "Contents" => content_id,
+            });
+            kids.push(page_id.into());
+        }
+
+        let count = kids.len() as i64;
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => kids,
+                "Count" => count,
+                "Resources" => resources_id,
+                "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+
+        let mut out = Vec::new();
+        doc.save_to(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn extracts_text_per_page_with_page_numbers() {
+        let bytes = build_pdf(&["Ownership rules", "Borrow checker"]);
+        let sections = extract_pages(&bytes).unwrap().sections;
+
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].page, Some(1));
+        assert!(sections[0].text.contains("Ownership"));
+        assert_eq!(sections[1].page, Some(2));
+        assert!(sections[1].text.contains("Borrow"));
+    }
+
+    /// Replace the contents of `page_no` with a `Tf` that has no operands.
+    fn break_page(bytes: &[u8], page_no: u32) -> Vec<u8> {
+        let mut doc = Document::load_mem(bytes).unwrap();
+        let page_id = doc.get_pages()[&page_no];
+        let content_id = doc.add_object(Stream::new(dictionary! {}, b"BT Tf ET".to_vec()));
+        let page = doc.get_object_mut(page_id).unwrap().as_dict_mut().unwrap();
+        page.set("Contents", content_id);
+        let mut out = Vec::new();
+        doc.save_to(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn skips_pages_that_fail_to_extract() {
+        let bytes = break_page(&build_pdf(&["Ownership rules", "Borrow checker"]), 1);
+        let loaded = extract_pages(&bytes).unwrap();
+
+        assert_eq!(loaded.sections.len(), 1);
+        assert_eq!(loaded.sections[0].page, Some(2));
+        assert_eq!(loaded.warnings.len(), 1);
+        assert!(loaded.warnings[0].contains("page 1"));
+
+        let bytes = break_page(&build_pdf(&["Ownership rules"]), 1);
+        assert!(extract_pages(&bytes).is_err());
+    }
+}
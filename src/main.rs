@@ -1,86 +1,569 @@
-use anyhow::Result;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
 use clap::Parser;
 use dotenvy::dotenv;
 
-use tapssp_project::cli::{Cli, Commands};
-use tapssp_project::embedder::{Embedder, LocalEmbedder, OpenAIEmbedder};
-use tapssp_project::ingest::run_ingest;
-use tapssp_project::query::run_query;
+use tapssp_project::chat::{run_chat, ChatOptions, ChatSession};
+use tapssp_project::cli::{Cli, Commands, IndexCommand, LlmBackend};
+use tapssp_project::collections::{query_collections, resolve_collections, run_collections, ALL_COLLECTIONS};
+use tapssp_project::context::{pack_context, ContextOptions};
+use tapssp_project::docs::run_docs;
+use tapssp_project::embedder::{Embedder, EmbedderSpec, LocalEmbedder, OpenAIEmbedder};
+use tapssp_project::expand::{check_kinds, ExpansionKind, QueryExpansion, SynonymMap};
+use tapssp_project::filter::QueryFilter;
+use tapssp_project::generate::{Generator, GeneratorConfig, OllamaGenerator, OpenAIGenerator};
+use tapssp_project::index::{build_index, evaluate_recall, retrain_ivf, BuildOptions, IndexKind};
+use tapssp_project::ingest::{run_ingest_with, IngestOptions};
+use tapssp_project::models::{Answer, SearchResult};
+use tapssp_project::output::{OutputFormat, QueryReport, QueryStatus, NO_CONTEXT_MESSAGE};
+use tapssp_project::explain::{explain as explain_query, QueryExplanation};
+use tapssp_project::query::{run_query_traced, QueryOptions};
+#[cfg(feature = "onnx")]
+use tapssp_project::rerank::CrossEncoderReranker;
+use tapssp_project::rerank::{HttpReranker, LexicalReranker, RerankKind, Reranker};
 use tapssp_project::stats::run_stats;
 use tapssp_project::store::Store;
+use tapssp_project::summarize::ExtractiveGenerator;
+use tapssp_project::template::{default_template_dir, PromptTemplate};
+use tapssp_project::watch::{run_watch, WatchOptions};
+
+/// Exit status of `rag query --fail-if-empty` when nothing relevant was found
+/// (1 is taken by errors, 2 by usage errors).
+const NO_CONTEXT_EXIT_CODE: i32 = 3;
 
 fn main() -> Result<()> {
     dotenv().ok(); // allow loading OPENAI_API_KEY from .env
     let cli = Cli::parse();
 
-    let store = Store::new(&cli.db)?;
+    let mut store = Store::new(&cli.db)?;
 
-    let embedder = build_embedder(&cli);
+    // Only queries read several collections; ingesting creates the target.
+    let several = cli.collection.len() > 1 || cli.collection.iter().any(|c| c == ALL_COLLECTIONS);
+    let writes = matches!(cli.command, Commands::Ingest { .. } | Commands::Watch { .. });
+    let merged = match &cli.command {
+        Commands::Query { .. } if several => Some(resolve_collections(&store, &cli.collection)?),
+        _ if several => bail!("only `rag query` takes several collections"),
+        Commands::Collections { .. } => None,
+        _ => {
+            if writes {
+                store.ensure_collection(&cli.collection[0])?;
+            }
+            store.use_collection(&cli.collection[0])?;
+            None
+        }
+    };
+    if let Some(first) = merged.as_ref().and_then(|names| names.first()) {
+        store.use_collection(first)?;
+    }
+
+    let embedder = collection_embedder(&cli, &store, writes)?;
 
     match &cli.command {
         Commands::Ingest {
             paths,
             chunk_size,
             overlap,
+            name,
+            tags,
+            meta,
+            max_entry_mb,
         } => {
-            run_ingest(&store, embedder.as_ref(), paths, *chunk_size, *overlap)?;
+            let opts = IngestOptions {
+                chunk_size: *chunk_size,
+                overlap: *overlap,
+                stdin_name: name.clone(),
+                tags: tags.clone(),
+                fields: meta.iter().cloned().collect(),
+                max_entry_bytes: max_entry_mb.saturating_mul(1 << 20),
+            };
+            run_ingest_with(&store, embedder.as_ref(), paths, &opts)?;
         }
         Commands::Query {
             question,
             top_k,
             raw_only,
+            context_tokens,
+            context_order,
+            stream,
+            format,
+            paths,
+            extensions,
+            since,
+            tags,
+            doc_ids,
+            meta,
+            mmr,
+            max_per_doc,
+            min_score,
+            relative_cutoff,
+            fail_if_empty,
+            explain,
+            rerank,
+            rerank_candidates,
+            rerank_url,
+            rerank_model,
+            rerank_model_dir,
+            expand,
+            synonyms,
+            expand_rewrites,
+            prf_docs,
+            exact,
+            rescore,
+            nprobe,
         } => {
-            let results = run_query(&store, embedder.as_ref(), question, *top_k)?;
-            if *raw_only {
-                print_raw_results(&results);
+            if *stream && *format != OutputFormat::Text {
+                bail!("--stream only works with --format text");
+            }
+            if *stream && *explain {
+                bail!("--explain cannot be combined with --stream");
+            }
+            let template = load_template(&cli)?;
+            let opts = QueryOptions {
+                top_k: *top_k,
+                filter: QueryFilter {
+                    paths: paths.clone(),
+                    extensions: extensions.clone(),
+                    since: *since,
+                    tags: tags.clone(),
+                    doc_ids: doc_ids.clone(),
+                    meta: meta.clone(),
+                },
+                mmr_lambda: *mmr,
+                max_per_doc: *max_per_doc,
+                reranker: match rerank {
+                    Some(kind) => Some(build_reranker(
+                        *kind,
+                        rerank_url.as_deref(),
+                        rerank_model,
+                        rerank_model_dir.as_deref(),
+                    )?),
+                    None => None,
+                },
+                rerank_candidates: *rerank_candidates,
+                min_score: *min_score,
+                relative_cutoff: *relative_cutoff,
+                expansion: build_expansion(&cli, &template, expand, synonyms.as_deref(), *expand_rewrites, *prf_docs)?,
+                exact: *exact,
+                rescore: *rescore,
+                nprobe: *nprobe,
+            };
+            let (results, explanation) = match &merged {
+                Some(_) if *explain => bail!("--explain works on one collection at a time"),
+                Some(names) => {
+                    let results = query_collections(&mut store, names, question, &opts, |s| {
+                        collection_embedder(&cli, s, false)
+                    })?;
+                    (results, None)
+                }
+                None => {
+                    let (results, trace) = run_query_traced(&store, embedder.as_ref(), question, &opts)?;
+                    let explanation = if *explain {
+                        Some(explain_query(&store, embedder.as_ref(), question, &opts, &results, &trace)?)
+                    } else {
+                        None
+                    };
+                    (results, explanation)
+                }
+            };
+            // Nothing to ground an answer in: say so instead of generating.
+            let answer = if *raw_only || results.is_empty() {
+                None
             } else {
-                print_synthesized_answer(question, &results);
+                let generator = build_generator(&cli, &template)?
+                    .unwrap_or_else(|| Box::new(ExtractiveGenerator::new(embedder.as_ref())));
+                let context = pack_context(
+                    &results,
+                    &ContextOptions {
+                        token_budget: *context_tokens,
+                        order: *context_order,
+                    },
+                );
+                if *stream {
+                    stream_answer(generator.as_ref(), question, &context, &template)?;
+                    return Ok(());
+                }
+                Some(generator.generate(question, &context)?)
+            };
+
+            let mut report = QueryReport::new(question, &results, answer.as_ref());
+            if let Some(explanation) = &explanation {
+                report = report.with_explanation(explanation);
+            }
+            if let (OutputFormat::Text, Some(explanation)) = (format, &explanation) {
+                print_explanation(&results, explanation);
+            }
+            match (format, &answer) {
+                (OutputFormat::Text, _) if results.is_empty() => println!("{}", NO_CONTEXT_MESSAGE),
+                (OutputFormat::Text, None) if explanation.is_some() => {}
+                (OutputFormat::Text, None) => print_raw_results(&results, None),
+                (OutputFormat::Text, Some(answer)) => {
+                    print_generated_answer(question, answer, &template)
+                }
+                (OutputFormat::Json, _) => println!("{}", report.to_json()?),
+                (OutputFormat::Jsonl, _) => print!("{}", report.to_jsonl()?),
+                (OutputFormat::Markdown, _) => print!("{}", report.to_markdown(&template)),
             }
+            if *fail_if_empty && report.status == QueryStatus::NoRelevantContext {
+                std::process::exit(NO_CONTEXT_EXIT_CODE);
+            }
+        }
+        Commands::Chat {
+            top_k,
+            context_tokens,
+            context_order,
+            session,
+        } => {
+            let template = load_template(&cli)?;
+            let llm = build_generator(&cli, &template)?;
+            let extractive = ExtractiveGenerator::new(embedder.as_ref());
+            let generator: &dyn Generator = llm.as_deref().unwrap_or(&extractive);
+            let opts = ChatOptions {
+                top_k: *top_k,
+                context: ContextOptions {
+                    token_budget: *context_tokens,
+                    order: *context_order,
+                },
+                template: template.clone(),
+                ..ChatOptions::default()
+            };
+            let chat = match session {
+                Some(id) => ChatSession::resume(
+                    &store,
+                    embedder.as_ref(),
+                    generator,
+                    llm.as_deref(),
+                    opts,
+                    *id,
+                )?,
+                None => {
+                    ChatSession::start(&store, embedder.as_ref(), generator, llm.as_deref(), opts)?
+                }
+            };
+            run_chat(chat, &cli.db.with_extension("history"))?;
+        }
+        Commands::Watch {
+            dirs,
+            chunk_size,
+            overlap,
+            debounce_ms,
+            initial,
+        } => {
+            let opts = WatchOptions {
+                ingest: IngestOptions {
+                    chunk_size: *chunk_size,
+                    overlap: *overlap,
+                    ..IngestOptions::default()
+                },
+                debounce: Duration::from_millis(*debounce_ms),
+                initial_sync: *initial,
+                ignore: vec![cli.db.clone()],
+            };
+            run_watch(&store, embedder.as_ref(), dirs, &opts)?;
         }
         Commands::Stats {} => {
             run_stats(&store)?;
         }
+        Commands::Index { action } => match action {
+            IndexCommand::Build {
+                kind,
+                subspaces,
+                iterations,
+                lists,
+                nprobe,
+            } => {
+                let opts = BuildOptions {
+                    kind: *kind,
+                    subspaces: *subspaces,
+                    iterations: *iterations,
+                    lists: *lists,
+                    nprobe: *nprobe,
+                    ..BuildOptions::default()
+                };
+                let encoded = build_index(&mut store, &opts)?;
+                match store.vector_index().ivf() {
+                    _ if *kind == IndexKind::Flat => println!("Vector index: flat (exact scan)"),
+                    Some(ivf) => println!(
+                        "Vector index: ivf ({} chunks in {} lists, nprobe {})",
+                        encoded,
+                        ivf.lists(),
+                        ivf.nprobe
+                    ),
+                    None => println!("Vector index: {} ({} chunks encoded)", kind.name(), encoded),
+                }
+            }
+            IndexCommand::Eval {
+                queries,
+                top_k,
+                rescore,
+                nprobe,
+            } => {
+                let opts = QueryOptions {
+                    rescore: *rescore,
+                    nprobe: *nprobe,
+                    ..QueryOptions::default()
+                };
+                let report = evaluate_recall(&store, *queries, *top_k, &opts)?;
+                let mut settings = Vec::new();
+                if let Some(n) = report.rescore {
+                    settings.push(format!("rescore {}", n));
+                }
+                if let Some(n) = report.nprobe {
+                    settings.push(format!("nprobe {}", n));
+                }
+                println!(
+                    "Index     : {}{}",
+                    report.kind.name(),
+                    if settings.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" ({})", settings.join(", "))
+                    }
+                );
+                println!("Queries   : {}", report.queries);
+                println!("Recall@{:<3}: {:.3}", report.top_k, report.recall);
+                println!("Scanned   : {} chunks/query", report.scanned);
+                println!("Exact     : {:.1} ms", report.exact_time.as_secs_f64() * 1000.0);
+                println!("Index     : {:.1} ms", report.index_time.as_secs_f64() * 1000.0);
+            }
+            IndexCommand::Retrain { threshold, force } => {
+                let (drift, retrained) = retrain_ivf(&mut store, *threshold, *force)?;
+                println!(
+                    "Drift: {:.0}% chunk count change, {:+.0}% centroid distance",
+                    drift.size_change() * 100.0,
+                    drift.error_growth() * 100.0
+                );
+                if retrained {
+                    println!("Retrained IVF index over {} chunks", drift.chunks);
+                } else {
+                    println!("Within --threshold {}; index kept", threshold);
+                }
+            }
+        },
+        Commands::Docs { action } => run_docs(&store, action)?,
+        Commands::Collections { action } => run_collections(&mut store, action)?,
     }
 
     Ok(())
 }
 
-fn build_embedder(cli: &Cli) -> Box<dyn Embedder> {
-    if let Some(key) = cli.openai_api_key.clone().or_else(|| std::env::var("OPENAI_API_KEY").ok())
-    {
-        Box::new(OpenAIEmbedder::new(key, cli.openai_model.clone()))
-    } else {
-        Box::new(LocalEmbedder::new(256))
+fn openai_api_key(cli: &Cli) -> Option<String> {
+    cli.openai_api_key.clone().or_else(|| std::env::var("OPENAI_API_KEY").ok())
+}
+
+/// Embedder of the store's collection: the one it was created with, else
+/// the one it was embedded with, else OpenAI if a key is set and the local
+/// embedder otherwise. Writing records the choice on first use.
+fn collection_embedder(cli: &Cli, store: &Store, record: bool) -> Result<Box<dyn Embedder>> {
+    let info = store.collection_info()?;
+    let spec = match info.embedder.or(info.embedder_name) {
+        Some(spec) => spec.parse().map_err(|e: String| anyhow!(e))?,
+        None if openai_api_key(cli).is_some() => EmbedderSpec::OpenAi {
+            model: cli.openai_model.clone(),
+        },
+        None => EmbedderSpec::Local {
+            dim: EmbedderSpec::DEFAULT_LOCAL_DIM,
+        },
+    };
+    store.check_embedder(&spec.to_string(), record)?;
+    build_embedder(cli, &spec)
+}
+
+fn build_embedder(cli: &Cli, spec: &EmbedderSpec) -> Result<Box<dyn Embedder>> {
+    Ok(match spec {
+        EmbedderSpec::Local { dim } => Box::new(LocalEmbedder::new(*dim)),
+        EmbedderSpec::OpenAi { model } => {
+            let key = openai_api_key(cli)
+                .ok_or_else(|| anyhow!("{} embeddings need --openai-api-key or OPENAI_API_KEY", spec))?;
+            Box::new(OpenAIEmbedder::new(key, model.clone()))
+        }
+    })
+}
+
+/// The `--template` preset; only commands that generate or format answers need it.
+fn load_template(cli: &Cli) -> Result<PromptTemplate> {
+    let template_dir = cli.template_dir.clone().unwrap_or_else(default_template_dir);
+    PromptTemplate::load(&template_dir, &cli.template)
+}
+
+/// LLM backend selected with `--llm`, if any.
+fn build_generator<'a>(cli: &Cli, template: &PromptTemplate) -> Result<Option<Box<dyn Generator + 'a>>> {
+    let Some(backend) = cli.llm else {
+        return Ok(None);
+    };
+    let (default_url, default_model) = match backend {
+        LlmBackend::Openai => ("https://api.openai.com/v1", "gpt-4o-mini"),
+        LlmBackend::Ollama => ("http://localhost:11434", "llama3.1"),
+    };
+    let config = GeneratorConfig {
+        base_url: cli.llm_base_url.clone().unwrap_or_else(|| default_url.to_string()),
+        model: cli.llm_model.clone().unwrap_or_else(|| default_model.to_string()),
+        temperature: cli.temperature,
+        max_tokens: cli.max_tokens,
+        api_key: cli.openai_api_key.clone().or_else(|| std::env::var("OPENAI_API_KEY").ok()),
+        template: template.clone(),
+        timeout: (cli.llm_timeout > 0).then(|| Duration::from_secs(cli.llm_timeout)),
+    };
+    Ok(Some(match backend {
+        LlmBackend::Openai => Box::new(OpenAIGenerator::new(config)?),
+        LlmBackend::Ollama => Box::new(OllamaGenerator::new(config)?),
+    }))
+}
+
+fn build_expansion(
+    cli: &Cli,
+    template: &PromptTemplate,
+    kinds: &[ExpansionKind],
+    synonyms: Option<&Path>,
+    rewrites: usize,
+    prf_docs: usize,
+) -> Result<QueryExpansion> {
+    check_kinds(kinds, cli.llm.is_some())?;
+    let mut expansion = QueryExpansion::default();
+    for kind in kinds {
+        match kind {
+            ExpansionKind::Synonyms => {
+                expansion.synonyms = Some(match synonyms {
+                    Some(path) => SynonymMap::load(path)?,
+                    None => SynonymMap::builtin(),
+                })
+            }
+            ExpansionKind::Prf => expansion.prf_docs = Some(prf_docs),
+            ExpansionKind::Rewrite => expansion.rewrites = rewrites,
+            ExpansionKind::Hyde => expansion.hyde = true,
+        }
+    }
+    if expansion.rewrites > 0 || expansion.hyde {
+        let generator: Option<Box<dyn Generator>> = build_generator(cli, template)?;
+        expansion.generator = generator.map(Arc::from);
     }
+    Ok(expansion)
 }
 
-fn print_raw_results(results: &[tapssp_project::models::SearchResult]) {
+fn build_reranker(
+    kind: RerankKind,
+    url: Option<&str>,
+    model: &str,
+    model_dir: Option<&Path>,
+) -> Result<Arc<dyn Reranker>> {
+    Ok(match kind {
+        RerankKind::Lexical => Arc::new(LexicalReranker),
+        RerankKind::Http => {
+            let url = url.ok_or_else(|| anyhow!("--rerank http needs --rerank-url"))?;
+            Arc::new(HttpReranker::new(
+                url.to_string(),
+                model.to_string(),
+                std::env::var("RERANK_API_KEY").ok(),
+            ))
+        }
+        #[cfg(feature = "onnx")]
+        RerankKind::CrossEncoder => {
+            let dir = model_dir
+                .ok_or_else(|| anyhow!("--rerank cross-encoder needs --rerank-model-dir"))?;
+            Arc::new(CrossEncoderReranker::load(dir)?)
+        }
+        #[cfg(not(feature = "onnx"))]
+        RerankKind::CrossEncoder => {
+            let _ = model_dir;
+            bail!("cross-encoder reranking not enabled (rebuild with `--features onnx`)")
+        }
+    })
+}
+
+fn print_generated_answer(question: &str, answer: &Answer, template: &PromptTemplate) {
+    println!("Question: {}", question);
     println!("─────────────────────────────────────────────");
-    for (i, r) in results.iter().enumerate() {
-        println!("#{} | score = {:.4}", i + 1, r.score);
-        println!("File : {}", r.document_path);
-        println!("Span : {}..{}", r.chunk.start_char, r.chunk.end_char);
-        println!("Text :\n{}\n", r.chunk.text.trim());
-        println!("─────────────────────────────────────────────");
+    if answer.text.is_empty() {
+        println!("(No answer could be extracted from the retrieved context.)\n");
+    } else {
+        println!("{}\n", answer.text);
     }
+    print_sources(answer, template);
 }
 
-/// VERY SIMPLE synthesizer: just concatenates top chunks with a header.
-/// (For the project, you can extend this to an actual LLM call if desired.)
-fn print_synthesized_answer(
+/// Print the answer as it is generated. The first Ctrl-C stops the
+/// request (sources for the partial answer are still listed); a second
+/// one exits immediately.
+fn stream_answer(
+    generator: &dyn Generator,
     question: &str,
-    results: &[tapssp_project::models::SearchResult],
-) {
+    context: &[SearchResult],
+    template: &PromptTemplate,
+) -> Result<()> {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&cancel);
+    ctrlc::set_handler(move || {
+        if flag.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+    })?;
+
     println!("Question: {}", question);
     println!("─────────────────────────────────────────────");
-    println!("(Prototype synthesized answer)\n");
+    let mut print_token = |token: &str| {
+        print!("{}", token);
+        let _ = io::stdout().flush();
+    };
+    let answer = generator.generate_stream(question, context, &mut print_token, &cancel)?;
+    println!("\n");
+    if cancel.load(Ordering::SeqCst) {
+        println!("(Cancelled; answer is incomplete.)\n");
+    }
+    print_sources(&answer, template);
+    Ok(())
+}
 
-    for (i, r) in results.iter().enumerate() {
-        println!("[{}] From {}", i + 1, r.document_path);
-        println!("Score: {:.4}", r.score);
-        println!("Snippet:\n{}\n", r.chunk.text.trim());
+fn print_sources(answer: &Answer, template: &PromptTemplate) {
+    println!("Sources:");
+    for c in &answer.citations {
+        println!("  {}", template.render_citation(c));
     }
+}
+
+fn print_explanation(results: &[SearchResult], explanation: &QueryExplanation) {
+    println!("Explain:");
+    for line in explanation.summary_lines() {
+        println!("  {}", line);
+    }
+    print_raw_results(results, Some(explanation));
+}
 
+fn print_raw_results(results: &[SearchResult], explanation: Option<&QueryExplanation>) {
     println!("─────────────────────────────────────────────");
-    println!("(Above snippets are the most relevant context chunks.)");
+    let merged = results.iter().any(|r| r.collection != results[0].collection);
+    for (i, r) in results.iter().enumerate() {
+        let mut header = format!("#{} | score = {:.4}", i + 1, r.score);
+        if merged {
+            header.push_str(&format!(" | collection = {}", r.collection));
+        }
+        if let Some(fs) = r.fused_score {
+            header.push_str(&format!(" | fused = {:.4}", fs));
+        }
+        if let Some(rs) = r.rerank_score {
+            header.push_str(&format!(" | rerank = {:.4}", rs));
+        }
+        println!("{}", header);
+        println!("File : {}", r.source_label());
+        println!("Span : {}..{}", r.chunk.start_char, r.chunk.end_char);
+        if !r.metadata.tags.is_empty() {
+            println!("Tags : {}", r.metadata.tags.join(", "));
+        }
+        if !r.metadata.fields.is_empty() {
+            let fields: Vec<String> = r.metadata.fields.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+            println!("Meta : {}", fields.join(", "));
+        }
+        println!("Text :\n{}\n", r.chunk.text.trim());
+        if let Some(hit) = explanation.and_then(|e| e.hits.get(i)) {
+            println!("Why  :");
+            for line in hit.lines() {
+                println!("  {}", line);
+            }
+            println!();
+        }
+        println!("─────────────────────────────────────────────");
+    }
 }
@@ -1,30 +1,225 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
 use chrono::{DateTime, Utc};
+use serde::Serialize;
 use uuid::Uuid;
 
+use crate::index::IndexKind;
+use crate::store::DEFAULT_COLLECTION;
+
 /// A single ingested document (file).
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Document {
     pub id: Uuid,
     pub path: String,
     pub created_at: DateTime<Utc>,
+    /// Source character encoding detected at ingest (e.g. `UTF-8`,
+    /// `windows-1252`); `None` for binary formats such as PDF.
+    pub encoding: Option<String>,
+    pub metadata: DocumentMetadata,
+}
+
+/// User-provided labels of a document: tags and free-form `key=value`
+/// fields, from `--tag`/`--meta`, Markdown front matter or `rag docs tag`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DocumentMetadata {
+    pub tags: Vec<String>,
+    pub fields: BTreeMap<String, FieldValue>,
+}
+
+/// Value of a metadata field: a single string, or a list (e.g. front-matter
+/// `authors: [ann, bo]`) whose items each match `meta:key=item` filters.
+/// Serialized as a string or an array.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum FieldValue {
+    One(String),
+    List(Vec<String>),
+}
+
+impl FieldValue {
+    pub fn items(&self) -> &[String] {
+        match self {
+            FieldValue::One(value) => std::slice::from_ref(value),
+            FieldValue::List(items) => items,
+        }
+    }
+}
+
+impl From<String> for FieldValue {
+    fn from(value: String) -> Self {
+        FieldValue::One(value)
+    }
+}
+
+/// Lists are joined with `, `.
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.items().join(", "))
+    }
+}
+
+impl DocumentMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.fields.is_empty()
+    }
+
+    /// Add `other`'s tags and fields; its field values win.
+    pub fn merge(&mut self, other: &DocumentMetadata) {
+        for tag in &other.tags {
+            if !self.tags.contains(tag) {
+                self.tags.push(tag.clone());
+            }
+        }
+        self.fields.extend(other.fields.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+}
+
+/// A named set of documents with its own embedder and vector index.
+#[derive(Debug, Clone, Serialize)]
+pub struct CollectionInfo {
+    pub name: String,
+    /// Configured embedder (`EmbedderSpec` syntax); `None` uses the
+    /// command-line default
+    pub embedder: Option<String>,
+    /// Embedder the chunks were embedded with (`EmbedderSpec` syntax)
+    pub embedder_name: Option<String>,
+    pub index_kind: IndexKind,
+    pub documents: usize,
+    pub chunks: usize,
+    pub created_at: DateTime<Utc>,
 }
 
 /// A chunk of text derived from a document, with its embedding.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Chunk {
     pub id: Uuid,
     pub doc_id: Uuid,
     pub chunk_index: i32,
     pub text: String,
+    /// Not part of serialized output; vectors are large and model-specific.
+    #[serde(skip_serializing)]
     pub embedding: Vec<f32>,
     pub start_char: i32,
     pub end_char: i32,
+    /// 1-based page number for paged formats such as PDF.
+    pub page: Option<u32>,
+}
+
+impl Chunk {
+    /// A chunk of `text` starting at `start_char`, with a fresh id and no
+    /// embedding or page.
+    pub fn new(doc_id: Uuid, chunk_index: i32, text: &str, start_char: i32) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            doc_id,
+            chunk_index,
+            text: text.to_string(),
+            embedding: Vec::new(),
+            start_char,
+            end_char: start_char + text.chars().count() as i32,
+            page: None,
+        }
+    }
 }
 
 /// Result of a similarity search.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct SearchResult {
     pub chunk: Chunk,
     pub document_path: String,
+    /// First-stage score: the cosine similarity mapped linearly through the
+    /// embedder's calibration, so 0 ≈ unrelated and 1 ≈ near-duplicate. It is
+    /// not clamped and can fall below 0 or exceed 1 (up to about 1.73 for
+    /// the local embedder).
     pub score: f32,
+    /// Rank-fusion score across query variants, scaled so that ranking
+    /// first for every variant gives 1.0; set when query expansion ran
+    pub fused_score: Option<f32>,
+    /// Score from the reranking stage, if one ran
+    pub rerank_score: Option<f32>,
+    /// Collection the chunk belongs to
+    pub collection: String,
+    /// Tags and fields of the chunk's document
+    pub metadata: DocumentMetadata,
+}
+
+impl SearchResult {
+    /// A first-stage result in the default collection, without fused or
+    /// rerank scores or metadata.
+    pub fn new(chunk: Chunk, document_path: impl Into<String>, score: f32) -> Self {
+        Self {
+            chunk,
+            document_path: document_path.into(),
+            score,
+            fused_score: None,
+            rerank_score: None,
+            collection: DEFAULT_COLLECTION.to_string(),
+            metadata: DocumentMetadata::default(),
+        }
+    }
+
+    /// The score results are ranked by: the rerank score when present,
+    /// then the fused score, otherwise the first-stage score.
+    pub fn relevance(&self) -> f32 {
+        self.rerank_score.or(self.fused_score).unwrap_or(self.score)
+    }
+
+    /// Source label for display, e.g. `docs/manual.pdf p.12`.
+    pub fn source_label(&self) -> String {
+        match self.chunk.page {
+            Some(page) => format!("{} p.{}", self.document_path, page),
+            None => self.document_path.clone(),
+        }
+    }
+}
+
+/// A numbered reference from a generated answer back to a source span.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Citation {
+    /// The `[n]` marker used in the answer text (1-based).
+    pub index: usize,
+    pub document_path: String,
+    pub start_char: i32,
+    pub end_char: i32,
+    pub page: Option<u32>,
+}
+
+impl Citation {
+    pub fn from_result(index: usize, r: &SearchResult) -> Self {
+        Self {
+            index,
+            document_path: r.document_path.clone(),
+            start_char: r.chunk.start_char,
+            end_char: r.chunk.end_char,
+            page: r.chunk.page,
+        }
+    }
+
+    /// e.g. `[2] docs/manual.pdf p.12 (1024..1536)`
+    pub fn label(&self) -> String {
+        let page = self.page.map(|p| format!(" p.{}", p)).unwrap_or_default();
+        format!(
+            "[{}] {}{} ({}..{})",
+            self.index, self.document_path, page, self.start_char, self.end_char
+        )
+    }
+}
+
+/// A synthesized answer with the sources it cites.
+#[derive(Debug, Clone, Serialize)]
+pub struct Answer {
+    pub text: String,
+    pub citations: Vec<Citation>,
+}
+
+/// One persisted message of a `rag chat` session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatMessage {
+    /// `user` or `assistant`
+    pub role: String,
+    pub content: String,
+    /// For user messages, the standalone query actually used for retrieval.
+    pub query: Option<String>,
 }
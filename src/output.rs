@@ -0,0 +1,238 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::explain::QueryExplanation;
+use crate::models::{Answer, SearchResult};
+use crate::template::PromptTemplate;
+
+/// Version of the JSON / JSONL query output. Bump it when a field is
+/// renamed or removed or its meaning changes; adding fields keeps the
+/// version. 2: `score` is calibrated per embedder instead of a raw cosine.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Shown instead of an answer when retrieval comes back empty.
+pub const NO_CONTEXT_MESSAGE: &str = "No relevant context found.";
+
+/// Output format of `rag query`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable text
+    Text,
+    /// One JSON document
+    Json,
+    /// One JSON object per line: a `query` header, `result`s, then an `answer`
+    Jsonl,
+    /// Markdown report
+    Markdown,
+}
+
+/// Whether retrieval found anything worth answering from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryStatus {
+    Ok,
+    /// No chunk passed the filters and score thresholds
+    NoRelevantContext,
+}
+
+/// A retrieved chunk with its 1-based rank.
+#[derive(Debug, Serialize)]
+pub struct RankedResult<'a> {
+    pub rank: usize,
+    #[serde(flatten)]
+    pub result: &'a SearchResult,
+}
+
+/// Everything `rag query` produced for one question.
+#[derive(Debug, Serialize)]
+pub struct QueryReport<'a> {
+    pub schema_version: u32,
+    pub question: &'a str,
+    pub status: QueryStatus,
+    pub results: Vec<RankedResult<'a>>,
+    /// `None` with `--raw-only`
+    pub answer: Option<&'a Answer>,
+    /// Score breakdown, with `--explain`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<&'a QueryExplanation>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum JsonlRecord<'a> {
+    Query {
+        schema_version: u32,
+        question: &'a str,
+        status: QueryStatus,
+    },
+    Result(&'a RankedResult<'a>),
+    Explanation(&'a QueryExplanation),
+    Answer(&'a Answer),
+}
+
+impl<'a> QueryReport<'a> {
+    pub fn new(question: &'a str, results: &'a [SearchResult], answer: Option<&'a Answer>) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            question,
+            status: if results.is_empty() {
+                QueryStatus::NoRelevantContext
+            } else {
+                QueryStatus::Ok
+            },
+            results: results
+                .iter()
+                .enumerate()
+                .map(|(i, result)| RankedResult {
+                    rank: i + 1,
+                    result,
+                })
+                .collect(),
+            answer,
+            explanation: None,
+        }
+    }
+
+    pub fn with_explanation(mut self, explanation: &'a QueryExplanation) -> Self {
+        self.explanation = Some(explanation);
+        self
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn to_jsonl(&self) -> Result<String> {
+        let mut records = vec![JsonlRecord::Query {
+            schema_version: self.schema_version,
+            question: self.question,
+            status: self.status,
+        }];
+        records.extend(self.results.iter().map(JsonlRecord::Result));
+        records.extend(self.explanation.map(JsonlRecord::Explanation));
+        records.extend(self.answer.map(JsonlRecord::Answer));
+
+        let mut out = String::new();
+        for r in &records {
+            out.push_str(&serde_json::to_string(r)?);
+            out.push('\n');
+        }
+        Ok(out)
+    }
+
+    /// Sources are listed with the citation template.
+    pub fn to_markdown(&self, template: &PromptTemplate) -> String {
+        let mut out = format!("# {}\n", self.question);
+        if let Some(answer) = self.answer {
+            out.push_str(&format!("\n{}\n", answer.text));
+            if !answer.citations.is_empty() {
+                out.push_str("\n**Sources**\n\n");
+                for c in &answer.citations {
+                    out.push_str(&format!("- {}\n", template.render_citation(c)));
+                }
+            }
+        }
+        out.push_str("\n## Retrieved chunks\n");
+        if self.status == QueryStatus::NoRelevantContext {
+            out.push_str(&format!("\n_{}_\n", NO_CONTEXT_MESSAGE));
+        }
+        for r in &self.results {
+            let chunk = &r.result.chunk;
+            out.push_str(&format!(
+                "\n### {}. {} ({}..{})\n\nscore {:.4} · chunk `{}`\n\n",
+                r.rank,
+                r.result.source_label(),
+                chunk.start_char,
+                chunk.end_char,
+                r.result.score,
+                chunk.id
+            ));
+            for line in chunk.text.trim().lines() {
+                out.push_str(&format!("> {}\n", line));
+            }
+            if let Some(hit) = self.explanation.and_then(|e| e.hits.get(r.rank - 1)) {
+                out.push('\n');
+                for line in hit.lines() {
+                    out.push_str(&format!("- {}\n", line));
+                }
+            }
+        }
+        if let Some(explanation) = self.explanation {
+            out.push_str("\n## Explain\n\n");
+            for line in explanation.summary_lines() {
+                out.push_str(&format!("- {}\n", line));
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Chunk, Citation};
+    use serde_json::Value;
+    use uuid::Uuid;
+
+    fn result(path: &str, text: &str) -> SearchResult {
+        let mut chunk = Chunk::new(Uuid::new_v4(), 2, text, 100);
+        chunk.embedding = vec![0.5; 8];
+        chunk.page = Some(4);
+        SearchResult::new(chunk, path, 0.75)
+    }
+
+    #[test]
+    fn json_report_has_versioned_schema_without_embeddings() {
+        let results = vec![result("a.pdf", "Alpha."), result("b.md", "Beta.")];
+        let answer = Answer {
+            text: "Alpha [1].".to_string(),
+            citations: vec![Citation::from_result(1, &results[0])],
+        };
+        let report = QueryReport::new("what?", &results, Some(&answer));
+
+        let v: Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(v["schema_version"], 2);
+        assert_eq!(v["question"], "what?");
+        assert_eq!(v["results"][1]["rank"], 2);
+        assert_eq!(v["results"][1]["document_path"], "b.md");
+        assert_eq!(
+            v["results"][0]["chunk"]["id"],
+            results[0].chunk.id.to_string()
+        );
+        assert_eq!(v["results"][0]["chunk"]["start_char"], 100);
+        assert_eq!(v["results"][0]["chunk"]["page"], 4);
+        assert!(v["results"][0]["chunk"].get("embedding").is_none());
+        assert_eq!(v["answer"]["citations"][0]["document_path"], "a.pdf");
+    }
+
+    #[test]
+    fn jsonl_tags_each_record() {
+        let results = vec![result("a.md", "Alpha.")];
+        let report = QueryReport::new("what?", &results, None);
+
+        let lines: Vec<Value> = report
+            .to_jsonl()
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["type"], "query");
+        assert_eq!(lines[0]["schema_version"], 2);
+        assert_eq!(lines[1]["type"], "result");
+        assert_eq!(lines[1]["rank"], 1);
+        assert_eq!(lines[1]["chunk"]["text"], "Alpha.");
+        assert_eq!(lines[0]["status"], "ok");
+    }
+
+    #[test]
+    fn empty_results_report_no_relevant_context() {
+        let report = QueryReport::new("what?", &[], None);
+        let v: Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(v["status"], "no_relevant_context");
+        assert!(report
+            .to_markdown(&PromptTemplate::default())
+            .contains(NO_CONTEXT_MESSAGE));
+    }
+}
@@ -0,0 +1,370 @@
+use anyhow::{anyhow, bail, Result};
+use rayon::prelude::*;
+use wide::f32x8;
+
+use crate::score::dot;
+
+/// Centroids per PQ subspace; codes are one byte each.
+pub const PQ_CENTROIDS: usize = 256;
+
+/// Scalar int8 code: a little-endian f32 scale followed by one signed byte
+/// per dimension, `x ≈ scale * code`.
+pub fn int8_encode(v: &[f32]) -> Vec<u8> {
+    let max = v.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+    let scale = if max > 0.0 { max / 127.0 } else { 1.0 };
+    let mut out = Vec::with_capacity(4 + v.len());
+    out.extend_from_slice(&scale.to_le_bytes());
+    out.extend(
+        v.iter()
+            .map(|x| (x / scale).round().clamp(-127.0, 127.0) as i8 as u8),
+    );
+    out
+}
+
+/// Asymmetric dot product: full-precision `query` against an int8 code.
+pub fn int8_dot(query: &[f32], code: &[u8]) -> f32 {
+    let Some((scale, values)) = code.split_first_chunk::<4>() else {
+        return 0.0;
+    };
+    let len = query.len().min(values.len());
+    let (query, values) = (&query[..len], &values[..len]);
+    let mut acc = f32x8::ZERO;
+    let mut q_lanes = query.chunks_exact(8);
+    let mut c_lanes = values.chunks_exact(8);
+    for (q, c) in q_lanes.by_ref().zip(c_lanes.by_ref()) {
+        let q = f32x8::from(<[f32; 8]>::try_from(q).expect("8 lanes"));
+        let c = f32x8::from(std::array::from_fn::<f32, 8, _>(|i| c[i] as i8 as f32));
+        acc = q.mul_add(c, acc);
+    }
+    let tail = q_lanes
+        .remainder()
+        .iter()
+        .zip(c_lanes.remainder())
+        .fold(0.0f32, |acc, (q, &c)| acc + q * (c as i8) as f32);
+    (acc.reduce_add() + tail) * f32::from_le_bytes(*scale)
+}
+
+/// Sign code: one bit per dimension (set when positive), packed
+/// little-endian into bytes.
+pub fn binary_encode(v: &[f32]) -> Vec<u8> {
+    v.chunks(8)
+        .map(|lanes| {
+            lanes
+                .iter()
+                .enumerate()
+                .fold(0u8, |byte, (i, &x)| byte | (((x > 0.0) as u8) << i))
+        })
+        .collect()
+}
+
+/// Number of differing bits, 64 at a time with popcount.
+pub fn hamming(a: &[u8], b: &[u8]) -> u32 {
+    let mut a_words = a.chunks_exact(8);
+    let mut b_words = b.chunks_exact(8);
+    let words: u32 = a_words
+        .by_ref()
+        .zip(b_words.by_ref())
+        .map(|(x, y)| {
+            let x = u64::from_le_bytes(x.try_into().expect("8 bytes"));
+            let y = u64::from_le_bytes(y.try_into().expect("8 bytes"));
+            (x ^ y).count_ones()
+        })
+        .sum();
+    let tail: u32 = a_words
+        .remainder()
+        .iter()
+        .zip(b_words.remainder())
+        .map(|(x, y)| (x ^ y).count_ones())
+        .sum();
+    words + tail
+}
+
+/// Product quantizer: the vector is split into `subspaces` equal slices and
+/// each slice is replaced by the index of its nearest centroid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProductQuantizer {
+    dim: usize,
+    subspaces: usize,
+    /// `subspaces` codebooks of up to `PQ_CENTROIDS` centroids each
+    codebooks: Vec<Vec<Vec<f32>>>,
+}
+
+impl ProductQuantizer {
+    /// Train codebooks with k-means over `vectors` (unit length, same dim).
+    pub fn train(vectors: &[Vec<f32>], subspaces: usize, iterations: usize) -> Result<Self> {
+        let dim = vectors
+            .first()
+            .map(Vec::len)
+            .ok_or_else(|| anyhow!("no vectors to train on"))?;
+        if subspaces == 0 || dim % subspaces != 0 {
+            bail!(
+                "embedding dimension {} is not divisible into {} subspaces",
+                dim,
+                subspaces
+            );
+        }
+        let width = dim / subspaces;
+        let codebooks = (0..subspaces)
+            .map(|m| {
+                let slices: Vec<&[f32]> = vectors
+                    .iter()
+                    .map(|v| &v[m * width..(m + 1) * width])
+                    .collect();
+                kmeans(&slices, PQ_CENTROIDS, iterations)
+            })
+            .collect();
+        Ok(Self {
+            dim,
+            subspaces,
+            codebooks,
+        })
+    }
+
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    fn width(&self) -> usize {
+        self.dim / self.subspaces
+    }
+
+    fn check_dim(&self, v: &[f32]) -> Result<()> {
+        if v.len() != self.dim {
+            bail!(
+                "vector has {} dimensions but the PQ codebooks were trained on {}",
+                v.len(),
+                self.dim
+            );
+        }
+        Ok(())
+    }
+
+    pub fn encode(&self, v: &[f32]) -> Result<Vec<u8>> {
+        self.check_dim(v)?;
+        let width = self.width();
+        Ok(self
+            .codebooks
+            .iter()
+            .enumerate()
+            .map(|(m, codebook)| nearest(codebook, &v[m * width..(m + 1) * width]) as u8)
+            .collect())
+    }
+
+    /// Per-subspace dot products of `query` with every centroid, flattened
+    /// as `[subspace * PQ_CENTROIDS + centroid]`.
+    pub fn lookup_table(&self, query: &[f32]) -> Result<Vec<f32>> {
+        self.check_dim(query)?;
+        let width = self.width();
+        let mut table = vec![0.0; self.subspaces * PQ_CENTROIDS];
+        for (m, codebook) in self.codebooks.iter().enumerate() {
+            let q = &query[m * width..(m + 1) * width];
+            for (c, centroid) in codebook.iter().enumerate() {
+                table[m * PQ_CENTROIDS + c] = dot(q, centroid);
+            }
+        }
+        Ok(table)
+    }
+
+    /// Asymmetric distance computation: the query's dot product with a
+    /// code, summed from its lookup table.
+    pub fn adc(table: &[f32], code: &[u8]) -> f32 {
+        code.iter().enumerate().fold(0.0, |acc, (m, &c)| {
+            acc + table[m * PQ_CENTROIDS + c as usize]
+        })
+    }
+
+    /// `dim`, `subspaces` and centroid count as little-endian u32s, then
+    /// every centroid's f32s.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let k = self.codebooks.first().map_or(0, Vec::len);
+        for n in [self.dim, self.subspaces, k] {
+            out.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+        for x in self.codebooks.iter().flatten().flatten() {
+            out.extend_from_slice(&x.to_le_bytes());
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let word = |i: usize| -> Result<usize> {
+            let b = bytes
+                .get(i * 4..i * 4 + 4)
+                .ok_or_else(|| anyhow!("truncated PQ codebook"))?;
+            Ok(u32::from_le_bytes(b.try_into().expect("4 bytes")) as usize)
+        };
+        let (dim, subspaces, k) = (word(0)?, word(1)?, word(2)?);
+        if dim == 0 || subspaces == 0 || dim % subspaces != 0 || k == 0 || k > PQ_CENTROIDS {
+            bail!("corrupt PQ codebook header");
+        }
+        let floats: Vec<f32> = bytes[12..]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().expect("4 bytes")))
+            .collect();
+        let width = dim / subspaces;
+        if floats.len() != subspaces * k * width {
+            bail!("PQ codebook size does not match its header");
+        }
+        let codebooks = floats
+            .chunks(k * width)
+            .map(|book| book.chunks(width).map(<[f32]>::to_vec).collect())
+            .collect();
+        Ok(Self {
+            dim,
+            subspaces,
+            codebooks,
+        })
+    }
+}
+
+pub fn squared_distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (x - y) * (x - y))
+        .fold(0.0, |s, d| s + d)
+}
+
+/// Index of the centroid closest to `v` (Euclidean).
+pub fn nearest(centroids: &[Vec<f32>], v: &[f32]) -> usize {
+    centroids
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (i, squared_distance(c, v)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map_or(0, |(i, _)| i)
+}
+
+/// Lloyd's k-means, initialised with evenly spaced points so results are
+/// reproducible. Returns `min(k, points.len())` centroids; a cluster that
+/// empties keeps its previous centroid.
+pub fn kmeans(points: &[&[f32]], k: usize, iterations: usize) -> Vec<Vec<f32>> {
+    let k = k.min(points.len());
+    if k == 0 {
+        return Vec::new();
+    }
+    let dim = points[0].len();
+    let mut centroids: Vec<Vec<f32>> = (0..k)
+        .map(|i| points[i * points.len() / k].to_vec())
+        .collect();
+    for _ in 0..iterations {
+        let assignment: Vec<usize> = points.par_iter().map(|p| nearest(&centroids, p)).collect();
+        let mut sums = vec![vec![0.0f32; dim]; k];
+        let mut counts = vec![0usize; k];
+        for (p, &c) in points.iter().zip(&assignment) {
+            counts[c] += 1;
+            for (s, x) in sums[c].iter_mut().zip(p.iter()) {
+                *s += x;
+            }
+        }
+        let mut moved = false;
+        for ((centroid, sum), count) in centroids.iter_mut().zip(sums).zip(counts) {
+            if count == 0 {
+                continue;
+            }
+            let next: Vec<f32> = sum.into_iter().map(|s| s / count as f32).collect();
+            moved |= next != *centroid;
+            *centroid = next;
+        }
+        if !moved {
+            break;
+        }
+    }
+    centroids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::score::normalize;
+
+    fn unit_vectors(n: usize, dim: usize) -> Vec<Vec<f32>> {
+        (0..n)
+            .map(|i| {
+                let mut v: Vec<f32> = (0..dim)
+                    .map(|d| ((i * 31 + d * 17) % 23) as f32 - 11.0)
+                    .collect();
+                normalize(&mut v);
+                v
+            })
+            .collect()
+    }
+
+    #[test]
+    fn int8_dot_is_close_to_exact() {
+        for v in unit_vectors(20, 45) {
+            let q = &unit_vectors(1, 45)[0];
+            let code = int8_encode(&v);
+            assert_eq!(code.len(), 4 + 45);
+            assert!((int8_dot(q, &code) - dot(q, &v)).abs() < 0.02);
+        }
+        assert_eq!(int8_dot(&[1.0], &int8_encode(&[0.0])), 0.0);
+    }
+
+    #[test]
+    fn binary_codes_count_sign_flips() {
+        let a = binary_encode(&[0.5, -1.0, 0.0, 2.0, -0.1, 0.3, 0.3, 0.3, 1.0]);
+        assert_eq!(a, vec![0b1110_1001, 0b1]);
+        let b = binary_encode(&[-0.5, -1.0, 0.0, 2.0, -0.1, 0.3, 0.3, 0.3, -1.0]);
+        assert_eq!(hamming(&a, &b), 2);
+
+        let long: Vec<f32> = (0..200)
+            .map(|i| if i % 3 == 0 { 1.0 } else { -1.0 })
+            .collect();
+        let flipped: Vec<f32> = long.iter().map(|x| -x).collect();
+        assert_eq!(
+            hamming(&binary_encode(&long), &binary_encode(&flipped)),
+            200
+        );
+        assert_eq!(hamming(&binary_encode(&long), &binary_encode(&long)), 0);
+    }
+
+    #[test]
+    fn pq_round_trips_and_approximates_dot() {
+        let vectors = unit_vectors(300, 32);
+        let pq = ProductQuantizer::train(&vectors, 4, 10).unwrap();
+        let q = &vectors[7];
+        let table = pq.lookup_table(q).unwrap();
+        let code = pq.encode(q).unwrap();
+        assert_eq!(code.len(), 4);
+        assert!((ProductQuantizer::adc(&table, &code) - 1.0).abs() < 0.2);
+
+        let restored = ProductQuantizer::from_bytes(&pq.to_bytes()).unwrap();
+        assert_eq!(restored, pq);
+        assert!(ProductQuantizer::train(&vectors, 5, 1).is_err());
+        assert!(ProductQuantizer::from_bytes(&[1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn pq_rejects_mismatched_vectors_and_empty_codebooks() {
+        let vectors = unit_vectors(50, 8);
+        let pq = ProductQuantizer::train(&vectors, 2, 3).unwrap();
+        assert!(pq.encode(&vectors[0][..4]).is_err());
+        assert!(pq.lookup_table(&[0.0; 16]).is_err());
+
+        // dim 8, 2 subspaces, 0 centroids
+        let header: Vec<u8> = [8u32, 2, 0].iter().flat_map(|n| n.to_le_bytes()).collect();
+        assert!(ProductQuantizer::from_bytes(&header).is_err());
+    }
+
+    #[test]
+    fn kmeans_separates_clusters() {
+        let points: Vec<Vec<f32>> = (0..20)
+            .map(|i| {
+                if i % 2 == 0 {
+                    vec![0.0, i as f32 * 0.01]
+                } else {
+                    vec![10.0, 10.0]
+                }
+            })
+            .collect();
+        let refs: Vec<&[f32]> = points.iter().map(Vec::as_slice).collect();
+        let centroids = kmeans(&refs, 2, 10);
+        assert_eq!(centroids.len(), 2);
+        assert_ne!(
+            nearest(&centroids, &[0.0, 0.0]),
+            nearest(&centroids, &[10.0, 10.0])
+        );
+        assert_eq!(kmeans(&refs[..1], 4, 3).len(), 1);
+    }
+}
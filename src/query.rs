@@ -1,9 +1,89 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
 use anyhow::Result;
+use uuid::Uuid;
 
 use crate::embedder::Embedder;
+use crate::expand::{
+    reciprocal_rank_fusion, rocchio, QueryExpansion, QueryVariant, ROCCHIO_ALPHA, ROCCHIO_BETA, RRF_K,
+};
+use crate::filter::QueryFilter;
 use crate::models::SearchResult;
+use crate::rerank::{rerank, Reranker};
+use crate::index::{PreparedQuery, VectorIndex};
+use crate::score::{dot, normalize, top_k_keyed, Scored, TopK};
 use crate::store::Store;
 
+/// Knobs for a retrieval run.
+#[derive(Clone)]
+pub struct QueryOptions {
+    /// Number of chunks to return
+    pub top_k: usize,
+    pub filter: QueryFilter,
+    /// Rerank candidates with maximal marginal relevance; 1.0 is pure
+    /// relevance, lower values favour diversity
+    pub mmr_lambda: Option<f32>,
+    /// Keep at most this many chunks from any one document
+    pub max_per_doc: Option<usize>,
+    /// Second stage run over the best `rerank_candidates` first-stage hits
+    pub reranker: Option<Arc<dyn Reranker>>,
+    pub rerank_candidates: usize,
+    /// Extra query variants fused with the question's own ranking
+    pub expansion: QueryExpansion,
+    /// Drop chunks whose calibrated score is below this
+    pub min_score: Option<f32>,
+    /// Drop chunks scoring below this fraction of the best chunk's score
+    pub relative_cutoff: Option<f32>,
+    /// Scan full-precision embeddings even when a quantized index is built
+    pub exact: bool,
+    /// Rescore this many of the best quantized hits with full-precision
+    /// embeddings before ranking (binary indexes always rescore, by default
+    /// `BINARY_RESCORE_PER_RESULT` per hit wanted)
+    pub rescore: Option<usize>,
+    /// IVF lists to scan; defaults to the index's own `nprobe`
+    pub nprobe: Option<usize>,
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        Self {
+            top_k: 6,
+            filter: QueryFilter::default(),
+            mmr_lambda: None,
+            max_per_doc: None,
+            reranker: None,
+            rerank_candidates: 50,
+            expansion: QueryExpansion::default(),
+            min_score: None,
+            relative_cutoff: None,
+            exact: false,
+            rescore: None,
+            nprobe: None,
+        }
+    }
+}
+
+impl fmt::Debug for QueryOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QueryOptions")
+            .field("top_k", &self.top_k)
+            .field("filter", &self.filter)
+            .field("mmr_lambda", &self.mmr_lambda)
+            .field("max_per_doc", &self.max_per_doc)
+            .field("reranker", &self.reranker.as_ref().map(|r| r.name()))
+            .field("rerank_candidates", &self.rerank_candidates)
+            .field("expansion", &self.expansion)
+            .field("min_score", &self.min_score)
+            .field("relative_cutoff", &self.relative_cutoff)
+            .field("exact", &self.exact)
+            .field("rescore", &self.rescore)
+            .field("nprobe", &self.nprobe)
+            .finish()
+    }
+}
+
 /// Entry point used from CLI.
 pub fn run_query(
     store: &Store,
@@ -11,28 +91,375 @@ pub fn run_query(
     question: &str,
     top_k: usize,
 ) -> Result<Vec<SearchResult>> {
-    let q_vecs = embedder.embed(&[question.to_string()])?;
-    let q_vec = &q_vecs[0];
+    let opts = QueryOptions {
+        top_k,
+        ..QueryOptions::default()
+    };
+    run_query_with(store, embedder, question, &opts)
+}
+
+/// Score the chunks of documents passing `opts.filter` against the question
+/// and return the best `opts.top_k`, expanded, reranked and diversified as
+/// configured. Scores are calibrated for the embedder; the result is empty
+/// when nothing passes the score thresholds.
+pub fn run_query_with(
+    store: &Store,
+    embedder: &dyn Embedder,
+    question: &str,
+    opts: &QueryOptions,
+) -> Result<Vec<SearchResult>> {
+    Ok(run_query_traced(store, embedder, question, opts)?.0)
+}
+
+/// Intermediate state of a query run, kept for `--explain`.
+#[derive(Debug, Clone, Default)]
+pub struct QueryTrace {
+    /// Query variants searched, the question first
+    pub variants: Vec<QueryVariant>,
+    /// 1-based rank of a chunk in each variant's list, when expansion ran
+    pub variant_ranks: HashMap<Uuid, Vec<Option<usize>>>,
+    /// Chunks scored after filtering
+    pub scored: usize,
+    /// Candidates left after the score thresholds
+    pub passed_thresholds: usize,
+    /// 1-based rank of each reranked candidate before reranking
+    pub first_stage_ranks: HashMap<Uuid, usize>,
+}
+
+/// `run_query_with`, also returning how the results came about.
+pub fn run_query_traced(
+    store: &Store,
+    embedder: &dyn Embedder,
+    question: &str,
+    opts: &QueryOptions,
+) -> Result<(Vec<SearchResult>, QueryTrace)> {
+    let variants = if opts.expansion.is_enabled() {
+        opts.expansion.text_variants(question)?
+    } else {
+        vec![QueryVariant::new("question", question)]
+    };
+    let texts: Vec<String> = variants.iter().map(|v| v.text.clone()).collect();
+    let mut q_vecs = embedder.embed(&texts)?;
+    q_vecs.iter_mut().for_each(|v| normalize(v));
+
+    // The per-document cap may skip any number of hits: search deeper
+    // until `top_k` results survive it or more candidates cannot help.
+    let mut depth = candidate_depth(opts);
+    loop {
+        let mut trace = QueryTrace {
+            variants: variants.clone(),
+            ..QueryTrace::default()
+        };
+        let (results, exhausted) = search(store, embedder, question, opts, &q_vecs, depth, &mut trace)?;
+        if opts.max_per_doc.is_none() || exhausted || results.len() >= opts.top_k {
+            return Ok((results, trace));
+        }
+        depth = depth.saturating_mul(PER_DOC_DEEPENING);
+    }
+}
+
+/// Run the stages after query embedding with the `depth` best first-stage
+/// hits per query variant. Also reports whether the candidates were
+/// exhausted, i.e. a deeper search would not change the result.
+fn search(
+    store: &Store,
+    embedder: &dyn Embedder,
+    question: &str,
+    opts: &QueryOptions,
+    q_vecs: &[Vec<f32>],
+    depth: usize,
+    trace: &mut QueryTrace,
+) -> Result<(Vec<SearchResult>, bool)> {
+    let expand = opts.expansion.is_enabled();
+
+    // Stored embeddings are unit length, so cosine similarity is a dot
+    // product. Only embeddings (or their codes) are streamed; text is
+    // fetched for the winners.
+    let (mut lists, scored) = scan_top_k(store, opts, q_vecs, depth, expand)?;
+    trace.scored = scored;
+
+    // Each query variant yields its own ranked list of the chunks it matches
+    // at all; the lists are fused by rank. `score` stays the similarity to
+    // the question as asked.
+    if let Some(n) = opts.expansion.prf_docs {
+        let top: Vec<i64> = lists[0].iter().take(n).copied().collect();
+        let hits = store.chunks_by_rowid(&top)?;
+        let feedback: Vec<&[f32]> = top
+            .iter()
+            .filter_map(|id| hits.get(id))
+            .map(|(chunk, _)| chunk.embedding.as_slice())
+            .collect();
+        let v = rocchio(&q_vecs[0], &feedback, ROCCHIO_ALPHA, ROCCHIO_BETA);
+        lists.extend(scan_top_k(store, opts, &[v], depth, true)?.0);
+        trace
+            .variants
+            .push(QueryVariant::new("prf", &format!("top {} hits", feedback.len())));
+    }
+    let order: Vec<(i64, Option<f32>)> = if expand {
+        let indexed: Vec<Vec<usize>> = lists
+            .iter()
+            .map(|l| l.iter().map(|&id| id as usize).collect())
+            .collect();
+        let best = lists.len() as f32 / (RRF_K + 1.0);
+        reciprocal_rank_fusion(&indexed, RRF_K)
+            .into_iter()
+            .map(|(id, s)| (id as i64, Some(s / best)))
+            .collect()
+    } else {
+        lists[0].iter().map(|&id| (id, None)).collect()
+    };
+
+    let rowids: Vec<i64> = order.iter().map(|(id, _)| *id).collect();
+    let mut winners = store.chunks_by_rowid(&rowids)?;
+    if expand {
+        for (v, list) in lists.iter().enumerate() {
+            for (rank, id) in list.iter().enumerate() {
+                if let Some((chunk, _)) = winners.get(id) {
+                    trace
+                        .variant_ranks
+                        .entry(chunk.id)
+                        .or_insert_with(|| vec![None; lists.len()])[v] = Some(rank + 1);
+                }
+            }
+        }
+    }
+    let doc_ids: Vec<Uuid> = winners.values().map(|(chunk, _)| chunk.doc_id).collect();
+    let metadata = store.document_metadata(&doc_ids)?;
+    let calibration = embedder.calibration();
+    let mut results: Vec<SearchResult> = order
+        .into_iter()
+        .filter_map(|(id, fused_score)| {
+            let (chunk, document_path) = winners.remove(&id)?;
+            let score = calibration.apply(dot(&q_vecs[0], &chunk.embedding));
+            let metadata = metadata.get(&chunk.doc_id).cloned().unwrap_or_default();
+            Some(SearchResult {
+                fused_score,
+                collection: store.collection().to_string(),
+                metadata,
+                ..SearchResult::new(chunk, document_path, score)
+            })
+        })
+        .collect();
+    let candidates = results.len();
+    apply_thresholds(&mut results, opts);
+    trace.passed_thresholds = results.len();
+    // Deeper hits score lower, so they would not pass thresholds that
+    // already cut, nor make it past the rerank cut.
+    let rerank_limit = opts.rerank_candidates.max(opts.top_k);
+    let exhausted = scored <= depth
+        || results.len() < candidates
+        || (opts.reranker.is_some() && results.len() >= rerank_limit);
+
+    if let Some(reranker) = &opts.reranker {
+        results.truncate(rerank_limit);
+        trace.first_stage_ranks = results.iter().enumerate().map(|(i, r)| (r.chunk.id, i + 1)).collect();
+        results = rerank(reranker.as_ref(), question, results)?;
+    }
+    Ok((diversify(results, opts), exhausted))
+}
+
+/// Drop results below `opts.min_score` or `opts.relative_cutoff` times the
+/// best score. Thresholds apply to `score`, the similarity to the question.
+fn apply_thresholds(results: &mut Vec<SearchResult>, opts: &QueryOptions) {
+    let best = results.iter().map(|r| r.score).fold(f32::NEG_INFINITY, f32::max);
+    let relative = match opts.relative_cutoff {
+        Some(ratio) if best > 0.0 => best * ratio,
+        _ => f32::NEG_INFINITY,
+    };
+    let floor = opts.min_score.unwrap_or(f32::NEG_INFINITY).max(relative);
+    results.retain(|r| r.score >= floor);
+}
 
-    let all = store.all_chunks_with_paths()?;
-    let mut results = Vec::new();
+/// Chunks scored per batch of the streaming scan.
+const SCAN_BATCH: usize = 4096;
+
+/// One pass over the chunks passing `opts.filter`: for each query, the
+/// rowids of the `depth` most similar chunks (optionally only those with
+/// positive similarity), best first, and the number of chunks scanned.
+/// Unless `opts.exact` is set, a quantized index scans codes and an IVF
+/// index scans only the lists nearest to the queries.
+pub fn scan_top_k(
+    store: &Store,
+    opts: &QueryOptions,
+    queries: &[Vec<f32>],
+    depth: usize,
+    positive: bool,
+) -> Result<(Vec<Vec<i64>>, usize)> {
+    let index = if opts.exact {
+        &VectorIndex::Flat
+    } else {
+        store.vector_index()
+    };
+    let rescore = index
+        .quantizer()
+        .and_then(|q| opts.rescore.or_else(|| q.default_rescore(depth)));
+    let keep = depth.max(rescore.unwrap_or(0));
+    let mut heaps: Vec<TopK> = queries.iter().map(|_| TopK::new(keep)).collect();
+    let push_dot_hits = |rowids: &[i64], embeddings: &[Vec<f32>]| {
+        for (query, heap) in queries.iter().zip(heaps.iter_mut()) {
+            let key = |i: usize| rowids[i] as usize;
+            for hit in top_k_keyed(embeddings.len(), keep, key, |i| dot(query, &embeddings[i])) {
+                heap.push(hit.index, hit.score);
+            }
+        }
+        Ok(())
+    };
+    let scanned = match index {
+        VectorIndex::Flat => store.scan_embeddings(&opts.filter, SCAN_BATCH, push_dot_hits)?,
+        VectorIndex::Ivf(ivf) => {
+            let nprobe = opts.nprobe.unwrap_or(ivf.nprobe);
+            let mut lists: Vec<usize> = queries.iter().flat_map(|q| ivf.probe(q, nprobe)).collect();
+            lists.sort_unstable();
+            lists.dedup();
+            store.scan_embeddings_in_lists(&opts.filter, &lists, SCAN_BATCH, push_dot_hits)?
+        }
+        VectorIndex::Quantized(quantizer) => {
+            let prepared: Vec<PreparedQuery> = queries.iter().map(|q| quantizer.prepare(q)).collect::<Result<_>>()?;
+            store.scan_codes(&opts.filter, SCAN_BATCH, |rowids, codes| {
+                for (query, heap) in prepared.iter().zip(heaps.iter_mut()) {
+                    let key = |i: usize| rowids[i] as usize;
+                    for hit in top_k_keyed(codes.len(), keep, key, |i| query.score(&codes[i])) {
+                        heap.push(hit.index, hit.score);
+                    }
+                }
+                Ok(())
+            })?
+        }
+    };
 
-    for (chunk, doc_path) in all {
-        let score = cosine_similarity(q_vec, &chunk.embedding);
-        results.push(SearchResult {
-            chunk,
-            document_path: doc_path,
-            score,
-        });
+    let mut ranked: Vec<Vec<Scored>> = heaps.into_iter().map(TopK::into_sorted).collect();
+    if let Some(n) = rescore {
+        let rowids: Vec<i64> = ranked
+            .iter()
+            .flat_map(|hits| hits.iter().take(n).map(|s| s.index as i64))
+            .collect();
+        let embeddings = store.embeddings_by_rowid(&rowids)?;
+        for (query, hits) in queries.iter().zip(ranked.iter_mut()) {
+            // Only the head is rescored; anything past it keeps its
+            // approximate order behind it.
+            let len = n.min(hits.len());
+            let head = &mut hits[..len];
+            for hit in head.iter_mut() {
+                if let Some(v) = embeddings.get(&(hit.index as i64)) {
+                    hit.score = cosine_similarity(query, v);
+                }
+            }
+            head.sort_by(|a, b| b.cmp(a));
+        }
+    }
+    let lists = ranked
+        .into_iter()
+        .map(|hits| {
+            hits.into_iter()
+                .filter(|s| !positive || s.score > 0.0)
+                .take(depth)
+                .map(|s| s.index as i64)
+                .collect()
+        })
+        .collect();
+    Ok((lists, scanned))
+}
+
+/// How many first-stage hits the later stages (fusion, thresholds, rerank,
+/// diversification) need to see, to start with.
+fn candidate_depth(opts: &QueryOptions) -> usize {
+    let mut depth = opts.top_k;
+    if opts.max_per_doc.is_some() {
+        depth = depth.saturating_mul(PER_DOC_DEEPENING);
+    }
+    if opts.mmr_lambda.is_some() || opts.expansion.is_enabled() {
+        depth = depth.max(opts.top_k.saturating_mul(MMR_CANDIDATES_PER_RESULT));
     }
+    if opts.reranker.is_some() || opts.expansion.is_enabled() {
+        depth = depth.max(opts.rerank_candidates);
+    }
+    depth
+}
+
+/// Factor by which the candidate depth grows while the per-document cap
+/// leaves fewer than `top_k` results.
+const PER_DOC_DEEPENING: usize = 4;
+
+/// How many of the best-scoring chunks MMR chooses from, per requested result.
+const MMR_CANDIDATES_PER_RESULT: usize = 5;
+
+/// Pick the final `top_k` from score-sorted results, applying MMR and the
+/// per-document cap when requested.
+fn diversify(mut results: Vec<SearchResult>, opts: &QueryOptions) -> Vec<SearchResult> {
+    let cap = opts.max_per_doc.unwrap_or(usize::MAX);
+    let per_doc_ok = |results: &[SearchResult], i: usize, picked: &[usize]| {
+        let doc = results[i].chunk.doc_id;
+        picked.iter().filter(|&&j| results[j].chunk.doc_id == doc).count() < cap
+    };
+
+    let picked: Vec<usize> = match opts.mmr_lambda {
+        Some(lambda) => {
+            results.truncate(opts.top_k.saturating_mul(MMR_CANDIDATES_PER_RESULT));
+            let relevance: Vec<f32> = results.iter().map(|r| r.relevance()).collect();
+            let vectors: Vec<Vec<f32>> = results.iter().map(|r| r.chunk.embedding.clone()).collect();
+            mmr_select_with(&relevance, &vectors, lambda, opts.top_k, |i, picked| {
+                per_doc_ok(&results, i, picked)
+            })
+        }
+        None => {
+            let mut picked = Vec::new();
+            for i in 0..results.len() {
+                if picked.len() == opts.top_k {
+                    break;
+                }
+                if per_doc_ok(&results, i, &picked) {
+                    picked.push(i);
+                }
+            }
+            picked
+        }
+    };
+
+    let mut slots: Vec<Option<SearchResult>> = results.into_iter().map(Some).collect();
+    picked.into_iter().filter_map(|i| slots[i].take()).collect()
+}
+
+/// Maximal marginal relevance: greedily pick the item maximizing
+/// `lambda * relevance - (1 - lambda) * max similarity to already-picked`.
+pub fn mmr_select(relevance: &[f32], vectors: &[Vec<f32>], lambda: f32, k: usize) -> Vec<usize> {
+    mmr_select_with(relevance, vectors, lambda, k, |_, _| true)
+}
+
+/// Like `mmr_select`, but `allow(i, picked)` can veto candidate `i` given
+/// the items picked so far.
+pub fn mmr_select_with(
+    relevance: &[f32],
+    vectors: &[Vec<f32>],
+    lambda: f32,
+    k: usize,
+    mut allow: impl FnMut(usize, &[usize]) -> bool,
+) -> Vec<usize> {
+    let mut picked: Vec<usize> = Vec::new();
+    let mut remaining: Vec<usize> = (0..relevance.len()).collect();
 
-    results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
-    results.truncate(top_k);
-    Ok(results)
+    while picked.len() < k {
+        remaining.retain(|&i| allow(i, &picked));
+        let Some((pos, _)) = remaining
+            .iter()
+            .enumerate()
+            .map(|(pos, &i)| {
+                let redundancy = picked
+                    .iter()
+                    .map(|&j| cosine_similarity(&vectors[i], &vectors[j]))
+                    .fold(0.0f32, f32::max);
+                (pos, lambda * relevance[i] - (1.0 - lambda) * redundancy)
+            })
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        else {
+            break;
+        };
+        picked.push(remaining.remove(pos));
+    }
+    picked
 }
 
 /// Cosine similarity between two vectors.
-fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     let len = a.len().min(b.len());
     if len == 0 {
         return 0.0;
@@ -58,6 +485,13 @@ fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::Chunk;
+
+    fn result(doc_id: Uuid, embedding: Vec<f32>, score: f32) -> SearchResult {
+        let mut chunk = Chunk::new(doc_id, 0, "", 0);
+        chunk.embedding = embedding;
+        SearchResult::new(chunk, doc_id.to_string(), score)
+    }
 
     #[test]
     fn cosine_similarity_of_identical_is_one() {
@@ -67,6 +501,82 @@ mod tests {
         assert!((s - 1.0).abs() < 1e-5);
     }
 
+    #[test]
+    fn mmr_prefers_diverse_items() {
+        let vectors = vec![vec![1.0, 0.0], vec![1.0, 0.01], vec![0.0, 1.0]];
+        let relevance = vec![0.9, 0.89, 0.6];
+        assert_eq!(mmr_select(&relevance, &vectors, 0.5, 2), vec![0, 2]);
+        assert_eq!(mmr_select(&relevance, &vectors, 1.0, 2), vec![0, 1]);
+    }
+
+    #[test]
+    fn caps_results_per_document() {
+        let doc_a = Uuid::new_v4();
+        let doc_b = Uuid::new_v4();
+        let results = vec![
+            result(doc_a, vec![1.0, 0.0], 0.9),
+            result(doc_a, vec![1.0, 0.1], 0.8),
+            result(doc_a, vec![0.9, 0.2], 0.7),
+            result(doc_b, vec![0.0, 1.0], 0.6),
+        ];
+        let opts = QueryOptions {
+            top_k: 3,
+            max_per_doc: Some(1),
+            ..QueryOptions::default()
+        };
+        let scores: Vec<f32> = diversify(results.clone(), &opts).iter().map(|r| r.score).collect();
+        assert_eq!(scores, vec![0.9, 0.6]);
+
+        let opts = QueryOptions {
+            top_k: 3,
+            mmr_lambda: Some(0.5),
+            max_per_doc: Some(2),
+            ..QueryOptions::default()
+        };
+        let scores: Vec<f32> = diversify(results, &opts).iter().map(|r| r.score).collect();
+        assert_eq!(scores, vec![0.9, 0.6, 0.8]);
+    }
+
+    #[test]
+    fn per_doc_cap_starts_from_a_bounded_depth() {
+        let opts = QueryOptions {
+            top_k: 5,
+            max_per_doc: Some(1),
+            ..QueryOptions::default()
+        };
+        assert_eq!(candidate_depth(&opts), 5 * PER_DOC_DEEPENING);
+    }
+
+    #[test]
+    fn thresholds_drop_weak_results() {
+        let doc = Uuid::new_v4();
+        let results = vec![
+            result(doc, vec![], 0.8),
+            result(doc, vec![], 0.5),
+            result(doc, vec![], 0.1),
+        ];
+        let kept = |opts: &QueryOptions| {
+            let mut r = results.clone();
+            apply_thresholds(&mut r, opts);
+            r.iter().map(|r| r.score).collect::<Vec<_>>()
+        };
+        let opts = QueryOptions {
+            min_score: Some(0.3),
+            ..QueryOptions::default()
+        };
+        assert_eq!(kept(&opts), vec![0.8, 0.5]);
+        let opts = QueryOptions {
+            relative_cutoff: Some(0.7),
+            ..QueryOptions::default()
+        };
+        assert_eq!(kept(&opts), vec![0.8]);
+        let opts = QueryOptions {
+            min_score: Some(0.9),
+            ..QueryOptions::default()
+        };
+        assert!(kept(&opts).is_empty());
+    }
+
     #[test]
     fn cosine_similarity_of_orthogonal_is_zero() {
         let a = vec![1.0, 0.0];
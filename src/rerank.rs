@@ -0,0 +1,219 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::models::SearchResult;
+use crate::text::content_terms;
+
+#[cfg(feature = "onnx")]
+mod onnx;
+#[cfg(feature = "onnx")]
+pub use onnx::CrossEncoderReranker;
+
+/// Reranker selectable with `rag query --rerank`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RerankKind {
+    /// Query-term overlap weighted by rarity (offline, no model)
+    Lexical,
+    /// Cohere/Jina-compatible `/rerank` endpoint
+    Http,
+    /// Local ONNX cross-encoder (requires the `onnx` feature)
+    CrossEncoder,
+}
+
+/// Second retrieval stage: rescores the first-stage candidates against the
+/// query with a (usually slower, more precise) model.
+pub trait Reranker {
+    fn name(&self) -> &'static str;
+
+    /// One relevance score per passage, higher is better. Scores only need
+    /// to be comparable within one call.
+    fn score(&self, query: &str, passages: &[&str]) -> Result<Vec<f32>>;
+}
+
+/// Rescore `candidates` with `reranker`, store the new scores in
+/// `rerank_score` and sort by them. First-stage scores are kept.
+pub fn rerank(
+    reranker: &dyn Reranker,
+    query: &str,
+    mut candidates: Vec<SearchResult>,
+) -> Result<Vec<SearchResult>> {
+    if candidates.is_empty() {
+        return Ok(candidates);
+    }
+    let passages: Vec<&str> = candidates.iter().map(|r| r.chunk.text.as_str()).collect();
+    let scores = reranker.score(query, &passages)?;
+    if scores.len() != candidates.len() {
+        return Err(anyhow!(
+            "reranker {} returned {} scores for {} passages",
+            reranker.name(),
+            scores.len(),
+            candidates.len()
+        ));
+    }
+    for (r, s) in candidates.iter_mut().zip(scores) {
+        r.rerank_score = Some(s);
+    }
+    candidates.sort_by(|a, b| {
+        b.relevance()
+            .partial_cmp(&a.relevance())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    Ok(candidates)
+}
+
+/// Offline reranker: the share of the query's content terms found in the
+/// passage, with terms weighted by how rare they are among the candidates.
+pub struct LexicalReranker;
+
+impl Reranker for LexicalReranker {
+    fn name(&self) -> &'static str {
+        "lexical"
+    }
+
+    fn score(&self, query: &str, passages: &[&str]) -> Result<Vec<f32>> {
+        let q_terms: HashSet<String> = content_terms(query).into_iter().collect();
+        let p_terms: Vec<HashSet<String>> = passages
+            .iter()
+            .map(|p| content_terms(p).into_iter().collect())
+            .collect();
+
+        let n = passages.len() as f32;
+        let weights: HashMap<&str, f32> = q_terms
+            .iter()
+            .map(|t| {
+                let df = p_terms.iter().filter(|p| p.contains(t)).count() as f32;
+                (t.as_str(), (1.0 + n / (1.0 + df)).ln())
+            })
+            .collect();
+        let total: f32 = weights.values().sum();
+        if total == 0.0 {
+            return Ok(vec![0.0; passages.len()]);
+        }
+
+        Ok(p_terms
+            .iter()
+            .map(|p| {
+                let matched: f32 = weights
+                    .iter()
+                    .filter(|(t, _)| p.contains(**t))
+                    .fold(0.0, |acc, (_, w)| acc + w);
+                matched / total
+            })
+            .collect())
+    }
+}
+
+/// Reranker behind a Cohere/Jina-compatible `POST {base_url}/rerank` endpoint.
+pub struct HttpReranker {
+    client: Client,
+    base_url: String,
+    model: String,
+    api_key: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct RerankRequest<'a> {
+    model: &'a str,
+    query: &'a str,
+    documents: &'a [&'a str],
+    top_n: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct RerankResponse {
+    results: Vec<RerankHit>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RerankHit {
+    index: usize,
+    relevance_score: f32,
+}
+
+impl HttpReranker {
+    pub fn new(base_url: String, model: String, api_key: Option<String>) -> Self {
+        Self {
+            client: Client::new(),
+            base_url,
+            model,
+            api_key,
+        }
+    }
+}
+
+impl Reranker for HttpReranker {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    fn score(&self, query: &str, passages: &[&str]) -> Result<Vec<f32>> {
+        let url = format!("{}/rerank", self.base_url.trim_end_matches('/'));
+        let body = RerankRequest {
+            model: &self.model,
+            query,
+            documents: passages,
+            top_n: passages.len(),
+        };
+        let mut req = self.client.post(url).json(&body);
+        if let Some(key) = &self.api_key {
+            req = req.bearer_auth(key);
+        }
+        let parsed: RerankResponse = req.send()?.error_for_status()?.json()?;
+
+        // Hits come back sorted by score; passages the service dropped
+        // rank below everything it returned.
+        let mut scores = vec![f32::MIN; passages.len()];
+        for hit in parsed.results {
+            let slot = scores
+                .get_mut(hit.index)
+                .ok_or_else(|| anyhow!("rerank result index {} out of range", hit.index))?;
+            *slot = hit.relevance_score;
+        }
+        Ok(scores)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Chunk;
+    use uuid::Uuid;
+
+    fn result(text: &str, score: f32) -> SearchResult {
+        SearchResult::new(Chunk::new(Uuid::new_v4(), 0, text, 0), "doc.md", score)
+    }
+
+    #[test]
+    fn lexical_reranker_favours_rare_query_terms() {
+        let scores = LexicalReranker
+            .score(
+                "rust borrow checker",
+                &[
+                    "Rust has a borrow checker.",
+                    "Rust is fast.",
+                    "Rust compiles slowly.",
+                ],
+            )
+            .unwrap();
+        assert!(scores[0] > 0.99);
+        assert!(scores[1] < 0.3 && scores[1] == scores[2]);
+    }
+
+    #[test]
+    fn rerank_sorts_by_new_score_and_keeps_first_stage_score() {
+        let candidates = vec![
+            result("Cargo is the package manager.", 0.9),
+            result("The borrow checker validates references.", 0.5),
+        ];
+        let reranked = rerank(&LexicalReranker, "what does the borrow checker do?", candidates)
+            .unwrap();
+        assert!(reranked[0].chunk.text.starts_with("The borrow checker"));
+        assert_eq!(reranked[0].score, 0.5);
+        assert_eq!(reranked[0].rerank_score, Some(1.0));
+        assert_eq!(reranked[1].rerank_score, Some(0.0));
+    }
+}
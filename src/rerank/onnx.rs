@@ -0,0 +1,109 @@
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use ort::session::{Session, SessionInputValue};
+use ort::value::Tensor;
+use tokenizers::{Tokenizer, TruncationParams};
+
+use super::Reranker;
+
+/// Longest query + passage pair fed to the model, in tokens.
+const MAX_TOKENS: usize = 512;
+
+/// Local cross-encoder (e.g. an ONNX export of
+/// `cross-encoder/ms-marco-MiniLM-L-6-v2`) scoring each query/passage pair
+/// jointly. The ONNX Runtime library is loaded at run time; point
+/// `ORT_DYLIB_PATH` at it if it is not on the library path.
+pub struct CrossEncoderReranker {
+    session: Mutex<Session>,
+    tokenizer: Tokenizer,
+}
+
+impl CrossEncoderReranker {
+    /// Load `model.onnx` and `tokenizer.json` from `dir`.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let mut tokenizer = Tokenizer::from_file(dir.join("tokenizer.json"))
+            .map_err(|e| anyhow!("loading {}: {}", dir.join("tokenizer.json").display(), e))?;
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: MAX_TOKENS,
+                ..TruncationParams::default()
+            }))
+            .map_err(|e| anyhow!("configuring tokenizer: {}", e))?;
+        let session = Session::builder()?.commit_from_file(dir.join("model.onnx"))?;
+        Ok(Self {
+            session: Mutex::new(session),
+            tokenizer,
+        })
+    }
+}
+
+impl Reranker for CrossEncoderReranker {
+    fn name(&self) -> &'static str {
+        "cross-encoder"
+    }
+
+    fn score(&self, query: &str, passages: &[&str]) -> Result<Vec<f32>> {
+        let pairs: Vec<(&str, &str)> = passages.iter().map(|p| (query, *p)).collect();
+        let encodings = self
+            .tokenizer
+            .encode_batch(pairs, true)
+            .map_err(|e| anyhow!("tokenizing: {}", e))?;
+
+        // Pad every pair to the longest one.
+        let rows = encodings.len();
+        let width = encodings
+            .iter()
+            .map(|e| e.get_ids().len())
+            .max()
+            .unwrap_or(0);
+        let mut ids = vec![0i64; rows * width];
+        let mut mask = vec![0i64; rows * width];
+        let mut types = vec![0i64; rows * width];
+        for (r, e) in encodings.iter().enumerate() {
+            for (c, ((id, m), t)) in e
+                .get_ids()
+                .iter()
+                .zip(e.get_attention_mask())
+                .zip(e.get_type_ids())
+                .enumerate()
+            {
+                ids[r * width + c] = *id as i64;
+                mask[r * width + c] = *m as i64;
+                types[r * width + c] = *t as i64;
+            }
+        }
+
+        let mut session = self
+            .session
+            .lock()
+            .map_err(|_| anyhow!("model lock poisoned"))?;
+        // Some exports drop `token_type_ids`; only feed what the model declares.
+        let mut inputs: Vec<(Cow<str>, SessionInputValue)> = Vec::new();
+        for input in &session.inputs {
+            let data = match input.name.as_str() {
+                "input_ids" => ids.clone(),
+                "attention_mask" => mask.clone(),
+                "token_type_ids" => types.clone(),
+                other => return Err(anyhow!("unexpected model input {:?}", other)),
+            };
+            inputs.push((
+                Cow::Owned(input.name.clone()),
+                Tensor::from_array(([rows, width], data))?.into(),
+            ));
+        }
+        let outputs = session.run(inputs)?;
+        let (shape, logits) = outputs[0].try_extract_tensor::<f32>()?;
+
+        // `[rows, 1]` relevance logits, or `[rows, 2]` where the last
+        // column is the "relevant" class.
+        let cols = if shape.len() == 2 {
+            shape[1] as usize
+        } else {
+            1
+        };
+        Ok((0..rows).map(|r| logits[r * cols + cols - 1]).collect())
+    }
+}
@@ -0,0 +1,182 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+use rayon::prelude::*;
+use wide::f32x8;
+
+/// Chunks scored per rayon task; small corpora stay on one thread.
+const PAR_MIN_LEN: usize = 1024;
+
+/// Scale `v` to unit length in place (zero vectors are left alone).
+pub fn normalize(v: &mut [f32]) {
+    let norm = dot(v, v).sqrt();
+    if norm > 0.0 {
+        for x in v.iter_mut() {
+            *x /= norm;
+        }
+    }
+}
+
+/// Dot product over the common prefix of `a` and `b`, eight lanes at a time.
+/// For unit vectors this is their cosine similarity.
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+    let len = a.len().min(b.len());
+    let (a, b) = (&a[..len], &b[..len]);
+    let mut acc = f32x8::ZERO;
+    let mut a_lanes = a.chunks_exact(8);
+    let mut b_lanes = b.chunks_exact(8);
+    for (x, y) in a_lanes.by_ref().zip(b_lanes.by_ref()) {
+        let x = f32x8::from(<[f32; 8]>::try_from(x).expect("8 lanes"));
+        let y = f32x8::from(<[f32; 8]>::try_from(y).expect("8 lanes"));
+        acc = x.mul_add(y, acc);
+    }
+    let tail: f32 = a_lanes
+        .remainder()
+        .iter()
+        .zip(b_lanes.remainder())
+        .map(|(x, y)| x * y)
+        .fold(0.0, |s, p| s + p);
+    acc.reduce_add() + tail
+}
+
+/// A score and the index of what it scores, ordered by score (ties: lower
+/// index first) so heaps and sorts agree.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scored {
+    pub score: f32,
+    pub index: usize,
+}
+
+impl Eq for Scored {}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Keeps the `k` best items pushed so far in a min-heap, so a scan costs
+/// O(n log k) instead of sorting all n scores.
+#[derive(Debug, Clone)]
+pub struct TopK {
+    k: usize,
+    heap: BinaryHeap<Reverse<Scored>>,
+}
+
+impl TopK {
+    pub fn new(k: usize) -> Self {
+        Self {
+            k,
+            heap: BinaryHeap::with_capacity(k.min(4096) + 1),
+        }
+    }
+
+    pub fn push(&mut self, index: usize, score: f32) {
+        let item = Scored { score, index };
+        if self.heap.len() < self.k {
+            self.heap.push(Reverse(item));
+        } else if self.heap.peek().is_some_and(|worst| item > worst.0) {
+            self.heap.pop();
+            self.heap.push(Reverse(item));
+        }
+    }
+
+    pub fn merge(mut self, other: TopK) -> TopK {
+        for Reverse(item) in other.heap {
+            self.push(item.index, item.score);
+        }
+        self
+    }
+
+    /// Best first.
+    pub fn into_sorted(self) -> Vec<Scored> {
+        let mut items: Vec<Scored> = self.heap.into_iter().map(|r| r.0).collect();
+        items.sort_by(|a, b| b.cmp(a));
+        items
+    }
+}
+
+/// The `k` vectors with the highest dot product with `query`, best first,
+/// scored in parallel. With unit-length vectors this is exact cosine top-k.
+pub fn top_k_dot<V>(query: &[f32], vectors: &[V], k: usize) -> Vec<Scored>
+where
+    V: AsRef<[f32]> + Sync,
+{
+    top_k_by(vectors.len(), k, |i| dot(query, vectors[i].as_ref()))
+}
+
+/// The `k` best of items `0..n` under `score`, best first, scored in
+/// parallel.
+pub fn top_k_by(n: usize, k: usize, score: impl Fn(usize) -> f32 + Sync) -> Vec<Scored> {
+    top_k_keyed(n, k, |i| i, score)
+}
+
+/// Like `top_k_by`, but each result's `index` is `key(i)`, which also breaks
+/// ties, so the outcome does not depend on the order items are scanned in.
+pub fn top_k_keyed(
+    n: usize,
+    k: usize,
+    key: impl Fn(usize) -> usize + Sync,
+    score: impl Fn(usize) -> f32 + Sync,
+) -> Vec<Scored> {
+    if k == 0 {
+        return Vec::new();
+    }
+    (0..n)
+        .into_par_iter()
+        .with_min_len(PAR_MIN_LEN)
+        .fold(
+            || TopK::new(k),
+            |mut top, i| {
+                top.push(key(i), score(i));
+                top
+            },
+        )
+        .reduce(|| TopK::new(k), TopK::merge)
+        .into_sorted()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dot_matches_scalar_for_any_length() {
+        for len in [0, 3, 8, 13, 64] {
+            let a: Vec<f32> = (0..len).map(|i| i as f32 * 0.5 - 3.0).collect();
+            let b: Vec<f32> = (0..len).map(|i| 1.0 - i as f32 * 0.25).collect();
+            let scalar: f32 = a.iter().zip(&b).map(|(x, y)| x * y).sum();
+            assert!((dot(&a, &b) - scalar).abs() < 1e-3, "len {}", len);
+        }
+        let mut v = vec![3.0, 4.0];
+        normalize(&mut v);
+        assert_eq!(v, vec![0.6, 0.8]);
+    }
+
+    #[test]
+    fn top_k_keeps_the_best_in_order() {
+        let vectors: Vec<Vec<f32>> = (0..5000).map(|i| vec![(i % 97) as f32, 1.0]).collect();
+        let top = top_k_dot(&[1.0, 0.0], &vectors, 3);
+        let scores: Vec<f32> = top.iter().map(|s| s.score).collect();
+        assert_eq!(scores, vec![96.0, 96.0, 96.0]);
+        assert_eq!(top[0].index, 96);
+        assert!(top[0].index < top[1].index && top[1].index < top[2].index);
+        assert_eq!(top_k_dot(&[1.0], &vectors, 0), vec![]);
+    }
+
+    #[test]
+    fn keyed_top_k_breaks_ties_by_key() {
+        let keys = [30, 10, 20];
+        let top = top_k_keyed(3, 2, |i| keys[i], |_| 1.0);
+        let picked: Vec<usize> = top.iter().map(|s| s.index).collect();
+        assert_eq!(picked, vec![10, 20]);
+    }
+}
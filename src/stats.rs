@@ -1,5 +1,6 @@
 use anyhow::Result;
 
+use crate::index::IndexStats;
 use crate::store::Store;
 
 /// Print corpus stats to stdout.
@@ -9,6 +10,7 @@ pub fn run_stats(store: &Store) -> Result<()> {
     println!("────────────────────────────");
     println!("Corpus Stats");
     println!("────────────────────────────");
+    println!("Collection  : {}", store.collection());
     println!("Documents   : {}", docs);
     println!("Chunks      : {}", chunks);
 
@@ -18,5 +20,25 @@ pub fn run_stats(store: &Store) -> Result<()> {
         println!("Last Ingest : (none)");
     }
 
+    let index = IndexStats::load(store)?;
+    match index.compression_ratio() {
+        Some(ratio) => println!(
+            "Index       : {} ({:.0} bytes/vector vs {} as f32, {:.1}x smaller)",
+            index.kind.name(),
+            index.code_bytes,
+            index.dim * 4,
+            ratio
+        ),
+        None => println!("Index       : {}", index.kind.name()),
+    }
+    if let (Some(lists), Some(drift)) = (index.ivf_lists, &index.drift) {
+        println!(
+            "IVF         : {} lists; since training {:.0}% chunk count change, {:+.0}% centroid distance",
+            lists,
+            drift.size_change() * 100.0,
+            drift.error_growth() * 100.0
+        );
+    }
+
     Ok(())
 }
@@ -1,15 +1,41 @@
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use chrono::{DateTime, Utc};
-use rusqlite::{params, Connection, Row};
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, Row};
 use serde_json;
 use uuid::Uuid;
 
-use crate::models::{Chunk, Document};
+use crate::filter::QueryFilter;
+use crate::index::{IndexKind, Quantizer, VectorIndex};
+use crate::models::{ChatMessage, Chunk, CollectionInfo, Document, DocumentMetadata, FieldValue};
+use crate::score::normalize;
+
+/// Rowids per `IN (...)` lookup, well under SQLite's parameter limit.
+const ROWID_BATCH: usize = 500;
+
+/// Collection used when none is named.
+pub const DEFAULT_COLLECTION: &str = "default";
+
+/// Documents of collection `?2` at path `?1`, below it as a directory, or
+/// inside an archive at it (`path!/...`).
+const UNDER_PATH: &str = r#"
+    collection = ?2
+    AND (path = ?1
+         OR substr(path, 1, length(?1) + 1) = ?1 || '/'
+         OR substr(path, 1, length(?1) + 2) = ?1 || '!/')
+"#;
 
 pub struct Store {
     conn: Connection,
+    /// Collection documents are read from and written to
+    collection: String,
+    /// Vector index of `collection`
+    index: VectorIndex,
 }
 
 impl Store {
@@ -18,8 +44,13 @@ impl Store {
             std::fs::create_dir_all(parent)?;
         }
         let conn = Connection::open(path)?;
-        let store = Self { conn };
+        let mut store = Self {
+            conn,
+            collection: DEFAULT_COLLECTION.to_string(),
+            index: VectorIndex::Flat,
+        };
         store.init_schema()?;
+        store.index = store.load_index()?;
         Ok(store)
     }
 
@@ -31,7 +62,8 @@ impl Store {
             CREATE TABLE IF NOT EXISTS documents (
                 id TEXT PRIMARY KEY,
                 path TEXT NOT NULL,
-                created_at TEXT NOT NULL
+                created_at TEXT NOT NULL,
+                encoding TEXT
             );
 
             CREATE TABLE IF NOT EXISTS chunks (
@@ -42,35 +74,305 @@ impl Store {
                 embedding TEXT NOT NULL,
                 start_char INTEGER,
                 end_char INTEGER,
+                page INTEGER,
+                FOREIGN KEY (doc_id) REFERENCES documents(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS document_tags (
+                doc_id TEXT NOT NULL,
+                tag TEXT NOT NULL,
+                PRIMARY KEY (doc_id, tag),
+                FOREIGN KEY (doc_id) REFERENCES documents(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS document_metadata (
+                doc_id TEXT NOT NULL,
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (doc_id, key, value),
+                FOREIGN KEY (doc_id) REFERENCES documents(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS collections (
+                name TEXT PRIMARY KEY,
+                embedder TEXT,
+                embedder_name TEXT,
+                index_kind TEXT NOT NULL DEFAULT 'flat',
+                index_model BLOB,
+                created_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS chat_sessions (
+                id TEXT PRIMARY KEY,
+                created_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS chat_messages (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id TEXT NOT NULL,
+                role TEXT NOT NULL,
+                content TEXT NOT NULL,
+                query TEXT,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (session_id) REFERENCES chat_sessions(id) ON DELETE CASCADE
+            );
+        "#,
+        )?;
+        // Databases created before paged-document support lack this column.
+        self.ensure_column("chunks", "page", "INTEGER")?;
+        self.ensure_column("documents", "encoding", "TEXT")?;
+        self.ensure_column("chunks", "unit_norm", "INTEGER NOT NULL DEFAULT 0")?;
+        self.ensure_column("chunks", "qcode", "BLOB")?;
+        self.ensure_column("chunks", "ivf_list", "INTEGER")?;
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_chunks_ivf_list ON chunks(ivf_list)",
+            [],
+        )?;
+        self.ensure_column(
+            "documents",
+            "collection",
+            &format!("TEXT NOT NULL DEFAULT '{}'", DEFAULT_COLLECTION),
+        )?;
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_documents_collection ON documents(collection)",
+            [],
+        )?;
+        self.ensure_collection(DEFAULT_COLLECTION)?;
+        self.migrate_index_meta()?;
+        self.migrate_metadata_key()?;
+        self.normalize_legacy_embeddings()?;
+        Ok(())
+    }
+
+    /// Databases from before collections kept one vector index in
+    /// `index_meta`; it becomes the default collection's.
+    fn migrate_index_meta(&self) -> Result<()> {
+        let exists: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'index_meta'",
+                [],
+                |r| r.get(0),
+            )
+            .optional()?;
+        if exists.is_none() {
+            return Ok(());
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            r#"
+            UPDATE collections SET
+                index_kind = COALESCE((SELECT value FROM index_meta WHERE key = 'kind'), index_kind),
+                index_model = (SELECT value FROM index_meta WHERE key = 'model')
+            WHERE name = ?1
+        "#,
+            params![DEFAULT_COLLECTION],
+        )?;
+        tx.execute("DROP TABLE index_meta", [])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// `document_metadata` used to allow one row per key; list fields now
+    /// store a row per item. Lists stored before are kept joined.
+    fn migrate_metadata_key(&self) -> Result<()> {
+        let value_in_key: i64 = self.conn.query_row(
+            "SELECT pk FROM pragma_table_info('document_metadata') WHERE name = 'value'",
+            [],
+            |r| r.get(0),
+        )?;
+        if value_in_key > 0 {
+            return Ok(());
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute_batch(
+            r#"
+            ALTER TABLE document_metadata RENAME TO document_metadata_old;
+            CREATE TABLE document_metadata (
+                doc_id TEXT NOT NULL,
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (doc_id, key, value),
                 FOREIGN KEY (doc_id) REFERENCES documents(id) ON DELETE CASCADE
             );
+            INSERT INTO document_metadata SELECT doc_id, key, value FROM document_metadata_old;
+            DROP TABLE document_metadata_old;
         "#,
         )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Embeddings are stored at unit length so queries score with a plain dot
+    /// product. Rows written before that was the case are rescaled once.
+    fn normalize_legacy_embeddings(&self) -> Result<()> {
+        let rows: Vec<(String, String)> = self
+            .conn
+            .prepare("SELECT id, embedding FROM chunks WHERE unit_norm = 0")?
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        for (id, emb_json) in rows {
+            let mut embedding: Vec<f32> = serde_json::from_str(&emb_json)?;
+            normalize(&mut embedding);
+            tx.execute(
+                "UPDATE chunks SET embedding = ?1, unit_norm = 1 WHERE id = ?2",
+                params![serde_json::to_string(&embedding)?, id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Add a column to an existing table if it is missing.
+    fn ensure_column(&self, table: &str, column: &str, decl: &str) -> Result<()> {
+        let mut stmt = self.conn.prepare(&format!("PRAGMA table_info({})", table))?;
+        let exists = stmt
+            .query_map([], |r| r.get::<_, String>("name"))?
+            .filter_map(|n| n.ok())
+            .any(|n| n == column);
+        if !exists {
+            self.conn.execute_batch(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {}",
+                table, column, decl
+            ))?;
+        }
         Ok(())
     }
 
     pub fn insert_document(&self, doc: &Document) -> Result<()> {
         self.conn.execute(
             r#"
-            INSERT INTO documents (id, path, created_at)
-            VALUES (?1, ?2, ?3)
+            INSERT INTO documents (id, path, created_at, encoding, collection)
+            VALUES (?1, ?2, ?3, ?4, ?5)
             ON CONFLICT(id) DO UPDATE SET path = excluded.path
         "#,
             params![
                 doc.id.to_string(),
                 doc.path,
                 doc.created_at.to_rfc3339(),
+                doc.encoding,
+                self.collection,
             ],
         )?;
+        self.add_document_tags(doc.id, &doc.metadata.tags)?;
+        self.set_document_fields(doc.id, &doc.metadata.fields)?;
         Ok(())
     }
 
+    pub fn add_document_tags(&self, doc_id: Uuid, tags: &[String]) -> Result<()> {
+        for tag in tags {
+            self.conn.execute(
+                "INSERT OR IGNORE INTO document_tags (doc_id, tag) VALUES (?1, ?2)",
+                params![doc_id.to_string(), tag],
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn remove_document_tags(&self, doc_id: Uuid, tags: &[String]) -> Result<()> {
+        for tag in tags {
+            self.conn.execute(
+                "DELETE FROM document_tags WHERE doc_id = ?1 AND tag = ?2",
+                params![doc_id.to_string(), tag],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Set metadata fields, replacing existing values of the same keys. List
+    /// items are stored one row each.
+    pub fn set_document_fields(&self, doc_id: Uuid, fields: &BTreeMap<String, FieldValue>) -> Result<()> {
+        let keys: Vec<String> = fields.keys().cloned().collect();
+        self.remove_document_fields(doc_id, &keys)?;
+        for (key, value) in fields {
+            for item in value.items() {
+                self.conn.execute(
+                    "INSERT OR IGNORE INTO document_metadata (doc_id, key, value) VALUES (?1, ?2, ?3)",
+                    params![doc_id.to_string(), key, item],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn remove_document_fields(&self, doc_id: Uuid, keys: &[String]) -> Result<()> {
+        for key in keys {
+            self.conn.execute(
+                "DELETE FROM document_metadata WHERE doc_id = ?1 AND key = ?2",
+                params![doc_id.to_string(), key],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Tags and fields of the given documents; documents without any are
+    /// missing from the map.
+    pub fn document_metadata(&self, doc_ids: &[Uuid]) -> Result<HashMap<Uuid, DocumentMetadata>> {
+        let mut out: HashMap<Uuid, DocumentMetadata> = HashMap::new();
+        let ids: Vec<String> = doc_ids.iter().map(|id| id.to_string()).collect();
+        for batch in ids.chunks(ROWID_BATCH) {
+            let marks = vec!["?"; batch.len()].join(", ");
+            let mut stmt = self.conn.prepare(&format!(
+                r#"
+                SELECT doc_id, tag, NULL, rowid FROM document_tags WHERE doc_id IN ({0})
+                UNION ALL
+                SELECT doc_id, key, value, rowid FROM document_metadata WHERE doc_id IN ({0})
+                ORDER BY 1, 2, 4
+            "#,
+                marks
+            ))?;
+            let mut rows = stmt.query(params_from_iter(batch.iter().chain(batch)))?;
+            while let Some(row) = rows.next()? {
+                let id = Uuid::parse_str(&row.get::<_, String>(0)?)?;
+                let entry = out.entry(id).or_default();
+                match row.get::<_, Option<String>>(2)? {
+                    Some(value) => match entry.fields.entry(row.get(1)?) {
+                        Entry::Vacant(e) => {
+                            e.insert(FieldValue::One(value));
+                        }
+                        Entry::Occupied(mut e) => {
+                            let mut items = e.get().items().to_vec();
+                            items.push(value);
+                            e.insert(FieldValue::List(items));
+                        }
+                    },
+                    None => entry.tags.push(row.get(1)?),
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Ids and paths of the collection's documents selected by `filter`.
+    pub fn documents_matching(&self, filter: &QueryFilter) -> Result<Vec<(Uuid, String)>> {
+        let (conditions, values) = self.filter_sql(filter)?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT d.id, d.path FROM documents d WHERE {} ORDER BY d.path",
+            conditions
+        ))?;
+        let mut rows = stmt.query(params_from_iter(values))?;
+        let mut out = Vec::new();
+        while let Some(row) = rows.next()? {
+            out.push((Uuid::parse_str(&row.get::<_, String>(0)?)?, row.get(1)?));
+        }
+        Ok(out)
+    }
+
+    /// Stores the chunk with its embedding scaled to unit length.
     pub fn insert_chunk(&self, chunk: &Chunk) -> Result<()> {
-        let emb_json = serde_json::to_string(&chunk.embedding)?;
+        let mut embedding = chunk.embedding.clone();
+        normalize(&mut embedding);
+        let emb_json = serde_json::to_string(&embedding)?;
+        let code = self.index.quantizer().map(|q| q.encode(&embedding)).transpose()?;
+        let list = self.index.ivf().map(|ivf| ivf.assign(&embedding) as i64);
         self.conn.execute(
             r#"
-            INSERT INTO chunks (id, doc_id, chunk_index, text, embedding, start_char, end_char)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            INSERT INTO chunks (id, doc_id, chunk_index, text, embedding, start_char, end_char, page, unit_norm, qcode, ivf_list)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, 1, ?9, ?10)
         "#,
             params![
                 chunk.id.to_string(),
@@ -79,12 +381,59 @@ impl Store {
                 chunk.text,
                 emb_json,
                 chunk.start_char,
-                chunk.end_char
+                chunk.end_char,
+                chunk.page,
+                code,
+                list
             ],
         )?;
         Ok(())
     }
 
+    /// Delete documents (and, via cascade, their chunks) of the collection
+    /// stored under `path`: the exact path, anything below it as a
+    /// directory, and members of an archive at that path (`path!/...`).
+    /// Returns how many were deleted.
+    pub fn delete_documents_by_path(&self, path: &str) -> Result<usize> {
+        let deleted = self.conn.execute(
+            &format!("DELETE FROM documents WHERE {}", UNDER_PATH),
+            params![path, self.collection],
+        )?;
+        Ok(deleted)
+    }
+
+    /// Ids and paths of the documents `delete_documents_by_path` would delete.
+    pub fn documents_by_path(&self, path: &str) -> Result<Vec<(Uuid, String)>> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!("SELECT id, path FROM documents WHERE {} ORDER BY path", UNDER_PATH))?;
+        let mut rows = stmt.query(params![path, self.collection])?;
+        let mut out = Vec::new();
+        while let Some(row) = rows.next()? {
+            out.push((Uuid::parse_str(&row.get::<_, String>(0)?)?, row.get(1)?));
+        }
+        Ok(out)
+    }
+
+    pub fn delete_documents(&self, ids: &[Uuid]) -> Result<usize> {
+        let mut deleted = 0;
+        for id in ids {
+            deleted += self
+                .conn
+                .execute("DELETE FROM documents WHERE id = ?1", params![id.to_string()])?;
+        }
+        Ok(deleted)
+    }
+
+    /// Run `f` in a transaction: its writes are committed if it succeeds
+    /// and rolled back if it fails.
+    pub fn in_transaction<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let tx = self.conn.unchecked_transaction()?;
+        let out = f()?;
+        tx.commit()?;
+        Ok(out)
+    }
+
     fn row_to_chunk(&self, row: &Row) -> Result<Chunk> {
         let id_str: String = row.get("id")?;
         let doc_id_str: String = row.get("doc_id")?;
@@ -99,21 +448,47 @@ impl Store {
             embedding,
             start_char: row.get("start_char")?,
             end_char: row.get("end_char")?,
+            page: row.get("page")?,
         })
     }
 
     pub fn all_chunks_with_paths(&self) -> Result<Vec<(Chunk, String)>> {
-        let mut stmt = self.conn.prepare(
+        self.chunks_with_paths(&QueryFilter::default())
+    }
+
+    /// Register what `filter.sql_where()` needs and return its conditions,
+    /// restricted to the collection.
+    fn filter_sql(&self, filter: &QueryFilter) -> Result<(String, Vec<Value>)> {
+        if let Some(globs) = filter.path_globs()? {
+            self.conn.create_scalar_function(
+                "rag_path_match",
+                1,
+                FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+                move |ctx| Ok(globs.is_match(ctx.get::<String>(0)?)),
+            )?;
+        }
+        let (conditions, mut values) = filter.sql_where();
+        values.insert(0, Value::Text(self.collection.clone()));
+        Ok((format!("d.collection = ? AND ({})", conditions), values))
+    }
+
+    /// Chunks of the documents selected by `filter`, with their paths.
+    #[allow(clippy::needless_borrow)]
+    pub fn chunks_with_paths(&self, filter: &QueryFilter) -> Result<Vec<(Chunk, String)>> {
+        let (conditions, values) = self.filter_sql(filter)?;
+        let mut stmt = self.conn.prepare(&format!(
             r#"
             SELECT
-                c.id, c.doc_id, c.chunk_index, c.text, c.embedding, c.start_char, c.end_char,
+                c.id, c.doc_id, c.chunk_index, c.text, c.embedding, c.start_char, c.end_char, c.page,
                 d.path AS doc_path
             FROM chunks c
             JOIN documents d ON c.doc_id = d.id
+            WHERE {}
         "#,
-        )?;
+            conditions
+        ))?;
 
-        let mut rows = stmt.query([])?;
+        let mut rows = stmt.query(params_from_iter(values))?;
         let mut out = Vec::new();
         while let Some(row) = rows.next()? {
             let chunk = self.row_to_chunk(&row)?;
@@ -123,19 +498,349 @@ impl Store {
         Ok(out)
     }
 
+    /// Stream the embeddings of the chunks selected by `filter` to
+    /// `on_batch` as `(rowids, embeddings)`, at most `batch_size` at a time,
+    /// without reading chunk text. Returns the number of chunks scanned.
+    /// Rowids are only stable until the store is next modified; pass them
+    /// to `chunks_by_rowid` right away.
+    pub fn scan_embeddings(
+        &self,
+        filter: &QueryFilter,
+        batch_size: usize,
+        on_batch: impl FnMut(&[i64], &[Vec<f32>]) -> Result<()>,
+    ) -> Result<usize> {
+        self.scan_rows(filter, None, "c.embedding", batch_size, decode_embedding(1), on_batch)
+    }
+
+    /// Like `scan_embeddings`, restricted to the given IVF lists (and
+    /// chunks not assigned to any list).
+    pub fn scan_embeddings_in_lists(
+        &self,
+        filter: &QueryFilter,
+        lists: &[usize],
+        batch_size: usize,
+        on_batch: impl FnMut(&[i64], &[Vec<f32>]) -> Result<()>,
+    ) -> Result<usize> {
+        self.scan_rows(
+            filter,
+            Some(lists),
+            "c.embedding",
+            batch_size,
+            decode_embedding(1),
+            on_batch,
+        )
+    }
+
+    /// Stream the text of the chunks selected by `filter` to `on_batch`,
+    /// without reading embeddings.
+    pub fn scan_texts(
+        &self,
+        filter: &QueryFilter,
+        batch_size: usize,
+        on_batch: impl FnMut(&[i64], &[String]) -> Result<()>,
+    ) -> Result<usize> {
+        self.scan_rows(filter, None, "c.text", batch_size, |row| Ok(row.get(1)?), on_batch)
+    }
+
+    /// Stream every chunk's IVF list (if assigned) and embedding.
+    pub fn scan_list_assignments(
+        &self,
+        batch_size: usize,
+        mut on_batch: impl FnMut(&[Option<usize>], &[Vec<f32>]) -> Result<()>,
+    ) -> Result<usize> {
+        let embedding = decode_embedding(2);
+        self.scan_rows(
+            &QueryFilter::default(),
+            None,
+            "c.ivf_list, c.embedding",
+            batch_size,
+            |row| Ok((row.get::<_, Option<i64>>(1)?.map(|l| l as usize), embedding(row)?)),
+            |_, rows: &[(Option<usize>, Vec<f32>)]| {
+                let (lists, embeddings): (Vec<_>, Vec<_>) = rows.iter().cloned().unzip();
+                on_batch(&lists, &embeddings)
+            },
+        )
+    }
+
+    /// Like `scan_embeddings`, but streams the quantized codes of the
+    /// current index. Chunks without a code yet are encoded on the fly.
+    pub fn scan_codes(
+        &self,
+        filter: &QueryFilter,
+        batch_size: usize,
+        on_batch: impl FnMut(&[i64], &[Vec<u8>]) -> Result<()>,
+    ) -> Result<usize> {
+        let quantizer = self
+            .index
+            .quantizer()
+            .ok_or_else(|| anyhow!("the vector index stores no codes"))?;
+        self.scan_rows(
+            filter,
+            None,
+            "c.qcode, CASE WHEN c.qcode IS NULL THEN c.embedding END",
+            batch_size,
+            |row| match row.get::<_, Option<Vec<u8>>>(1)? {
+                Some(code) => Ok(code),
+                None => quantizer.encode(&decode_embedding(2)(row)?),
+            },
+            on_batch,
+        )
+    }
+
+    /// Stream `SELECT c.rowid, <columns>` over the filtered chunks (only
+    /// those in `lists`, if given) in batches, decoding each row with
+    /// `decode`.
+    fn scan_rows<T>(
+        &self,
+        filter: &QueryFilter,
+        lists: Option<&[usize]>,
+        columns: &str,
+        batch_size: usize,
+        decode: impl Fn(&Row) -> Result<T>,
+        mut on_batch: impl FnMut(&[i64], &[T]) -> Result<()>,
+    ) -> Result<usize> {
+        let (mut conditions, mut values) = self.filter_sql(filter)?;
+        if let Some(lists) = lists {
+            conditions = format!(
+                "({}) AND (c.ivf_list IN ({}) OR c.ivf_list IS NULL)",
+                conditions,
+                vec!["?"; lists.len()].join(", ")
+            );
+            values.extend(lists.iter().map(|&l| Value::Integer(l as i64)));
+        }
+        let mut stmt = self.conn.prepare(&format!(
+            r#"
+            SELECT c.rowid, {}
+            FROM chunks c
+            JOIN documents d ON c.doc_id = d.id
+            WHERE {}
+        "#,
+            columns, conditions
+        ))?;
+
+        let mut rows = stmt.query(params_from_iter(values))?;
+        let mut rowids = Vec::with_capacity(batch_size);
+        let mut items = Vec::with_capacity(batch_size);
+        let mut scanned = 0;
+        while let Some(row) = rows.next()? {
+            rowids.push(row.get::<_, i64>(0)?);
+            items.push(decode(row)?);
+            if rowids.len() == batch_size {
+                on_batch(&rowids, &items)?;
+                scanned += rowids.len();
+                rowids.clear();
+                items.clear();
+            }
+        }
+        if !rowids.is_empty() {
+            on_batch(&rowids, &items)?;
+            scanned += rowids.len();
+        }
+        Ok(scanned)
+    }
+
+    /// Full-precision embeddings for rowids from a scan.
+    pub fn embeddings_by_rowid(&self, rowids: &[i64]) -> Result<HashMap<i64, Vec<f32>>> {
+        let mut out = HashMap::with_capacity(rowids.len());
+        for batch in rowids.chunks(ROWID_BATCH) {
+            let marks = vec!["?"; batch.len()].join(", ");
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT rowid, embedding FROM chunks WHERE rowid IN ({})",
+                marks
+            ))?;
+            let mut rows = stmt.query(params_from_iter(batch))?;
+            while let Some(row) = rows.next()? {
+                out.insert(row.get(0)?, serde_json::from_str(row.get_ref(1)?.as_str()?)?);
+            }
+        }
+        Ok(out)
+    }
+
+    pub fn vector_index(&self) -> &VectorIndex {
+        &self.index
+    }
+
+    pub fn quantizer(&self) -> Option<&Quantizer> {
+        self.index.quantizer()
+    }
+
+    fn load_index(&self) -> Result<VectorIndex> {
+        let (kind, model): (String, Option<Vec<u8>>) = self.conn.query_row(
+            "SELECT index_kind, index_model FROM collections WHERE name = ?1",
+            params![self.collection],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )?;
+        VectorIndex::from_meta(IndexKind::from_name(&kind)?, model.as_deref())
+    }
+
+    /// Switch the collection's vector index. Existing codes and list
+    /// assignments are dropped; new chunks are encoded or assigned as they
+    /// are inserted, existing ones by `write_codes` / `write_lists`.
+    pub fn set_index(&mut self, index: VectorIndex) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            r#"
+            UPDATE chunks SET qcode = NULL, ivf_list = NULL
+            WHERE doc_id IN (SELECT id FROM documents WHERE collection = ?1)
+        "#,
+            params![self.collection],
+        )?;
+        tx.execute(
+            "UPDATE collections SET index_kind = ?1, index_model = ?2 WHERE name = ?3",
+            params![index.kind().name(), index.model(), self.collection],
+        )?;
+        tx.commit()?;
+        self.index = index;
+        Ok(())
+    }
+
+    pub fn write_codes(&self, codes: &[(i64, Vec<u8>)]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for (rowid, code) in codes {
+            tx.execute(
+                "UPDATE chunks SET qcode = ?1 WHERE rowid = ?2",
+                params![code, rowid],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn write_lists(&self, lists: &[(i64, usize)]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for (rowid, list) in lists {
+            tx.execute(
+                "UPDATE chunks SET ivf_list = ?1 WHERE rowid = ?2",
+                params![*list as i64, rowid],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Embedding dimension, number of chunks with codes and their average
+    /// code size in bytes.
+    pub fn code_stats(&self) -> Result<(usize, usize, f64)> {
+        let dim: Option<i64> = self
+            .conn
+            .query_row(
+                r#"
+                SELECT json_array_length(c.embedding)
+                FROM chunks c JOIN documents d ON c.doc_id = d.id
+                WHERE d.collection = ?1 LIMIT 1
+            "#,
+                params![self.collection],
+                |r| r.get(0),
+            )
+            .optional()?;
+        let (coded, avg): (i64, Option<f64>) = self.conn.query_row(
+            r#"
+            SELECT COUNT(c.qcode), AVG(LENGTH(c.qcode))
+            FROM chunks c JOIN documents d ON c.doc_id = d.id
+            WHERE d.collection = ?1
+        "#,
+            params![self.collection],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )?;
+        Ok((dim.unwrap_or(0) as usize, coded as usize, avg.unwrap_or(0.0)))
+    }
+
+    /// Full chunks and document paths for rowids from `scan_embeddings`.
+    /// Rowids that no longer exist are missing from the map.
+    pub fn chunks_by_rowid(&self, rowids: &[i64]) -> Result<HashMap<i64, (Chunk, String)>> {
+        let mut out = HashMap::with_capacity(rowids.len());
+        for batch in rowids.chunks(ROWID_BATCH) {
+            let marks = vec!["?"; batch.len()].join(", ");
+            let mut stmt = self.conn.prepare(&format!(
+                r#"
+                SELECT
+                    c.rowid, c.id, c.doc_id, c.chunk_index, c.text, c.embedding, c.start_char,
+                    c.end_char, c.page, d.path AS doc_path
+                FROM chunks c
+                JOIN documents d ON c.doc_id = d.id
+                WHERE c.rowid IN ({})
+            "#,
+                marks
+            ))?;
+            let mut rows = stmt.query(params_from_iter(batch))?;
+            while let Some(row) = rows.next()? {
+                let chunk = self.row_to_chunk(row)?;
+                out.insert(row.get(0)?, (chunk, row.get("doc_path")?));
+            }
+        }
+        Ok(out)
+    }
+
+    pub fn create_chat_session(&self, id: Uuid) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO chat_sessions (id, created_at) VALUES (?1, ?2)",
+            params![id.to_string(), Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    pub fn chat_session_exists(&self, id: Uuid) -> Result<bool> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM chat_sessions WHERE id = ?1",
+            params![id.to_string()],
+            |r| r.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    pub fn insert_chat_message(&self, session_id: Uuid, message: &ChatMessage) -> Result<()> {
+        self.conn.execute(
+            r#"
+            INSERT INTO chat_messages (session_id, role, content, query, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+        "#,
+            params![
+                session_id.to_string(),
+                message.role,
+                message.content,
+                message.query,
+                Utc::now().to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Messages of a chat session in the order they were written.
+    pub fn chat_messages(&self, session_id: Uuid) -> Result<Vec<ChatMessage>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT role, content, query FROM chat_messages WHERE session_id = ?1 ORDER BY id",
+        )?;
+        let rows = stmt.query_map(params![session_id.to_string()], |r| {
+            Ok(ChatMessage {
+                role: r.get(0)?,
+                content: r.get(1)?,
+                query: r.get(2)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
     pub fn corpus_stats(&self) -> Result<(usize, usize, Option<DateTime<Utc>>)> {
         let doc_count: i64 = self
             .conn
-            .query_row("SELECT COUNT(*) FROM documents", [], |r| r.get(0))
+            .query_row(
+                "SELECT COUNT(*) FROM documents WHERE collection = ?1",
+                params![self.collection],
+                |r| r.get(0),
+            )
             .unwrap_or(0);
         let chunk_count: i64 = self
             .conn
-            .query_row("SELECT COUNT(*) FROM chunks", [], |r| r.get(0))
+            .query_row(
+                "SELECT COUNT(*) FROM chunks c JOIN documents d ON c.doc_id = d.id WHERE d.collection = ?1",
+                params![self.collection],
+                |r| r.get(0),
+            )
             .unwrap_or(0);
 
         let latest_created: Option<String> = self.conn.query_row(
-            "SELECT created_at FROM documents ORDER BY created_at DESC LIMIT 1",
-            [],
+            "SELECT created_at FROM documents WHERE collection = ?1 ORDER BY created_at DESC LIMIT 1",
+            params![self.collection],
             |r| r.get(0),
         ).optional()?;
 
@@ -147,6 +852,165 @@ impl Store {
 
         Ok((doc_count as usize, chunk_count as usize, latest_dt))
     }
+
+    pub fn collection(&self) -> &str {
+        &self.collection
+    }
+
+    /// Read and write `name` from now on; it must exist.
+    pub fn use_collection(&mut self, name: &str) -> Result<()> {
+        if !self.collection_exists(name)? {
+            bail!("no collection `{}`; create it with `rag collections create`", name);
+        }
+        self.collection = name.to_string();
+        self.index = self.load_index()?;
+        Ok(())
+    }
+
+    pub fn collection_exists(&self, name: &str) -> Result<bool> {
+        let found: Option<String> = self
+            .conn
+            .query_row("SELECT name FROM collections WHERE name = ?1", params![name], |r| r.get(0))
+            .optional()?;
+        Ok(found.is_some())
+    }
+
+    /// Create `name` if it does not exist yet.
+    pub fn ensure_collection(&self, name: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO collections (name, created_at) VALUES (?1, ?2)",
+            params![name, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Create `name`, embedding with `embedder` (an `EmbedderSpec`) if given.
+    pub fn create_collection(&self, name: &str, embedder: Option<&str>) -> Result<()> {
+        if name.is_empty() || name.contains(',') {
+            bail!("invalid collection name {:?}", name);
+        }
+        if self.collection_exists(name)? {
+            bail!("collection `{}` already exists", name);
+        }
+        self.conn.execute(
+            "INSERT INTO collections (name, embedder, created_at) VALUES (?1, ?2, ?3)",
+            params![name, embedder, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Delete `name` with its documents. Returns the number of documents
+    /// deleted. The default collection is emptied rather than removed.
+    pub fn drop_collection(&mut self, name: &str) -> Result<usize> {
+        if !self.collection_exists(name)? {
+            bail!("no collection `{}`", name);
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        let deleted = tx.execute("DELETE FROM documents WHERE collection = ?1", params![name])?;
+        if name == DEFAULT_COLLECTION {
+            tx.execute(
+                r#"
+                UPDATE collections
+                SET embedder = NULL, embedder_name = NULL, index_kind = 'flat', index_model = NULL
+                WHERE name = ?1
+            "#,
+                params![name],
+            )?;
+        } else {
+            tx.execute("DELETE FROM collections WHERE name = ?1", params![name])?;
+        }
+        tx.commit()?;
+        if self.collection == name {
+            self.use_collection(DEFAULT_COLLECTION)?;
+        }
+        Ok(deleted)
+    }
+
+    pub fn rename_collection(&mut self, from: &str, to: &str) -> Result<()> {
+        if from == DEFAULT_COLLECTION {
+            bail!("the default collection cannot be renamed");
+        }
+        if !self.collection_exists(from)? {
+            bail!("no collection `{}`", from);
+        }
+        if to.is_empty() || to.contains(',') || self.collection_exists(to)? {
+            bail!("cannot rename `{}` to {:?}: name invalid or taken", from, to);
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("UPDATE collections SET name = ?1 WHERE name = ?2", params![to, from])?;
+        tx.execute("UPDATE documents SET collection = ?1 WHERE collection = ?2", params![to, from])?;
+        tx.commit()?;
+        if self.collection == from {
+            self.collection = to.to_string();
+        }
+        Ok(())
+    }
+
+    /// Every collection with its settings and size, by name.
+    pub fn collections(&self) -> Result<Vec<CollectionInfo>> {
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT
+                k.name, k.embedder, k.embedder_name, k.index_kind, k.created_at,
+                (SELECT COUNT(*) FROM documents d WHERE d.collection = k.name) AS documents,
+                (SELECT COUNT(*) FROM chunks c JOIN documents d ON c.doc_id = d.id
+                 WHERE d.collection = k.name) AS chunks
+            FROM collections k
+            ORDER BY k.name
+        "#,
+        )?;
+        let mut rows = stmt.query([])?;
+        let mut out = Vec::new();
+        while let Some(row) = rows.next()? {
+            let created_at: String = row.get("created_at")?;
+            out.push(CollectionInfo {
+                name: row.get("name")?,
+                embedder: row.get("embedder")?,
+                embedder_name: row.get("embedder_name")?,
+                index_kind: IndexKind::from_name(&row.get::<_, String>("index_kind")?)?,
+                documents: row.get::<_, i64>("documents")? as usize,
+                chunks: row.get::<_, i64>("chunks")? as usize,
+                created_at: DateTime::parse_from_rfc3339(&created_at)?.with_timezone(&Utc),
+            });
+        }
+        Ok(out)
+    }
+
+    /// Settings of the current collection.
+    pub fn collection_info(&self) -> Result<CollectionInfo> {
+        self.collections()?
+            .into_iter()
+            .find(|c| c.name == self.collection)
+            .ok_or_else(|| anyhow!("no collection `{}`", self.collection))
+    }
+
+    /// Check that the embedder `spec` (`EmbedderSpec` syntax, e.g.
+    /// `local:256`) is the one the collection was embedded with; the first
+    /// check on an unmarked collection records it when `record` is set.
+    /// Vectors from different embedders do not compare.
+    pub fn check_embedder(&self, spec: &str, record: bool) -> Result<()> {
+        match self.collection_info()?.embedder_name {
+            Some(used) if used != spec => bail!(
+                "collection `{}` was embedded with {}, not {}",
+                self.collection,
+                used,
+                spec
+            ),
+            None if record => {
+                self.conn.execute(
+                    "UPDATE collections SET embedder_name = ?1 WHERE name = ?2",
+                    params![spec, self.collection],
+                )?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Decoder for a JSON embedding in column `idx` of a scan row.
+fn decode_embedding(idx: usize) -> impl Fn(&Row) -> Result<Vec<f32>> {
+    move |row| Ok(serde_json::from_str(row.get_ref(idx)?.as_str()?)?)
 }
 
 trait OptionalRow<T> {
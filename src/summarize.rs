@@ -0,0 +1,169 @@
+use std::collections::HashSet;
+use std::sync::atomic::AtomicBool;
+
+use anyhow::{anyhow, Result};
+
+use crate::embedder::Embedder;
+use crate::generate::{attach_citations, Generator};
+use crate::models::{Answer, SearchResult};
+use crate::query::{cosine_similarity, mmr_select};
+use crate::text::{content_terms, split_sentences};
+
+/// Offline answer synthesizer: picks the retrieved sentences that best
+/// answer the question and stitches them together with inline citations.
+///
+/// Sentences are scored by a blend of embedding similarity and keyword
+/// overlap with the question, then selected with maximal marginal
+/// relevance so near-duplicates from overlapping chunks are skipped.
+pub struct ExtractiveGenerator<'a> {
+    embedder: &'a dyn Embedder,
+    /// Maximum number of sentences in the answer
+    pub max_sentences: usize,
+    /// Weight of embedding similarity vs. keyword overlap (0..=1)
+    pub similarity_weight: f32,
+    /// MMR trade-off between relevance (1.0) and diversity (0.0)
+    pub lambda: f32,
+}
+
+/// A candidate sentence and the 1-based index of the passage it came from.
+struct Candidate {
+    text: String,
+    source: usize,
+}
+
+impl<'a> ExtractiveGenerator<'a> {
+    pub fn new(embedder: &'a dyn Embedder) -> Self {
+        Self {
+            embedder,
+            max_sentences: 4,
+            similarity_weight: 0.6,
+            lambda: 0.7,
+        }
+    }
+}
+
+impl Generator for ExtractiveGenerator<'_> {
+    fn name(&self) -> &'static str {
+        "extractive"
+    }
+
+    fn complete(&self, _system: &str, _user: &str) -> Result<String> {
+        Err(anyhow!(
+            "the extractive synthesizer cannot answer free-form prompts; configure --llm"
+        ))
+    }
+
+    fn generate_stream(
+        &self,
+        question: &str,
+        context: &[SearchResult],
+        on_token: &mut dyn FnMut(&str),
+        _cancel: &AtomicBool,
+    ) -> Result<Answer> {
+        let answer = self.generate(question, context)?;
+        on_token(&answer.text);
+        Ok(answer)
+    }
+
+    fn generate(&self, question: &str, context: &[SearchResult]) -> Result<Answer> {
+        let candidates = candidate_sentences(context);
+        if candidates.is_empty() {
+            return Ok(attach_citations(String::new(), &[]));
+        }
+
+        let mut texts = vec![question.to_string()];
+        texts.extend(candidates.iter().map(|c| c.text.clone()));
+        let vectors = self.embedder.embed(&texts)?;
+        let (q_vec, sent_vecs) = vectors.split_first().expect("question vector");
+
+        let q_terms: HashSet<String> = content_terms(question).into_iter().collect();
+        let relevance: Vec<f32> = candidates
+            .iter()
+            .zip(sent_vecs)
+            .map(|(c, v)| {
+                let sim = cosine_similarity(q_vec, v);
+                let overlap = keyword_overlap(&q_terms, &c.text);
+                self.similarity_weight * sim + (1.0 - self.similarity_weight) * overlap
+            })
+            .collect();
+
+        // Weak matches only pad the answer; keep sentences near the best one.
+        let best = relevance.iter().cloned().fold(0.0f32, f32::max);
+        let picked = mmr_select(&relevance, sent_vecs, self.lambda, self.max_sentences);
+        let text = picked
+            .iter()
+            .filter(|&&i| relevance[i] >= best * 0.5)
+            .map(|&i| format!("{} [{}]", candidates[i].text, candidates[i].source))
+            .collect::<Vec<_>>()
+            .join(" ");
+        Ok(attach_citations(text, context))
+    }
+}
+
+/// Sentences from all passages, skipping fragments and exact repeats
+/// (overlapping chunk windows share text).
+fn candidate_sentences(context: &[SearchResult]) -> Vec<Candidate> {
+    let mut seen = HashSet::new();
+    let mut out = Vec::new();
+    for (i, r) in context.iter().enumerate() {
+        for sentence in split_sentences(&r.chunk.text) {
+            if sentence.split_whitespace().count() < 4 {
+                continue;
+            }
+            if seen.insert(sentence.to_lowercase()) {
+                out.push(Candidate {
+                    text: sentence,
+                    source: i + 1,
+                });
+            }
+        }
+    }
+    out
+}
+
+/// Fraction of the question's content terms present in the sentence.
+fn keyword_overlap(q_terms: &HashSet<String>, sentence: &str) -> f32 {
+    if q_terms.is_empty() {
+        return 0.0;
+    }
+    let s_terms: HashSet<String> = content_terms(sentence).into_iter().collect();
+    q_terms.intersection(&s_terms).count() as f32 / q_terms.len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedder::LocalEmbedder;
+    use crate::models::Chunk;
+    use uuid::Uuid;
+
+    fn result(path: &str, text: &str) -> SearchResult {
+        SearchResult::new(Chunk::new(Uuid::new_v4(), 0, text, 0), path, 1.0)
+    }
+
+    #[test]
+    fn picks_relevant_sentences_with_citations_and_skips_duplicates() {
+        let embedder = LocalEmbedder::new(256);
+        let context = vec![
+            result(
+                "a.md",
+                "Logs rotate daily at midnight. The borrow checker enforces ownership rules at compile time.",
+            ),
+            result(
+                "b.md",
+                "The borrow checker enforces ownership rules at compile time. Cargo downloads crates from the registry.",
+            ),
+        ];
+
+        let mut gen = ExtractiveGenerator::new(&embedder);
+        gen.max_sentences = 1;
+        let answer = gen.generate("what does the borrow checker enforce?", &context).unwrap();
+
+        assert_eq!(
+            answer.text,
+            "The borrow checker enforces ownership rules at compile time. [1]"
+        );
+        assert_eq!(answer.citations.len(), 1);
+        assert_eq!(answer.citations[0].document_path, "a.md");
+    }
+}
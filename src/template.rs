@@ -0,0 +1,290 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::models::{Citation, SearchResult};
+
+const DEFAULT_SYSTEM: &str =
+    "You answer questions using only the numbered context passages provided. \
+Cite the passages that support each statement with their bracketed numbers, e.g. [1] or [2][3]. \
+If the context does not contain the answer, say that you don't know.";
+const DEFAULT_USER: &str = "Context:\n\n{context}\n\nQuestion: {question}\nAnswer:";
+const DEFAULT_CHUNK: &str = "[{index}] {source} ({span})\n{text}";
+const DEFAULT_CITATION: &str = "[{index}] {source} ({span})";
+
+const SYSTEM_VARS: &[&str] = &["question"];
+const USER_VARS: &[&str] = &["question", "context"];
+const CHUNK_VARS: &[&str] = &["index", "path", "source", "page", "span", "score", "text"];
+const CITATION_VARS: &[&str] = &["index", "path", "source", "page", "span"];
+
+/// A text template with `{variable}` placeholders, checked against the
+/// variables it may use when parsed. `{{` and `}}` are literal braces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Var(&'static str),
+}
+
+impl Template {
+    pub fn parse(source: &str, allowed: &[&'static str]) -> Result<Self> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = source.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => bail!("unclosed `{{{}` (use `{{{{` for a literal brace)", name),
+                        }
+                    }
+                    let var = allowed.iter().find(|v| **v == name.trim()).ok_or_else(|| {
+                        anyhow!(
+                            "unknown variable {{{}}} (allowed: {})",
+                            name,
+                            allowed
+                                .iter()
+                                .map(|v| format!("{{{}}}", v))
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        )
+                    })?;
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Var(var));
+                }
+                '}' => bail!("unmatched `}}` (use `}}}}` for a literal brace)"),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        Ok(Self { segments })
+    }
+
+    /// Fill in placeholders; `value` is only asked for variables that were
+    /// allowed at parse time.
+    pub fn render(&self, value: impl Fn(&str) -> String) -> String {
+        let mut out = String::new();
+        for s in &self.segments {
+            match s {
+                Segment::Literal(text) => out.push_str(text),
+                Segment::Var(name) => out.push_str(&value(name)),
+            }
+        }
+        out
+    }
+}
+
+/// Prompt and citation formats for answer generation.
+///
+/// A preset is a directory `<template dir>/<name>/` holding any of
+/// `system.txt`, `user.txt`, `chunk.txt` and `citation.txt`; missing files
+/// keep the built-in default.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptTemplate {
+    pub name: String,
+    /// System message; may use `{question}`
+    system: Template,
+    /// User message; `{context}` is the rendered chunks separated by blank lines
+    user: Template,
+    /// One context passage: `{index}`, `{path}`, `{source}` (path plus page),
+    /// `{page}`, `{span}`, `{score}`, `{text}`
+    chunk: Template,
+    /// One entry of the sources list: `{index}`, `{path}`, `{source}`, `{page}`, `{span}`
+    citation: Template,
+}
+
+impl Default for PromptTemplate {
+    fn default() -> Self {
+        Self {
+            name: "default".to_string(),
+            system: Template::parse(DEFAULT_SYSTEM, SYSTEM_VARS).expect("built-in template"),
+            user: Template::parse(DEFAULT_USER, USER_VARS).expect("built-in template"),
+            chunk: Template::parse(DEFAULT_CHUNK, CHUNK_VARS).expect("built-in template"),
+            citation: Template::parse(DEFAULT_CITATION, CITATION_VARS).expect("built-in template"),
+        }
+    }
+}
+
+impl PromptTemplate {
+    /// Load the preset `name` from `dir`. `default` is built in and only
+    /// read from disk if the directory has a `default/` preset.
+    pub fn load(dir: &Path, name: &str) -> Result<Self> {
+        let preset = dir.join(name);
+        if !preset.is_dir() {
+            if name == "default" {
+                return Ok(Self::default());
+            }
+            bail!(
+                "no prompt template {:?} in {} (available: {})",
+                name,
+                dir.display(),
+                available_presets(dir).join(", ")
+            );
+        }
+
+        let mut template = Self {
+            name: name.to_string(),
+            ..Self::default()
+        };
+        let parts = [
+            ("system.txt", SYSTEM_VARS, &mut template.system),
+            ("user.txt", USER_VARS, &mut template.user),
+            ("chunk.txt", CHUNK_VARS, &mut template.chunk),
+            ("citation.txt", CITATION_VARS, &mut template.citation),
+        ];
+        for (file, vars, slot) in parts {
+            let path = preset.join(file);
+            if !path.exists() {
+                continue;
+            }
+            let source =
+                fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+            *slot = Template::parse(source.trim_end(), vars)
+                .with_context(|| format!("invalid template {}", path.display()))?;
+        }
+        Ok(template)
+    }
+
+    /// Build the (system, user) messages for a grounded answer.
+    pub fn render_prompt(&self, question: &str, context: &[SearchResult]) -> (String, String) {
+        let chunks: Vec<String> = context
+            .iter()
+            .enumerate()
+            .map(|(i, r)| {
+                self.chunk.render(|var| match var {
+                    "index" => (i + 1).to_string(),
+                    "path" => r.document_path.clone(),
+                    "source" => r.source_label(),
+                    "page" => r.chunk.page.map(|p| p.to_string()).unwrap_or_default(),
+                    "span" => format!("{}..{}", r.chunk.start_char, r.chunk.end_char),
+                    "score" => format!("{:.4}", r.relevance()),
+                    _ => r.chunk.text.trim().to_string(),
+                })
+            })
+            .collect();
+        let context = chunks.join("\n\n");
+
+        let system = self.system.render(|_| question.to_string());
+        let user = self.user.render(|var| match var {
+            "context" => context.clone(),
+            _ => question.to_string(),
+        });
+        (system, user)
+    }
+
+    /// One line of the sources list printed under an answer.
+    pub fn render_citation(&self, c: &Citation) -> String {
+        self.citation.render(|var| match var {
+            "index" => c.index.to_string(),
+            "path" => c.document_path.clone(),
+            "source" => match c.page {
+                Some(page) => format!("{} p.{}", c.document_path, page),
+                None => c.document_path.clone(),
+            },
+            "page" => c.page.map(|p| p.to_string()).unwrap_or_default(),
+            _ => format!("{}..{}", c.start_char, c.end_char),
+        })
+    }
+}
+
+/// `$XDG_CONFIG_HOME/rag/templates`, falling back to `~/.config/rag/templates`.
+pub fn default_template_dir() -> PathBuf {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+        .unwrap_or_else(|| PathBuf::from("."));
+    config.join("rag").join("templates")
+}
+
+fn available_presets(dir: &Path) -> Vec<String> {
+    let mut names = vec!["default".to_string()];
+    if let Ok(entries) = fs::read_dir(dir) {
+        for e in entries.flatten() {
+            let name = e.file_name().to_string_lossy().into_owned();
+            if e.path().is_dir() && name != "default" {
+                names.push(name);
+            }
+        }
+    }
+    names.sort();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Chunk;
+    use uuid::Uuid;
+
+    fn result(path: &str, text: &str, page: Option<u32>) -> SearchResult {
+        let mut chunk = Chunk::new(Uuid::new_v4(), 0, text, 10);
+        chunk.end_char = 20;
+        chunk.page = page;
+        SearchResult::new(chunk, path, 0.5)
+    }
+
+    #[test]
+    fn rejects_unknown_variables_and_stray_braces() {
+        let err = Template::parse("{index} {title}", CITATION_VARS).unwrap_err();
+        assert!(err.to_string().contains("unknown variable {title}"));
+        assert!(Template::parse("{index", CITATION_VARS).is_err());
+        assert!(Template::parse("index}", CITATION_VARS).is_err());
+        let t = Template::parse("{{literal}} {index}", CITATION_VARS).unwrap();
+        assert_eq!(t.render(|_| "7".to_string()), "{literal} 7");
+    }
+
+    #[test]
+    fn loads_preset_files_over_defaults() {
+        let dir = std::env::temp_dir().join("rag_test_templates");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("terse")).unwrap();
+        fs::write(dir.join("terse/chunk.txt"), "<{path}#{page}> {text}\n").unwrap();
+        fs::write(dir.join("terse/citation.txt"), "{index}. {path}\n").unwrap();
+        fs::create_dir_all(dir.join("broken")).unwrap();
+        fs::write(dir.join("broken/user.txt"), "{question} {chunks}").unwrap();
+
+        let t = PromptTemplate::load(&dir, "terse").unwrap();
+        let context = vec![
+            result("a.pdf", " Alpha. ", Some(3)),
+            result("b.md", "Beta.", None),
+        ];
+        let (system, user) = t.render_prompt("why?", &context);
+        assert_eq!(system, DEFAULT_SYSTEM);
+        assert_eq!(
+            user,
+            "Context:\n\n<a.pdf#3> Alpha.\n\n<b.md#> Beta.\n\nQuestion: why?\nAnswer:"
+        );
+        let c = Citation::from_result(2, &context[1]);
+        assert_eq!(t.render_citation(&c), "2. b.md");
+
+        let err = PromptTemplate::load(&dir, "broken").unwrap_err();
+        assert!(format!("{:#}", err).contains("unknown variable {chunks}"));
+        assert!(PromptTemplate::load(&dir, "missing").is_err());
+        assert_eq!(
+            PromptTemplate::load(&dir, "default").unwrap(),
+            PromptTemplate::default()
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,112 @@
+/// Common English function words that carry no retrieval signal.
+const STOPWORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "can", "do", "does", "for", "from",
+    "how", "i", "if", "in", "into", "is", "it", "its", "of", "on", "or", "so", "that", "the",
+    "their", "then", "there", "these", "this", "to", "was", "what", "when", "where", "which",
+    "who", "why", "will", "with", "you", "your",
+];
+
+pub fn is_stopword(term: &str) -> bool {
+    STOPWORDS.contains(&term)
+}
+
+/// Lower-cased alphanumeric terms with stopwords and 1-char tokens removed.
+pub fn content_terms(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| t.chars().count() > 1)
+        .map(|t| t.to_lowercase())
+        .filter(|t| !is_stopword(t))
+        .collect()
+}
+
+/// Split text into sentences on `.`/`!`/`?` followed by whitespace, and on
+/// blank lines / Markdown headings. Common abbreviations do not end a sentence.
+pub fn split_sentences(text: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    for block in text.split("\n\n") {
+        for line_group in split_headings(block) {
+            split_block(&line_group, &mut out);
+        }
+    }
+    out
+}
+
+/// Headings (`# ...`) become their own sentence.
+fn split_headings(block: &str) -> Vec<String> {
+    let mut groups = Vec::new();
+    let mut current = String::new();
+    for line in block.lines() {
+        if line.trim_start().starts_with('#') {
+            if !current.trim().is_empty() {
+                groups.push(std::mem::take(&mut current));
+            }
+            groups.push(line.trim_start_matches(['#', ' ']).to_string());
+        } else {
+            current.push_str(line);
+            current.push(' ');
+        }
+    }
+    if !current.trim().is_empty() {
+        groups.push(current);
+    }
+    groups
+}
+
+fn split_block(block: &str, out: &mut Vec<String>) {
+    let chars: Vec<char> = block.chars().collect();
+    let mut start = 0;
+    for i in 0..chars.len() {
+        let ends = matches!(chars[i], '.' | '!' | '?')
+            && chars.get(i + 1).is_none_or(|c| c.is_whitespace())
+            && !(chars[i] == '.' && is_abbreviation(&chars[start..i]));
+        if ends {
+            push_sentence(&chars[start..=i], out);
+            start = i + 1;
+        }
+    }
+    if start < chars.len() {
+        push_sentence(&chars[start..], out);
+    }
+}
+
+fn is_abbreviation(before: &[char]) -> bool {
+    let word: String = before
+        .iter()
+        .rev()
+        .take_while(|c| !c.is_whitespace())
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .collect();
+    let word = word.to_lowercase();
+    word.chars().count() == 1 || matches!(word.as_str(), "e.g" | "i.e" | "etc" | "vs" | "mr" | "dr")
+}
+
+fn push_sentence(chars: &[char], out: &mut Vec<String>) {
+    let s: String = chars.iter().collect();
+    let s = s.split_whitespace().collect::<Vec<_>>().join(" ");
+    if !s.is_empty() {
+        out.push(s);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_sentences_headings_and_abbreviations() {
+        let text = "# Ownership\nRust uses ownership, e.g. moves. Is it safe?\nYes!\n\nTrailing fragment";
+        assert_eq!(
+            split_sentences(text),
+            vec![
+                "Ownership",
+                "Rust uses ownership, e.g. moves.",
+                "Is it safe?",
+                "Yes!",
+                "Trailing fragment"
+            ]
+        );
+        assert_eq!(content_terms("What is the Rust borrow-checker?"), vec!["rust", "borrow", "checker"]);
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use uuid::Uuid;
+
+use crate::embedder::Embedder;
+use crate::ingest::{collect_files, run_ingest_with, IngestOptions};
+use crate::models::DocumentMetadata;
+use crate::store::Store;
+
+/// Knobs for `rag watch`.
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    pub ingest: IngestOptions,
+    /// Quiet period after the last filesystem event before changes are applied
+    pub debounce: Duration,
+    /// Re-ingest everything under the watched directories before watching
+    pub initial_sync: bool,
+    /// Files never ingested, e.g. the database itself when it lives under a watched dir
+    pub ignore: Vec<PathBuf>,
+}
+
+/// What should happen to a path once the debounce window closes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Upsert,
+    Remove,
+}
+
+/// Coalesces bursts of filesystem events into one change per path;
+/// the most recent event for a path wins.
+#[derive(Debug, Default)]
+pub struct ChangeSet {
+    changes: BTreeMap<PathBuf, Change>,
+}
+
+impl ChangeSet {
+    pub fn record(&mut self, path: PathBuf, change: Change) {
+        self.changes.insert(path, change);
+    }
+
+    /// Fold a notify event into the set. Access events are ignored.
+    pub fn record_event(&mut self, event: Event) {
+        match event.kind {
+            EventKind::Create(_) => self.record_all(event.paths, Change::Upsert),
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                self.record_all(event.paths, Change::Remove)
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                let mut paths = event.paths.into_iter();
+                if let Some(from) = paths.next() {
+                    self.record(from, Change::Remove);
+                }
+                self.record_all(paths.collect(), Change::Upsert);
+            }
+            EventKind::Modify(_) => self.record_all(event.paths, Change::Upsert),
+            EventKind::Remove(_) => self.record_all(event.paths, Change::Remove),
+            EventKind::Access(_) | EventKind::Any | EventKind::Other => {}
+        }
+    }
+
+    fn record_all(&mut self, paths: Vec<PathBuf>, change: Change) {
+        for p in paths {
+            self.record(p, change);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn drain(&mut self) -> Vec<(PathBuf, Change)> {
+        std::mem::take(&mut self.changes).into_iter().collect()
+    }
+}
+
+/// Watch directories and keep the store in sync until the process is killed.
+pub fn run_watch(
+    store: &Store,
+    embedder: &dyn Embedder,
+    dirs: &[PathBuf],
+    opts: &WatchOptions,
+) -> Result<()> {
+    // notify reports absolute paths; map them back onto the user's spelling
+    // of each root so document paths match what `rag ingest` stores.
+    let mut roots = Vec::new();
+    for dir in dirs {
+        if !dir.is_dir() {
+            return Err(anyhow!("Not a directory: {}", dir.display()));
+        }
+        roots.push((dir.canonicalize()?, dir.clone()));
+    }
+    let ignore: Vec<PathBuf> = opts
+        .ignore
+        .iter()
+        .filter_map(|p| p.canonicalize().ok())
+        .collect();
+
+    if opts.initial_sync {
+        let mut initial = ChangeSet::default();
+        for dir in dirs {
+            initial.record(dir.clone(), Change::Upsert);
+        }
+        if let Err(e) = apply_changes(store, embedder, initial.drain(), &opts.ingest) {
+            println!("[watch] Initial sync failed: {:#}", e);
+        }
+    }
+
+    let (tx, rx) = channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    for (canonical, _) in &roots {
+        watcher.watch(canonical, RecursiveMode::Recursive)?;
+    }
+    println!(
+        "[watch] Watching {} director{} (debounce {} ms); Ctrl-C to stop",
+        roots.len(),
+        if roots.len() == 1 { "y" } else { "ies" },
+        opts.debounce.as_millis()
+    );
+
+    let mut pending = ChangeSet::default();
+    let mut last_event = Instant::now();
+    loop {
+        match rx.recv_timeout(opts.debounce) {
+            Ok(Ok(mut event)) => {
+                event.paths.retain(|p| !is_ignored(p, &ignore));
+                event.paths = event
+                    .paths
+                    .iter()
+                    .filter_map(|p| relativize(p, &roots))
+                    .collect();
+                pending.record_event(event);
+                last_event = Instant::now();
+            }
+            Ok(Err(e)) => println!("[watch] Watch error: {}", e),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+
+        // A failed batch leaves the store as it was; the next change to
+        // those files retries them.
+        if !pending.is_empty() && last_event.elapsed() >= opts.debounce {
+            if let Err(e) = apply_changes(store, embedder, pending.drain(), &opts.ingest) {
+                println!("[watch] Failed to apply changes: {:#}", e);
+            }
+        }
+    }
+}
+
+/// Apply coalesced changes: removed paths (and anything under them) are
+/// deleted from the store; upserted paths are re-ingested, replacing their
+/// old documents only once the new ones are stored. Tags and fields of a
+/// replaced document carry over to its successor unless re-ingesting set
+/// them anew. Everything happens in one transaction, so if ingesting fails
+/// the store is left unchanged, removals included.
+pub fn apply_changes(
+    store: &Store,
+    embedder: &dyn Embedder,
+    changes: Vec<(PathBuf, Change)>,
+    opts: &IngestOptions,
+) -> Result<()> {
+    let mut to_ingest = Vec::new();
+    let mut upserted = Vec::new();
+    let mut replaced = Vec::new();
+    let mut removed = Vec::new();
+    for (path, change) in changes {
+        let path_str = path.to_string_lossy();
+        if change == Change::Remove || !path.exists() {
+            removed.push(path);
+            continue;
+        }
+        replaced.extend(store.documents_by_path(&path_str)?);
+        upserted.push(path_str.to_string());
+        for file in collect_files(std::slice::from_ref(&path))? {
+            if !is_editor_temp(&file) {
+                to_ingest.push(file);
+            }
+        }
+    }
+    if removed.is_empty() && replaced.is_empty() && to_ingest.is_empty() {
+        return Ok(());
+    }
+
+    let replaced_ids: Vec<Uuid> = replaced.iter().map(|(id, _)| *id).collect();
+    let previous = store.document_metadata(&replaced_ids)?;
+    let carried: HashMap<String, DocumentMetadata> = replaced
+        .into_iter()
+        .filter_map(|(id, path)| Some((path, previous.get(&id)?.clone())))
+        .collect();
+
+    let removed_counts = store.in_transaction(|| {
+        let mut counts = Vec::new();
+        for path in &removed {
+            counts.push(store.delete_documents_by_path(&path.to_string_lossy())?);
+        }
+        if !to_ingest.is_empty() {
+            run_ingest_with(store, embedder, &to_ingest, opts)?;
+        }
+        store.delete_documents(&replaced_ids)?;
+        if !carried.is_empty() {
+            carry_metadata(store, &upserted, &carried)?;
+        }
+        Ok(counts)
+    })?;
+    for (path, count) in removed.iter().zip(removed_counts) {
+        if count > 0 {
+            println!("[watch] Removed {} ({} documents)", path.display(), count);
+        }
+    }
+    Ok(())
+}
+
+/// Give documents now stored under `roots` the tags and fields `carried`
+/// recorded for their path, keeping values ingest just set (e.g. edited
+/// front matter).
+fn carry_metadata(
+    store: &Store,
+    roots: &[String],
+    carried: &HashMap<String, DocumentMetadata>,
+) -> Result<()> {
+    let mut docs = Vec::new();
+    let mut seen = HashSet::new();
+    for root in roots {
+        for (id, path) in store.documents_by_path(root)? {
+            if carried.contains_key(&path) && seen.insert(id) {
+                docs.push((id, path));
+            }
+        }
+    }
+    let ids: Vec<Uuid> = docs.iter().map(|(id, _)| *id).collect();
+    let current = store.document_metadata(&ids)?;
+    for (id, path) in &docs {
+        let old = &carried[path];
+        let new = current.get(id).cloned().unwrap_or_default();
+        store.add_document_tags(*id, &old.tags)?;
+        let fields = old
+            .fields
+            .iter()
+            .filter(|(key, _)| !new.fields.contains_key(*key))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        store.set_document_fields(*id, &fields)?;
+    }
+    Ok(())
+}
+
+fn relativize(path: &Path, roots: &[(PathBuf, PathBuf)]) -> Option<PathBuf> {
+    roots.iter().find_map(|(canonical, given)| {
+        path.strip_prefix(canonical).ok().map(|rest| given.join(rest))
+    })
+}
+
+fn is_ignored(path: &Path, ignore: &[PathBuf]) -> bool {
+    ignore.iter().any(|i| {
+        // SQLite sidecar files (`-journal`, `-wal`, `-shm`) share the db prefix.
+        path.starts_with(i) || path.to_string_lossy().starts_with(&*i.to_string_lossy())
+    })
+}
+
+/// Swap/backup files editors write next to the real file.
+fn is_editor_temp(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    name.starts_with(".#")
+        || name.ends_with('~')
+        || name.ends_with(".swp")
+        || name.ends_with(".swx")
+        || name.ends_with(".tmp")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, DataChange, RemoveKind};
+
+    fn event(kind: EventKind, paths: &[&str]) -> Event {
+        let mut e = Event::new(kind);
+        for p in paths {
+            e = e.add_path(PathBuf::from(p));
+        }
+        e
+    }
+
+    #[test]
+    fn bursts_coalesce_to_latest_change_per_path() {
+        let mut set = ChangeSet::default();
+        set.record_event(event(EventKind::Create(CreateKind::File), &["a.md"]));
+        set.record_event(event(
+            EventKind::Modify(ModifyKind::Data(DataChange::Content)),
+            &["a.md"],
+        ));
+        set.record_event(event(EventKind::Create(CreateKind::File), &["b.md"]));
+        set.record_event(event(EventKind::Remove(RemoveKind::File), &["b.md"]));
+        set.record_event(event(
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+            &["c.md", "d.md"],
+        ));
+
+        assert_eq!(
+            set.drain(),
+            vec![
+                (PathBuf::from("a.md"), Change::Upsert),
+                (PathBuf::from("b.md"), Change::Remove),
+                (PathBuf::from("c.md"), Change::Remove),
+                (PathBuf::from("d.md"), Change::Upsert),
+            ]
+        );
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn editor_temp_files_are_skipped() {
+        assert!(is_editor_temp(Path::new("docs/.notes.md.swp")));
+        assert!(is_editor_temp(Path::new("docs/notes.md~")));
+        assert!(!is_editor_temp(Path::new("docs/notes.md")));
+    }
+}
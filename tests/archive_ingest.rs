@@ -0,0 +1,28 @@
+use std::fs;
+
+use anyhow::Result;
+
+use tapssp_project::embedder::LocalEmbedder;
+use tapssp_project::ingest::run_ingest;
+use tapssp_project::query::run_query;
+use tapssp_project::store::Store;
+
+#[test]
+fn corrupt_archives_are_skipped_and_the_rest_ingested() -> Result<()> {
+    let tmp_dir = std::env::temp_dir().join("rag_test_corrupt_archive");
+    let _ = fs::remove_dir_all(&tmp_dir);
+    fs::create_dir_all(tmp_dir.join("docs"))?;
+    fs::write(tmp_dir.join("docs/broken.zip"), b"PK\x03\x04 not really a zip")?;
+    fs::write(tmp_dir.join("docs/broken.tar.gz"), b"not gzip either")?;
+    fs::write(tmp_dir.join("docs/notes.md"), "Cargo builds Rust crates.")?;
+
+    let store = Store::new(tmp_dir.join("rag.db"))?;
+    let embedder = LocalEmbedder::new(64);
+    run_ingest(&store, &embedder, &[tmp_dir.join("docs")], 64, 16)?;
+    let results = run_query(&store, &embedder, "Cargo", 5)?;
+    assert_eq!(results.len(), 1);
+    assert!(results[0].document_path.ends_with("notes.md"));
+
+    let _ = fs::remove_dir_all(&tmp_dir);
+    Ok(())
+}
@@ -8,6 +8,7 @@ use tapssp_project::query::run_query;
 use tapssp_project::store::Store;
 
 #[test]
+#[allow(clippy::cloned_ref_to_slice_refs)]
 fn basic_end_to_end_flow() -> Result<()> {
     let tmp_dir = std::env::temp_dir();
     let db_path = tmp_dir.join("rag_test.db");
@@ -31,3 +32,37 @@ fn basic_end_to_end_flow() -> Result<()> {
     let _ = fs::remove_file(corpus_path);
     Ok(())
 }
+
+#[test]
+fn ingests_archive_entries_without_extracting() -> Result<()> {
+    let tmp_dir = std::env::temp_dir();
+    let db_path = tmp_dir.join("rag_test_archive.db");
+    let archive_path = tmp_dir.join("rag_test_bundle.tar.gz");
+    let _ = fs::remove_file(&db_path);
+
+    let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+        fs::File::create(&archive_path)?,
+        flate2::Compression::default(),
+    ));
+    let body = b"Cargo is the Rust package manager and build tool.";
+    let mut header = tar::Header::new_gnu();
+    header.set_size(body.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, "docs/cargo.md", &body[..])?;
+    builder.into_inner()?.finish()?;
+
+    let store = Store::new(&db_path)?;
+    let embedder = LocalEmbedder::new(64);
+    run_ingest(&store, &embedder, std::slice::from_ref(&archive_path), 64, 16)?;
+
+    let results = run_query(&store, &embedder, "Rust package manager", 1)?;
+    assert_eq!(
+        results[0].document_path,
+        format!("{}!/docs/cargo.md", archive_path.display())
+    );
+
+    let _ = fs::remove_file(db_path);
+    let _ = fs::remove_file(archive_path);
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use std::fs;
+
+use anyhow::Result;
+
+use tapssp_project::embedder::{Embedder, LocalEmbedder};
+use tapssp_project::index::{build_index, evaluate_recall, BuildOptions, IndexKind, IndexStats};
+use tapssp_project::ingest::run_ingest;
+use tapssp_project::query::{cosine_similarity, run_query_with, QueryOptions};
+use tapssp_project::store::Store;
+
+#[test]
+fn binary_index_prefilters_by_hamming_and_rescores_exactly() -> Result<()> {
+    let tmp_dir = std::env::temp_dir().join("rag_test_binary_index");
+    let _ = fs::remove_dir_all(&tmp_dir);
+    fs::create_dir_all(&tmp_dir)?;
+    let db_path = tmp_dir.join("rag.db");
+    for i in 0..40 {
+        let text = format!("topic{} note{} shared words here", i % 7, i);
+        fs::write(tmp_dir.join(format!("{}.md", i)), text)?;
+    }
+
+    let embedder = LocalEmbedder::new(64);
+    let mut store = Store::new(&db_path)?;
+    run_ingest(&store, &embedder, std::slice::from_ref(&tmp_dir), 512, 64)?;
+    let flat = run_query_with(&store, &embedder, "topic3 note10", &QueryOptions::default())?;
+
+    let opts = BuildOptions {
+        kind: IndexKind::Binary,
+        ..BuildOptions::default()
+    };
+    assert_eq!(build_index(&mut store, &opts)?, 40);
+    let stats = IndexStats::load(&store)?;
+    assert_eq!(
+        (stats.code_bytes, stats.compression_ratio()),
+        (8.0, Some(32.0))
+    );
+
+    // Rescoring every candidate recovers exact search.
+    let all = QueryOptions {
+        rescore: Some(40),
+        ..QueryOptions::default()
+    };
+    assert_eq!(evaluate_recall(&store, 10, 5, &all)?.recall, 1.0);
+    let hits = run_query_with(&store, &embedder, "topic3 note10", &all)?;
+    let ids: Vec<_> = hits.iter().map(|h| h.chunk.id).collect();
+    assert_eq!(ids, flat.iter().map(|h| h.chunk.id).collect::<Vec<_>>());
+
+    // The default rescoring pass ranks by exact cosine.
+    let hits = run_query_with(&store, &embedder, "topic3 note10", &QueryOptions::default())?;
+    let q = embedder.embed(&["topic3 note10".to_string()])?.remove(0);
+    let cosines: Vec<f32> = hits
+        .iter()
+        .map(|h| cosine_similarity(&q, &h.chunk.embedding))
+        .collect();
+    assert!(cosines.windows(2).all(|w| w[0] >= w[1]));
+    assert!(hits[0].document_path.ends_with("10.md"));
+
+    let _ = fs::remove_dir_all(&tmp_dir);
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use std::fs;
+
+use anyhow::Result;
+
+use tapssp_project::chat::{ChatOptions, ChatSession};
+use tapssp_project::embedder::LocalEmbedder;
+use tapssp_project::filter::QueryFilter;
+use tapssp_project::ingest::run_ingest;
+use tapssp_project::store::Store;
+use tapssp_project::summarize::ExtractiveGenerator;
+
+#[test]
+fn chat_rewrites_follow_ups_filters_and_resumes() -> Result<()> {
+    let tmp_dir = std::env::temp_dir().join("rag_test_chat");
+    let _ = fs::remove_dir_all(&tmp_dir);
+    fs::create_dir_all(tmp_dir.join("docs"))?;
+    fs::create_dir_all(tmp_dir.join("notes"))?;
+    let db_path = tmp_dir.join("rag.db");
+    fs::write(
+        tmp_dir.join("docs/borrow.md"),
+        "The borrow checker enforces ownership rules at compile time. \
+         It rejects code that keeps a reference alive after the value moves.",
+    )?;
+    fs::write(
+        tmp_dir.join("notes/borrow.md"),
+        "Notes on the borrow checker and how ownership rules are enforced by the compiler.",
+    )?;
+
+    let store = Store::new(&db_path)?;
+    let embedder = LocalEmbedder::new(256);
+    run_ingest(&store, &embedder, std::slice::from_ref(&tmp_dir), 512, 64)?;
+    let generator = ExtractiveGenerator::new(&embedder);
+
+    let mut chat = ChatSession::start(&store, &embedder, &generator, None, ChatOptions::default())?;
+    chat.ask("What does the borrow checker enforce?")?;
+    assert_eq!(
+        chat.standalone_query("why does it reject code?")?,
+        "why does it reject code? (borrow checker enforce)"
+    );
+
+    chat.set_filter(QueryFilter::parse("path:**/docs/**")?);
+    chat.ask("why does it reject code?")?;
+    assert!(!chat.last_context().is_empty());
+    assert!(chat
+        .last_context()
+        .iter()
+        .all(|r| r.document_path.contains("docs/")));
+
+    let id = chat.id;
+    let resumed = ChatSession::resume(
+        &store,
+        &embedder,
+        &generator,
+        None,
+        ChatOptions::default(),
+        id,
+    )?;
+    let history = resumed.history();
+    assert_eq!(history.len(), 4);
+    assert_eq!(history[2].content, "why does it reject code?");
+    assert_eq!(
+        history[2].query.as_deref(),
+        Some("why does it reject code? (borrow checker enforce)")
+    );
+    assert!(resumed
+        .transcript()
+        .contains("**You:** What does the borrow checker enforce?"));
+
+    let _ = fs::remove_dir_all(&tmp_dir);
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use std::fs;
+
+use anyhow::{bail, Result};
+
+use tapssp_project::collections::{query_collections, resolve_collections};
+use tapssp_project::embedder::{Embedder, LocalEmbedder};
+use tapssp_project::index::{build_index, BuildOptions, IndexKind};
+use tapssp_project::ingest::run_ingest;
+use tapssp_project::query::{run_query_with, QueryOptions};
+use tapssp_project::store::{Store, DEFAULT_COLLECTION};
+
+#[test]
+fn collections_isolate_documents_and_merge_on_query() -> Result<()> {
+    let tmp_dir = std::env::temp_dir().join("rag_test_collections");
+    let _ = fs::remove_dir_all(&tmp_dir);
+    fs::create_dir_all(tmp_dir.join("code"))?;
+    fs::create_dir_all(tmp_dir.join("food"))?;
+    let db_path = tmp_dir.join("rag.db");
+    fs::write(
+        tmp_dir.join("code/borrow.md"),
+        "rust borrow checker and lifetimes",
+    )?;
+    fs::write(
+        tmp_dir.join("food/flour.md"),
+        "borrow a cup of flour for the bread",
+    )?;
+
+    let mut store = Store::new(&db_path)?;
+    let wide = LocalEmbedder::new(128);
+    let narrow = LocalEmbedder::new(32);
+    run_ingest(&store, &wide, &[tmp_dir.join("code")], 512, 64)?;
+    store.create_collection("food", Some("local:32"))?;
+    assert!(store.create_collection("food", None).is_err());
+    store.use_collection("food")?;
+    store.check_embedder("local:32", true)?;
+    run_ingest(&store, &narrow, &[tmp_dir.join("food")], 512, 64)?;
+    build_index(
+        &mut store,
+        &BuildOptions {
+            kind: IndexKind::Int8,
+            ..BuildOptions::default()
+        },
+    )?;
+
+    // Each collection sees only its own documents, and keeps its own index.
+    let hits = run_query_with(&store, &narrow, "borrow", &QueryOptions::default())?;
+    assert_eq!(hits.len(), 1);
+    assert_eq!(
+        (hits[0].collection.as_str(), store.corpus_stats()?.0),
+        ("food", 1)
+    );
+    store.use_collection(DEFAULT_COLLECTION)?;
+    assert_eq!(store.vector_index().kind(), IndexKind::Flat);
+    let hits = run_query_with(&store, &wide, "borrow", &QueryOptions::default())?;
+    assert!(hits.len() == 1 && hits[0].document_path.ends_with("borrow.md"));
+
+    // A query across collections embeds with each one's embedder.
+    let names = resolve_collections(&store, &["all".to_string()])?;
+    assert_eq!(names, ["default", "food"]);
+    let merged = query_collections(
+        &mut store,
+        &names,
+        "borrow",
+        &QueryOptions::default(),
+        |s| {
+            let dim = if s.collection() == "food" { 32 } else { 128 };
+            Ok(Box::new(LocalEmbedder::new(dim)) as Box<dyn Embedder>)
+        },
+    )?;
+    let mut found: Vec<&str> = merged.iter().map(|r| r.collection.as_str()).collect();
+    found.sort();
+    assert_eq!(found, ["default", "food"]);
+    assert!(merged[0].score >= merged[1].score);
+    assert_eq!(store.collection(), DEFAULT_COLLECTION);
+
+    // A collection that fails still leaves the store where it was.
+    let failed = query_collections(
+        &mut store,
+        &names,
+        "borrow",
+        &QueryOptions::default(),
+        |s| match s.collection() {
+            "food" => bail!("no embedder for food"),
+            _ => Ok(Box::new(LocalEmbedder::new(128)) as Box<dyn Embedder>),
+        },
+    );
+    assert!(failed.is_err());
+    assert_eq!(store.collection(), DEFAULT_COLLECTION);
+
+    // Renames carry documents and settings; drops delete the documents.
+    store.rename_collection("food", "recipes")?;
+    store.use_collection("recipes")?;
+    assert!(store.check_embedder("local:64", false).is_err());
+    assert_eq!(store.vector_index().kind(), IndexKind::Int8);
+    assert_eq!(store.drop_collection("recipes")?, 1);
+    assert_eq!(store.collection(), DEFAULT_COLLECTION);
+    assert!(resolve_collections(&store, &["recipes".to_string()]).is_err());
+    assert_eq!(store.collections()?.len(), 1);
+
+    let _ = fs::remove_dir_all(&tmp_dir);
+    Ok(())
+}
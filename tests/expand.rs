@@ -0,0 +1,80 @@
+use std::fs;
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use tapssp_project::embedder::LocalEmbedder;
+use tapssp_project::expand::{QueryExpansion, SynonymMap};
+use tapssp_project::generate::Generator;
+use tapssp_project::ingest::run_ingest;
+use tapssp_project::query::{run_query_with, QueryOptions};
+use tapssp_project::store::Store;
+
+/// Answers every prompt with a fixed passage.
+struct CannedGenerator(&'static str);
+
+impl Generator for CannedGenerator {
+    fn name(&self) -> &'static str {
+        "canned"
+    }
+
+    fn complete(&self, _system: &str, _user: &str) -> Result<String> {
+        Ok(self.0.to_string())
+    }
+}
+
+#[test]
+fn expanded_queries_find_what_the_short_question_misses() -> Result<()> {
+    let tmp_dir = std::env::temp_dir().join("rag_test_expand");
+    let _ = fs::remove_dir_all(&tmp_dir);
+    fs::create_dir_all(&tmp_dir)?;
+    let db_path = tmp_dir.join("rag.db");
+    for (name, text) in [
+        (
+            "cluster.md",
+            "A kubernetes cluster schedules containers across nodes.",
+        ),
+        ("cargo.md", "Cargo downloads crates and builds packages."),
+        (
+            "ownership.md",
+            "Ownership rules decide when memory gets freed.",
+        ),
+    ] {
+        fs::write(tmp_dir.join(name), text)?;
+    }
+
+    let store = Store::new(&db_path)?;
+    let embedder = LocalEmbedder::new(256);
+    run_ingest(&store, &embedder, std::slice::from_ref(&tmp_dir), 512, 64)?;
+
+    let plain = run_query_with(&store, &embedder, "what is k8s?", &QueryOptions::default())?;
+    assert!(plain.iter().all(|r| r.fused_score.is_none()));
+
+    let opts = QueryOptions {
+        expansion: QueryExpansion {
+            synonyms: Some(SynonymMap::builtin()),
+            ..QueryExpansion::default()
+        },
+        ..QueryOptions::default()
+    };
+    let results = run_query_with(&store, &embedder, "what is k8s?", &opts)?;
+    assert!(results[0].document_path.ends_with("cluster.md"));
+    assert!(results.iter().all(|r| r.fused_score.is_some()));
+    assert!(results[0].fused_score.unwrap() <= 1.0);
+
+    let opts = QueryOptions {
+        expansion: QueryExpansion {
+            generator: Some(Arc::new(CannedGenerator(
+                "Memory is freed when its owner goes out of scope, following ownership rules.",
+            ))),
+            hyde: true,
+            ..QueryExpansion::default()
+        },
+        ..QueryOptions::default()
+    };
+    let results = run_query_with(&store, &embedder, "when does Rust release memory?", &opts)?;
+    assert!(results[0].document_path.ends_with("ownership.md"));
+
+    let _ = fs::remove_dir_all(&tmp_dir);
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use std::fs;
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use tapssp_project::embedder::LocalEmbedder;
+use tapssp_project::expand::{QueryExpansion, SynonymMap};
+use tapssp_project::explain::explain;
+use tapssp_project::filter::QueryFilter;
+use tapssp_project::ingest::run_ingest;
+use tapssp_project::output::QueryReport;
+use tapssp_project::query::{run_query_traced, QueryOptions};
+use tapssp_project::rerank::LexicalReranker;
+use tapssp_project::store::Store;
+
+#[test]
+fn explain_breaks_down_every_stage() -> Result<()> {
+    let tmp_dir = std::env::temp_dir().join("rag_test_explain");
+    let _ = fs::remove_dir_all(&tmp_dir);
+    fs::create_dir_all(&tmp_dir)?;
+    let db_path = tmp_dir.join("rag.db");
+    for (name, text) in [
+        (
+            "borrow.md",
+            "the borrow checker rejects dangling references",
+        ),
+        ("cargo.md", "the cargo tool builds packages"),
+        ("notes.txt", "the borrow checker is strict"),
+    ] {
+        fs::write(tmp_dir.join(name), text)?;
+    }
+
+    let store = Store::new(&db_path)?;
+    let embedder = LocalEmbedder::new(256);
+    run_ingest(&store, &embedder, std::slice::from_ref(&tmp_dir), 512, 64)?;
+
+    let question = "what does the borrow checker reject";
+    let opts = QueryOptions {
+        top_k: 2,
+        filter: QueryFilter::parse("ext:md")?,
+        expansion: QueryExpansion {
+            synonyms: Some(SynonymMap::builtin()),
+            prf_docs: Some(1),
+            ..QueryExpansion::default()
+        },
+        reranker: Some(Arc::new(LexicalReranker)),
+        ..QueryOptions::default()
+    };
+    let (results, trace) = run_query_traced(&store, &embedder, question, &opts)?;
+    let explanation = explain(&store, &embedder, question, &opts, &results, &trace)?;
+
+    assert_eq!(explanation.filter, "ext:md");
+    assert_eq!(explanation.scored, 2);
+    assert_eq!(explanation.reranker, Some("lexical"));
+    assert_eq!(explanation.variants.last().unwrap().kind, "prf");
+    assert_eq!(explanation.hits.len(), results.len());
+
+    let top = &explanation.hits[0];
+    assert_eq!(top.chunk_id, results[0].chunk.id);
+    assert_eq!(top.matched_terms, vec!["borrow", "checker"]);
+    assert!(top.bm25 > explanation.hits[1].bm25);
+    assert_eq!(top.score, results[0].score);
+    assert!((explanation.calibration.apply(top.cosine) - top.score).abs() < 1e-6);
+    assert_eq!(top.variant_ranks.len(), explanation.variants.len());
+    assert!(top.first_stage_rank.is_some());
+    let overlap = top.token_overlap.as_ref().unwrap();
+    assert!(overlap.iter().any(|o| o.query_tokens == ["borrow"]));
+
+    let json = QueryReport::new(question, &results, None)
+        .with_explanation(&explanation)
+        .to_json()?;
+    assert!(json.contains("\"matched_terms\""));
+
+    let _ = fs::remove_dir_all(&tmp_dir);
+    Ok(())
+}
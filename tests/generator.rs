@@ -0,0 +1,160 @@
+mod common;
+
+use std::io::Read;
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use common::mock_server;
+use tapssp_project::generate::{Generator, GeneratorConfig, OllamaGenerator, OpenAIGenerator};
+use tapssp_project::models::{Chunk, SearchResult};
+use tapssp_project::template::PromptTemplate;
+
+fn result(path: &str, text: &str, start: i32) -> SearchResult {
+    SearchResult::new(Chunk::new(Uuid::new_v4(), 0, text, start), path, 0.9)
+}
+
+fn config(base_url: String) -> GeneratorConfig {
+    GeneratorConfig {
+        base_url,
+        model: "test-model".to_string(),
+        temperature: 0.0,
+        max_tokens: 64,
+        api_key: Some("sk-test".to_string()),
+        template: PromptTemplate::default(),
+        timeout: Some(Duration::from_secs(10)),
+    }
+}
+
+#[test]
+fn openai_generator_grounds_prompt_and_keeps_cited_sources() -> Result<()> {
+    let (url, server) = mock_server(
+        "application/json",
+        r#"{"choices":[{"message":{"role":"assistant","content":"Rust is memory safe [2]."}}]}"#
+            .to_string(),
+    );
+    let context = vec![
+        result("docs/intro.md", "Cargo builds crates.", 0),
+        result("docs/safety.md", "Rust guarantees memory safety.", 100),
+    ];
+
+    let answer = OpenAIGenerator::new(config(format!("{}/v1", url)))?
+        .generate("Is Rust safe?", &context)?;
+    let request = server.join().unwrap();
+
+    assert!(request.starts_with("POST /v1/chat/completions"));
+    assert!(request.to_ascii_lowercase().contains("authorization: bearer sk-test"));
+    assert!(request.contains(r#""model":"test-model""#));
+    assert!(request.contains("[2] docs/safety.md (100..130)"));
+    assert_eq!(answer.text, "Rust is memory safe [2].");
+    assert_eq!(answer.citations.len(), 1);
+    assert_eq!(answer.citations[0].label(), "[2] docs/safety.md (100..130)");
+    Ok(())
+}
+
+#[test]
+fn ollama_generator_uses_chat_api() -> Result<()> {
+    let (url, server) = mock_server(
+        "application/json",
+        r#"{"model":"test-model","message":{"role":"assistant","content":"Use cargo [1]."},"done":true}"#
+            .to_string(),
+    );
+    let context = vec![result("docs/intro.md", "Cargo builds crates.", 0)];
+
+    let answer = OllamaGenerator::new(config(url))?.generate("How do I build?", &context)?;
+    let request = server.join().unwrap();
+
+    assert!(request.starts_with("POST /api/chat"));
+    assert!(request.contains(r#""stream":false"#));
+    assert!(request.contains(r#""num_predict":64"#));
+    assert_eq!(answer.text, "Use cargo [1].");
+    assert_eq!(answer.citations[0].document_path, "docs/intro.md");
+    Ok(())
+}
+
+#[test]
+fn openai_stream_emits_tokens_until_done() -> Result<()> {
+    let body = concat!(
+        ": keep-alive\n\n",
+        "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n",
+        "data: {\"choices\":[{\"delta\":{\"content\":\"Rust is \"}}]}\n\n",
+        "data: {\"choices\":[{\"delta\":{\"content\":\"safe [1].\"}}]}\n\n",
+        "data: [DONE]\n\n",
+        "data: {\"choices\":[{\"delta\":{\"content\":\"ignored\"}}]}\n\n",
+    );
+    let (url, server) = mock_server("text/event-stream", body.to_string());
+    let context = vec![result("docs/safety.md", "Rust guarantees memory safety.", 0)];
+
+    let mut tokens = Vec::new();
+    let answer = OpenAIGenerator::new(config(url))?.generate_stream(
+        "Is Rust safe?",
+        &context,
+        &mut |t| tokens.push(t.to_string()),
+        &AtomicBool::new(false),
+    )?;
+    let request = server.join().unwrap();
+
+    assert!(request.contains(r#""stream":true"#));
+    assert_eq!(tokens, vec!["Rust is ", "safe [1]."]);
+    assert_eq!(answer.text, "Rust is safe [1].");
+    assert_eq!(answer.citations[0].document_path, "docs/safety.md");
+    Ok(())
+}
+
+#[test]
+fn ollama_stream_stops_when_cancelled() -> Result<()> {
+    let body = concat!(
+        "{\"message\":{\"role\":\"assistant\",\"content\":\"Use \"},\"done\":false}\n",
+        "{\"message\":{\"role\":\"assistant\",\"content\":\"cargo\"},\"done\":false}\n",
+        "{\"done\":true}\n",
+    );
+    let (url, server) = mock_server("application/x-ndjson", body.to_string());
+    let context = vec![result("docs/intro.md", "Cargo builds crates.", 0)];
+
+    let cancel = AtomicBool::new(false);
+    let answer = OllamaGenerator::new(config(url))?.generate_stream(
+        "How do I build?",
+        &context,
+        &mut |_| cancel.store(true, Ordering::SeqCst),
+        &cancel,
+    )?;
+    server.join().unwrap();
+
+    assert_eq!(answer.text, "Use");
+    Ok(())
+}
+
+#[test]
+fn stream_cancels_while_waiting_for_the_first_token() -> Result<()> {
+    // Accepts the request but never answers.
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}", listener.local_addr()?);
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let _ = stream.read_to_end(&mut Vec::new());
+    });
+    let context = vec![result("docs/intro.md", "Cargo builds crates.", 0)];
+
+    let cancel = AtomicBool::new(false);
+    let started = Instant::now();
+    let answer = thread::scope(|s| {
+        s.spawn(|| {
+            thread::sleep(Duration::from_millis(200));
+            cancel.store(true, Ordering::SeqCst);
+        });
+        OllamaGenerator::new(config(url))?.generate_stream(
+            "How do I build?",
+            &context,
+            &mut |_| {},
+            &cancel,
+        )
+    })?;
+
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(answer.text, "");
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use std::fs;
+
+use anyhow::Result;
+
+use tapssp_project::embedder::LocalEmbedder;
+use tapssp_project::index::{
+    build_index, evaluate_recall, ivf_drift, retrain_ivf, BuildOptions, IndexKind,
+};
+use tapssp_project::ingest::run_ingest;
+use tapssp_project::query::{run_query_traced, QueryOptions};
+use tapssp_project::store::Store;
+
+const WORDS: &[&str] = &[
+    "rust",
+    "cargo",
+    "borrow",
+    "checker",
+    "lifetime",
+    "trait",
+    "tokio",
+    "async",
+    "sqlite",
+    "index",
+    "vector",
+    "embedding",
+    "query",
+    "rerank",
+    "chunk",
+    "token",
+    "model",
+    "cosine",
+    "heap",
+    "scan",
+];
+
+fn write_docs(dir: &std::path::Path, range: std::ops::Range<usize>) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    for i in range {
+        // A unique token per doc avoids tied scores between duplicates.
+        let mut text: Vec<String> = (0..5)
+            .map(|j| WORDS[(i * 3 + j * j * 7 + i / 4) % WORDS.len()].to_string())
+            .collect();
+        text.push(format!("note{}", i));
+        fs::write(dir.join(format!("{}.md", i)), text.join(" "))?;
+    }
+    Ok(())
+}
+
+#[test]
+fn ivf_scans_probed_lists_and_retrains_on_drift() -> Result<()> {
+    let tmp_dir = std::env::temp_dir().join("rag_test_ivf_index");
+    let _ = fs::remove_dir_all(&tmp_dir);
+    write_docs(&tmp_dir.join("a"), 0..60)?;
+    let db_path = tmp_dir.join("rag.db");
+
+    let embedder = LocalEmbedder::new(64);
+    let mut store = Store::new(&db_path)?;
+    run_ingest(&store, &embedder, &[tmp_dir.join("a")], 512, 64)?;
+    let opts = BuildOptions {
+        kind: IndexKind::Ivf,
+        lists: Some(6),
+        nprobe: 6,
+        ..BuildOptions::default()
+    };
+    assert_eq!(build_index(&mut store, &opts)?, 60);
+
+    // Probing every list is exact; one list scans a fraction of the corpus.
+    drop(store);
+    let mut store = Store::new(&db_path)?;
+    assert_eq!(store.vector_index().ivf().map(|ivf| ivf.lists()), Some(6));
+    let all = evaluate_recall(&store, 20, 5, &QueryOptions::default())?;
+    assert_eq!((all.recall, all.scanned), (1.0, 60));
+    let one = QueryOptions {
+        nprobe: Some(1),
+        ..QueryOptions::default()
+    };
+    assert!(evaluate_recall(&store, 20, 5, &one)?.scanned < 60);
+    let (hits, trace) = run_query_traced(&store, &embedder, "tokio async", &one)?;
+    assert!(!hits.is_empty() && trace.scored < 60);
+
+    // New chunks are assigned on insert; enough of them triggers retraining.
+    assert!(!ivf_drift(&store)?.unwrap().drifted(0.2));
+    write_docs(&tmp_dir.join("b"), 60..90)?;
+    run_ingest(&store, &embedder, &[tmp_dir.join("b")], 512, 64)?;
+    let all = evaluate_recall(&store, 20, 5, &QueryOptions::default())?;
+    assert_eq!((all.recall, all.scanned), (1.0, 90));
+    let (drift, retrained) = retrain_ivf(&mut store, 0.2, false)?;
+    assert!(retrained && drift.size_change() > 0.4);
+    assert_eq!(ivf_drift(&store)?.unwrap().trained_chunks, 90);
+    assert!(!retrain_ivf(&mut store, 0.2, false)?.1);
+
+    let _ = fs::remove_dir_all(&tmp_dir);
+    Ok(())
+}
@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use anyhow::Result;
+use chrono::Utc;
+use uuid::Uuid;
+
+use tapssp_project::cli::DocsCommand;
+use tapssp_project::docs::run_docs;
+use tapssp_project::embedder::LocalEmbedder;
+use tapssp_project::filter::QueryFilter;
+use tapssp_project::ingest::{run_ingest_with, IngestOptions};
+use tapssp_project::models::{Document, DocumentMetadata, FieldValue};
+use tapssp_project::query::{run_query_with, QueryOptions};
+use tapssp_project::store::Store;
+
+#[test]
+fn metadata_from_front_matter_and_flags_is_stored_filtered_and_edited() -> Result<()> {
+    let tmp_dir = std::env::temp_dir().join("rag_test_metadata");
+    let _ = fs::remove_dir_all(&tmp_dir);
+    fs::create_dir_all(tmp_dir.join("notes"))?;
+    let db_path = tmp_dir.join("rag.db");
+    let text = "Ownership and borrowing keep memory safe.";
+    fs::write(
+        tmp_dir.join("notes/a.md"),
+        format!(
+            "---\ntitle: Ownership\nstatus: draft\ntags: [rust]\nauthors: [ann, bo]\n---\n{}",
+            text
+        ),
+    )?;
+    fs::write(tmp_dir.join("notes/b.txt"), text)?;
+
+    let store = Store::new(&db_path)?;
+    let embedder = LocalEmbedder::new(128);
+    let opts = IngestOptions {
+        tags: vec!["team-a".to_string()],
+        fields: BTreeMap::from([("status".to_string(), "review".to_string())]),
+        ..IngestOptions::default()
+    };
+    run_ingest_with(&store, &embedder, &[tmp_dir.join("notes")], &opts)?;
+
+    // Front matter is metadata, not text; flags add to it and win on conflicts.
+    let search = |filter: &str| -> Result<Vec<_>> {
+        let opts = QueryOptions {
+            filter: QueryFilter::parse(filter)?,
+            ..QueryOptions::default()
+        };
+        run_query_with(&store, &embedder, "ownership borrowing", &opts)
+    };
+    let hits = search("tag:rust")?;
+    assert_eq!(hits.len(), 1);
+    assert!(!hits[0].chunk.text.contains("title:"));
+    assert_eq!(hits[0].metadata.tags, ["rust", "team-a"]);
+    assert_eq!(hits[0].metadata.fields["title"].to_string(), "Ownership");
+    assert_eq!(hits[0].metadata.fields["status"].to_string(), "review");
+    assert_eq!(
+        hits[0].metadata.fields["authors"],
+        FieldValue::List(vec!["ann".to_string(), "bo".to_string()])
+    );
+    // List items match one at a time.
+    assert_eq!(search("meta:authors=bo")?.len(), 1);
+    let joined = QueryFilter {
+        meta: vec![("authors".to_string(), "ann, bo".to_string())],
+        ..QueryFilter::default()
+    };
+    assert!(store.documents_matching(&joined)?.is_empty());
+    assert_eq!(search("meta:status=review")?.len(), 2);
+    assert_eq!(search("meta:title=Ownership meta:status=draft")?.len(), 0);
+    assert_eq!(search("meta:title=Ownership meta:title=Other")?.len(), 1);
+
+    // Later edits apply to the selected documents only.
+    let selected = store.documents_matching(&QueryFilter::parse("path:**/b.txt")?)?;
+    assert_eq!(selected.len(), 1);
+    let id = selected[0].0;
+    store.add_document_tags(id, &["archived".to_string()])?;
+    store.remove_document_tags(id, &["team-a".to_string()])?;
+    store.set_document_fields(
+        id,
+        &BTreeMap::from([("status".to_string(), FieldValue::One("done".to_string()))]),
+    )?;
+    let hits = search("meta:status=done")?;
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].metadata.tags, ["archived"]);
+    store.remove_document_fields(id, &["status".to_string()])?;
+    assert!(search("meta:status=done")?.is_empty());
+
+    let _ = fs::remove_dir_all(&tmp_dir);
+    Ok(())
+}
+
+#[test]
+fn single_value_metadata_tables_are_migrated() -> Result<()> {
+    let tmp_dir = std::env::temp_dir().join("rag_test_metadata_migration");
+    let _ = fs::remove_dir_all(&tmp_dir);
+    fs::create_dir_all(&tmp_dir)?;
+    let db_path = tmp_dir.join("rag.db");
+
+    // A database from when each key held one row.
+    let store = Store::new(&db_path)?;
+    drop(store);
+    let conn = rusqlite::Connection::open(&db_path)?;
+    conn.execute_batch(
+        "DROP TABLE document_metadata;
+         CREATE TABLE document_metadata (doc_id TEXT NOT NULL, key TEXT NOT NULL, value TEXT NOT NULL,
+             PRIMARY KEY (doc_id, key));
+         INSERT INTO documents (id, path, created_at) VALUES
+             ('00000000-0000-0000-0000-000000000001', 'a.md', '2026-01-01T00:00:00+00:00');
+         INSERT INTO document_metadata VALUES ('00000000-0000-0000-0000-000000000001', 'status', 'draft');",
+    )?;
+    drop(conn);
+
+    let store = Store::new(&db_path)?;
+    let id = store.documents_matching(&QueryFilter::parse("meta:status=draft")?)?[0].0;
+    store.set_document_fields(
+        id,
+        &BTreeMap::from([(
+            "authors".to_string(),
+            FieldValue::List(vec!["ann".to_string(), "bo".to_string()]),
+        )]),
+    )?;
+    assert_eq!(
+        store
+            .documents_matching(&QueryFilter::parse("meta:authors=bo")?)?
+            .len(),
+        1
+    );
+
+    let _ = fs::remove_dir_all(&tmp_dir);
+    Ok(())
+}
+
+#[test]
+fn docs_tag_resolves_each_doc_prefix_to_one_document() -> Result<()> {
+    let tmp_dir = std::env::temp_dir().join("rag_test_docs_tag_prefix");
+    let _ = fs::remove_dir_all(&tmp_dir);
+    let store = Store::new(tmp_dir.join("rag.db"))?;
+    let ids = [
+        "aaaa0000-0000-4000-8000-000000000001",
+        "aaaa1111-0000-4000-8000-000000000002",
+    ];
+    for (n, id) in ids.iter().enumerate() {
+        store.insert_document(&Document {
+            id: Uuid::parse_str(id)?,
+            path: format!("notes/{}.md", n),
+            created_at: Utc::now(),
+            encoding: None,
+            metadata: DocumentMetadata::default(),
+        })?;
+    }
+    let tag = |doc: &str| {
+        run_docs(
+            &store,
+            &DocsCommand::Tag {
+                paths: Vec::new(),
+                doc_ids: vec![doc.to_string()],
+                add: vec!["archived".to_string()],
+                remove: Vec::new(),
+                meta: Vec::new(),
+                unset: Vec::new(),
+            },
+        )
+    };
+
+    // Empty and ambiguous prefixes change nothing; the error names the candidates.
+    assert!(tag("").is_err());
+    let err = tag("aaaa").unwrap_err().to_string();
+    assert!(ids.iter().all(|id| err.contains(id)));
+    assert!(tag("bbbb").is_err());
+    assert!(store
+        .documents_matching(&QueryFilter::parse("tag:archived")?)?
+        .is_empty());
+
+    tag("AAAA1")?;
+    let tagged = store.documents_matching(&QueryFilter::parse("tag:archived")?)?;
+    assert_eq!(tagged.len(), 1);
+    assert_eq!(tagged[0].0.to_string(), ids[1]);
+
+    let _ = fs::remove_dir_all(&tmp_dir);
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use std::fs;
+
+use anyhow::Result;
+use chrono::Utc;
+use uuid::Uuid;
+
+use tapssp_project::embedder::{Embedder, LocalEmbedder};
+use tapssp_project::models::{Chunk, Document, DocumentMetadata};
+use tapssp_project::query::run_query;
+use tapssp_project::store::Store;
+
+#[test]
+fn chunk_pages_round_trip_into_source_labels() -> Result<()> {
+    let tmp_dir = std::env::temp_dir().join("rag_test_paged_documents");
+    let _ = fs::remove_dir_all(&tmp_dir);
+    let store = Store::new(tmp_dir.join("rag.db"))?;
+    let embedder = LocalEmbedder::new(64);
+
+    let doc = Document {
+        id: Uuid::new_v4(),
+        path: "manual.pdf".to_string(),
+        created_at: Utc::now(),
+        encoding: None,
+        metadata: DocumentMetadata::default(),
+    };
+    store.insert_document(&doc)?;
+    let text = "Cargo builds and tests Rust crates.".to_string();
+    store.insert_chunk(&Chunk {
+        id: Uuid::new_v4(),
+        doc_id: doc.id,
+        chunk_index: 0,
+        embedding: embedder.embed(std::slice::from_ref(&text))?.remove(0),
+        text,
+        start_char: 0,
+        end_char: 35,
+        page: Some(12),
+    })?;
+
+    let results = run_query(&store, &embedder, "Cargo crates", 1)?;
+    assert_eq!(results[0].chunk.page, Some(12));
+    assert_eq!(results[0].source_label(), "manual.pdf p.12");
+
+    let _ = fs::remove_dir_all(&tmp_dir);
+    Ok(())
+}
+
+#[cfg(not(feature = "pdf"))]
+#[test]
+fn pdfs_are_skipped_without_the_pdf_feature() -> Result<()> {
+    let tmp_dir = std::env::temp_dir().join("rag_test_pdf_skipped");
+    let _ = fs::remove_dir_all(&tmp_dir);
+    fs::create_dir_all(tmp_dir.join("docs"))?;
+    fs::write(tmp_dir.join("docs/manual.pdf"), b"%PDF-1.4\n%%EOF\n")?;
+    fs::write(tmp_dir.join("docs/notes.txt"), "Cargo builds Rust crates.")?;
+
+    let store = Store::new(tmp_dir.join("rag.db"))?;
+    let embedder = LocalEmbedder::new(64);
+    tapssp_project::ingest::run_ingest(&store, &embedder, &[tmp_dir.join("docs")], 64, 16)?;
+    let results = run_query(&store, &embedder, "Cargo", 5)?;
+    assert_eq!(results.len(), 1);
+    assert!(results[0].document_path.ends_with("notes.txt"));
+    assert_eq!(results[0].chunk.page, None);
+
+    let _ = fs::remove_dir_all(&tmp_dir);
+    Ok(())
+}
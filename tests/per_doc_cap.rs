@@ -0,0 +1,48 @@
+use std::fs;
+
+use anyhow::Result;
+
+use tapssp_project::embedder::LocalEmbedder;
+use tapssp_project::ingest::run_ingest;
+use tapssp_project::query::{run_query_with, QueryOptions};
+use tapssp_project::store::Store;
+
+#[test]
+fn per_doc_cap_searches_past_a_dominant_document() -> Result<()> {
+    let tmp_dir = std::env::temp_dir().join("rag_test_per_doc_cap");
+    let _ = fs::remove_dir_all(&tmp_dir);
+    fs::create_dir_all(&tmp_dir)?;
+    let db_path = tmp_dir.join("rag.db");
+    // Every chunk of big.md outscores the other documents.
+    fs::write(tmp_dir.join("big.md"), "rust ownership\n".repeat(40))?;
+    for name in ["a.md", "b.md", "c.md"] {
+        fs::write(
+            tmp_dir.join(name),
+            format!(
+                "rust ownership notes kept in {} with unrelated filler words",
+                name
+            ),
+        )?;
+    }
+
+    let store = Store::new(&db_path)?;
+    let embedder = LocalEmbedder::new(256);
+    run_ingest(&store, &embedder, std::slice::from_ref(&tmp_dir), 16, 0)?;
+
+    let opts = QueryOptions {
+        top_k: 3,
+        max_per_doc: Some(1),
+        ..QueryOptions::default()
+    };
+    let mut paths: Vec<String> = run_query_with(&store, &embedder, "rust ownership", &opts)?
+        .into_iter()
+        .map(|r| r.document_path.rsplit('/').next().unwrap().to_string())
+        .collect();
+    assert_eq!(paths[0], "big.md");
+    paths.sort();
+    paths.dedup();
+    assert_eq!(paths.len(), 3);
+
+    let _ = fs::remove_dir_all(&tmp_dir);
+    Ok(())
+}
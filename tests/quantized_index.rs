@@ -0,0 +1,100 @@
+use std::fs;
+
+use anyhow::Result;
+
+use tapssp_project::embedder::LocalEmbedder;
+use tapssp_project::index::{build_index, evaluate_recall, BuildOptions, IndexKind, IndexStats};
+use tapssp_project::ingest::run_ingest;
+use tapssp_project::query::{run_query_with, QueryOptions};
+use tapssp_project::store::Store;
+
+const WORDS: &[&str] = &[
+    "rust",
+    "cargo",
+    "borrow",
+    "checker",
+    "lifetime",
+    "trait",
+    "tokio",
+    "async",
+    "sqlite",
+    "index",
+    "vector",
+    "embedding",
+    "query",
+    "rerank",
+    "chunk",
+    "token",
+    "model",
+    "cosine",
+    "heap",
+    "scan",
+];
+
+#[test]
+fn quantized_indexes_keep_recall_and_persist() -> Result<()> {
+    let tmp_dir = std::env::temp_dir().join("rag_test_quantized_index");
+    let _ = fs::remove_dir_all(&tmp_dir);
+    fs::create_dir_all(tmp_dir.join("docs"))?;
+    let db_path = tmp_dir.join("rag.db");
+    for i in 0..80 {
+        let text: Vec<&str> = (0..6)
+            .map(|j| WORDS[(i * 7 + j * j * 3 + i / 5) % WORDS.len()])
+            .collect();
+        fs::write(
+            tmp_dir.join("docs").join(format!("{}.md", i)),
+            text.join(" "),
+        )?;
+    }
+
+    let embedder = LocalEmbedder::new(64);
+    let mut store = Store::new(&db_path)?;
+    run_ingest(&store, &embedder, &[tmp_dir.join("docs")], 512, 64)?;
+    let question = "borrow checker lifetime";
+    let flat = run_query_with(&store, &embedder, question, &QueryOptions::default())?;
+
+    let encoded = build_index(
+        &mut store,
+        &BuildOptions {
+            kind: IndexKind::Int8,
+            ..BuildOptions::default()
+        },
+    )?;
+    assert_eq!(encoded, 80);
+    assert!(evaluate_recall(&store, 20, 5, &QueryOptions::default())?.recall >= 0.9);
+    let int8 = run_query_with(&store, &embedder, question, &QueryOptions::default())?;
+    assert_eq!(int8[0].chunk.id, flat[0].chunk.id);
+    // Reported scores are always full precision.
+    assert!((int8[0].score - flat[0].score).abs() < 1e-6);
+
+    build_index(
+        &mut store,
+        &BuildOptions {
+            kind: IndexKind::Pq,
+            subspaces: 8,
+            ..BuildOptions::default()
+        },
+    )?;
+    drop(store);
+    let store = Store::new(&db_path)?;
+    assert_eq!(store.vector_index().kind(), IndexKind::Pq);
+    let rescored = QueryOptions {
+        rescore: Some(40),
+        ..QueryOptions::default()
+    };
+    let report = evaluate_recall(&store, 20, 5, &rescored)?;
+    assert!(report.recall >= 0.9, "recall {}", report.recall);
+    let stats = IndexStats::load(&store)?;
+    assert_eq!((stats.dim, stats.coded), (64, 80));
+    assert_eq!(stats.compression_ratio(), Some(32.0));
+
+    // Chunks ingested after the build are encoded on insert.
+    fs::write(tmp_dir.join("late.md"), "tokio async runtime")?;
+    run_ingest(&store, &embedder, &[tmp_dir.join("late.md")], 512, 64)?;
+    assert_eq!(IndexStats::load(&store)?.coded, 81);
+    let hits = run_query_with(&store, &embedder, "tokio async runtime", &rescored)?;
+    assert!(hits[0].document_path.ends_with("late.md"));
+
+    let _ = fs::remove_dir_all(&tmp_dir);
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use std::fs;
+
+use anyhow::Result;
+use chrono::{Duration, Utc};
+
+use tapssp_project::embedder::LocalEmbedder;
+use tapssp_project::filter::QueryFilter;
+use tapssp_project::ingest::{run_ingest_with, IngestOptions};
+use tapssp_project::query::{run_query_with, QueryOptions};
+use tapssp_project::store::Store;
+
+#[test]
+fn filters_restrict_which_documents_are_scored() -> Result<()> {
+    let tmp_dir = std::env::temp_dir().join("rag_test_filter");
+    let _ = fs::remove_dir_all(&tmp_dir);
+    fs::create_dir_all(tmp_dir.join("src/nested"))?;
+    fs::create_dir_all(tmp_dir.join("notes"))?;
+    let db_path = tmp_dir.join("rag.db");
+    let text = "Ownership and borrowing keep memory safe without a garbage collector.";
+    fs::write(tmp_dir.join("src/lib.rs"), format!("// {}", text))?;
+    fs::write(tmp_dir.join("src/nested/mod.rs"), format!("// {}", text))?;
+    fs::write(tmp_dir.join("notes/ownership.md"), text)?;
+
+    let store = Store::new(&db_path)?;
+    let embedder = LocalEmbedder::new(128);
+    let tagged = IngestOptions {
+        tags: vec!["team-a".to_string()],
+        ..IngestOptions::default()
+    };
+    run_ingest_with(&store, &embedder, &[tmp_dir.join("src")], &tagged)?;
+    run_ingest_with(
+        &store,
+        &embedder,
+        &[tmp_dir.join("notes")],
+        &IngestOptions::default(),
+    )?;
+
+    let search = |filter: QueryFilter| -> Result<Vec<String>> {
+        let opts = QueryOptions {
+            top_k: 10,
+            filter,
+            ..QueryOptions::default()
+        };
+        let mut paths: Vec<String> = run_query_with(&store, &embedder, "ownership", &opts)?
+            .into_iter()
+            .map(|r| r.document_path.rsplit('/').next().unwrap().to_string())
+            .collect();
+        paths.sort();
+        Ok(paths)
+    };
+
+    assert_eq!(search(QueryFilter::default())?.len(), 3);
+    assert_eq!(search(QueryFilter::parse("ext:md")?)?, vec!["ownership.md"]);
+    assert_eq!(search(QueryFilter::parse("ext:MD")?)?, vec!["ownership.md"]);
+    // LIKE wildcards in filter values match literally.
+    assert!(search(QueryFilter::parse("ext:m_")?)?.is_empty());
+    assert!(search(QueryFilter::parse("ext:%")?)?.is_empty());
+    assert!(search(QueryFilter::parse("doc:%")?)?.is_empty());
+    assert_eq!(
+        search(QueryFilter::parse("path:**/nested/*.rs")?)?,
+        vec!["mod.rs"]
+    );
+    assert_eq!(
+        search(QueryFilter::parse("tag:team-a")?)?,
+        vec!["lib.rs", "mod.rs"]
+    );
+    assert_eq!(
+        search(QueryFilter::parse("tag:team-a ext:md")?)?,
+        Vec::<String>::new()
+    );
+    let future = QueryFilter {
+        since: Some(Utc::now() + Duration::days(1)),
+        ..QueryFilter::default()
+    };
+    assert!(search(future)?.is_empty());
+
+    let doc_id = run_query_with(&store, &embedder, "ownership", &QueryOptions::default())?[0]
+        .chunk
+        .doc_id
+        .to_string();
+    let by_doc = QueryFilter::parse(&format!("doc:{}", &doc_id[..8]))?;
+    assert_eq!(search(by_doc)?.len(), 1);
+
+    let _ = fs::remove_dir_all(&tmp_dir);
+    Ok(())
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use std::fs;
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use common::mock_server;
+use tapssp_project::embedder::LocalEmbedder;
+use tapssp_project::ingest::run_ingest;
+use tapssp_project::query::{run_query_with, QueryOptions};
+use tapssp_project::rerank::{HttpReranker, LexicalReranker, Reranker};
+use tapssp_project::store::Store;
+
+#[test]
+fn http_reranker_maps_scores_back_to_passages() -> Result<()> {
+    let (url, server) = mock_server(
+        "application/json",
+        r#"{"results":[{"index":2,"relevance_score":0.91},{"index":0,"relevance_score":0.12}]}"#
+            .to_string(),
+    );
+    let reranker = HttpReranker::new(
+        format!("{}/v1", url),
+        "rerank-test".to_string(),
+        Some("rk-test".to_string()),
+    );
+
+    let scores = reranker.score("borrow checker", &["cargo", "crates", "borrowck"])?;
+    let request = server.join().unwrap();
+
+    assert!(request.starts_with("POST /v1/rerank"));
+    assert!(request
+        .to_ascii_lowercase()
+        .contains("authorization: bearer rk-test"));
+    assert!(request.contains(r#""documents":["cargo","crates","borrowck"]"#));
+    assert!(request.contains(r#""model":"rerank-test""#));
+    assert_eq!(scores[0], 0.12);
+    assert_eq!(scores[2], 0.91);
+    assert!(scores[1] < scores[0]);
+    Ok(())
+}
+
+#[test]
+fn query_reranks_overfetched_candidates() -> Result<()> {
+    let tmp_dir = std::env::temp_dir().join("rag_test_rerank");
+    let _ = fs::remove_dir_all(&tmp_dir);
+    fs::create_dir_all(&tmp_dir)?;
+    let db_path = tmp_dir.join("rag.db");
+    for (name, text) in [
+        ("a.md", "Cargo builds packages and downloads crates."),
+        ("b.md", "The borrow checker rejects dangling references."),
+        ("c.md", "Rust compiles to native code."),
+    ] {
+        fs::write(tmp_dir.join(name), text)?;
+    }
+
+    let store = Store::new(&db_path)?;
+    let embedder = LocalEmbedder::new(64);
+    run_ingest(&store, &embedder, std::slice::from_ref(&tmp_dir), 512, 64)?;
+
+    let opts = QueryOptions {
+        top_k: 1,
+        reranker: Some(Arc::new(LexicalReranker)),
+        rerank_candidates: 3,
+        ..QueryOptions::default()
+    };
+    let results = run_query_with(
+        &store,
+        &embedder,
+        "what rejects dangling references?",
+        &opts,
+    )?;
+
+    assert_eq!(results.len(), 1);
+    assert!(results[0].document_path.ends_with("b.md"));
+    assert_eq!(results[0].rerank_score, Some(1.0));
+
+    let _ = fs::remove_dir_all(&tmp_dir);
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use std::fs;
+
+use anyhow::Result;
+
+use tapssp_project::embedder::LocalEmbedder;
+use tapssp_project::filter::QueryFilter;
+use tapssp_project::ingest::run_ingest;
+use tapssp_project::store::Store;
+
+#[test]
+fn scan_streams_embeddings_in_batches_and_fetches_winners() -> Result<()> {
+    let tmp_dir = std::env::temp_dir().join("rag_test_store_scan");
+    let _ = fs::remove_dir_all(&tmp_dir);
+    fs::create_dir_all(&tmp_dir)?;
+    let db_path = tmp_dir.join("rag.db");
+    for (name, text) in [
+        ("a.md", "alpha"),
+        ("b.md", "beta"),
+        ("c.md", "gamma"),
+        ("d.txt", "delta"),
+        ("e.md", "epsilon"),
+    ] {
+        fs::write(tmp_dir.join(name), text)?;
+    }
+
+    let store = Store::new(&db_path)?;
+    let embedder = LocalEmbedder::new(64);
+    run_ingest(&store, &embedder, std::slice::from_ref(&tmp_dir), 512, 64)?;
+
+    let mut batches = Vec::new();
+    let mut rowids = Vec::new();
+    let scanned = store.scan_embeddings(&QueryFilter::parse("ext:md")?, 2, |ids, embeddings| {
+        assert_eq!(ids.len(), embeddings.len());
+        assert!(embeddings.iter().all(|e| e.len() == 64));
+        batches.push(ids.len());
+        rowids.extend_from_slice(ids);
+        Ok(())
+    })?;
+    assert_eq!(scanned, 4);
+    assert_eq!(batches, vec![2, 2]);
+
+    let fetched = store.chunks_by_rowid(&rowids[..3])?;
+    assert_eq!(fetched.len(), 3);
+    assert!(fetched.values().all(|(c, path)| path.ends_with(".md") && !c.text.is_empty()));
+    assert!(store.chunks_by_rowid(&[i64::MAX])?.is_empty());
+
+    let _ = fs::remove_dir_all(&tmp_dir);
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use std::fs;
+
+use anyhow::Result;
+use rusqlite::{params, Connection};
+
+use tapssp_project::filter::QueryFilter;
+use tapssp_project::store::Store;
+
+#[test]
+fn legacy_embeddings_are_normalized_on_open() -> Result<()> {
+    let tmp_dir = std::env::temp_dir().join("rag_test_unit_vectors");
+    let _ = fs::remove_dir_all(&tmp_dir);
+    fs::create_dir_all(&tmp_dir)?;
+    let db_path = tmp_dir.join("rag.db");
+
+    // A database written before embeddings were stored at unit length.
+    let conn = Connection::open(&db_path)?;
+    conn.execute_batch(
+        "CREATE TABLE documents (id TEXT PRIMARY KEY, path TEXT NOT NULL, created_at TEXT NOT NULL);
+         CREATE TABLE chunks (id TEXT PRIMARY KEY, doc_id TEXT NOT NULL, chunk_index INTEGER NOT NULL,
+             text TEXT NOT NULL, embedding TEXT NOT NULL, start_char INTEGER, end_char INTEGER);",
+    )?;
+    conn.execute(
+        "INSERT INTO documents VALUES (?1, 'a.md', '2026-01-01T00:00:00+00:00')",
+        params!["00000000-0000-0000-0000-000000000001"],
+    )?;
+    conn.execute(
+        "INSERT INTO chunks VALUES (?1, ?2, 0, 'alpha', '[3.0,4.0]', 0, 5)",
+        params![
+            "00000000-0000-0000-0000-0000000000c1",
+            "00000000-0000-0000-0000-000000000001"
+        ],
+    )?;
+    drop(conn);
+
+    let store = Store::new(&db_path)?;
+    let chunks = store.chunks_with_paths(&QueryFilter::default())?;
+    assert_eq!(chunks[0].0.embedding, vec![0.6, 0.8]);
+
+    // Opening again must not rescale twice.
+    drop(store);
+    let store = Store::new(&db_path)?;
+    let chunks = store.chunks_with_paths(&QueryFilter::default())?;
+    assert_eq!(chunks[0].0.embedding, vec![0.6, 0.8]);
+
+    let _ = fs::remove_dir_all(&tmp_dir);
+    Ok(())
+}
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use chrono::Utc;
+use uuid::Uuid;
+
+use tapssp_project::embedder::{Embedder, LocalEmbedder};
+use tapssp_project::ingest::IngestOptions;
+use tapssp_project::models::{Document, DocumentMetadata, FieldValue};
+use tapssp_project::query::run_query;
+use tapssp_project::store::Store;
+use tapssp_project::watch::{apply_changes, Change};
+
+/// Stands in for an embedding API that is down.
+struct FailingEmbedder;
+
+impl Embedder for FailingEmbedder {
+    fn name(&self) -> &'static str {
+        "failing"
+    }
+
+    fn embed(&self, _texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        bail!("service unavailable")
+    }
+}
+
+fn insert_doc(store: &Store, path: &str) -> Result<()> {
+    store.insert_document(&Document {
+        id: Uuid::new_v4(),
+        path: path.to_string(),
+        created_at: Utc::now(),
+        encoding: None,
+        metadata: DocumentMetadata::default(),
+    })
+}
+
+#[test]
+fn delete_by_path_covers_directories_and_archive_members_only() -> Result<()> {
+    let tmp_dir = std::env::temp_dir().join("rag_test_delete_by_path");
+    let _ = fs::remove_dir_all(&tmp_dir);
+    let store = Store::new(tmp_dir.join("rag.db"))?;
+    for path in [
+        "docs/a.md",
+        "docs/sub/b.md",
+        "docs-old/c.md",
+        "bundle.zip!/d.md",
+        "bundle.zip.bak",
+    ] {
+        insert_doc(&store, path)?;
+    }
+
+    assert_eq!(store.delete_documents_by_path("bundle.zip")?, 1);
+    let under: Vec<String> = store
+        .documents_by_path("docs")?
+        .into_iter()
+        .map(|(_, p)| p)
+        .collect();
+    assert_eq!(under, ["docs/a.md", "docs/sub/b.md"]);
+    assert_eq!(store.delete_documents_by_path("docs")?, 2);
+    assert_eq!(store.corpus_stats()?.0, 2);
+
+    let _ = fs::remove_dir_all(&tmp_dir);
+    Ok(())
+}
+
+#[test]
+fn failed_reingest_keeps_the_old_documents() -> Result<()> {
+    let tmp_dir = std::env::temp_dir().join("rag_test_watch_apply");
+    let _ = fs::remove_dir_all(&tmp_dir);
+    fs::create_dir_all(&tmp_dir)?;
+    let file = tmp_dir.join("notes.md");
+    let gone = tmp_dir.join("gone.md");
+    fs::write(&file, "Cargo builds Rust crates.")?;
+    fs::write(&gone, "Tokio runs async tasks.")?;
+
+    let store = Store::new(tmp_dir.join("rag.db"))?;
+    let embedder = LocalEmbedder::new(64);
+    let opts = IngestOptions::default();
+    let upsert = |p: &PathBuf| vec![(p.clone(), Change::Upsert)];
+    apply_changes(&store, &embedder, upsert(&file), &opts)?;
+    apply_changes(&store, &embedder, upsert(&gone), &opts)?;
+    assert_eq!(store.corpus_stats()?.0, 2);
+
+    // The embedder fails: the edit is not applied, the old version stays.
+    fs::write(&file, "Cargo builds and publishes Rust crates.")?;
+    assert!(apply_changes(&store, &FailingEmbedder, upsert(&file), &opts).is_err());
+    let hits = run_query(&store, &embedder, "Cargo", 5)?;
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0].chunk.text, "Cargo builds Rust crates.");
+
+    // A removal batched with the failed edit is rolled back too.
+    fs::remove_file(&gone)?;
+    let batch = vec![
+        (file.clone(), Change::Upsert),
+        (gone.clone(), Change::Remove),
+    ];
+    assert!(apply_changes(&store, &FailingEmbedder, batch, &opts).is_err());
+    assert_eq!(store.corpus_stats()?.0, 2);
+
+    // Once it works again the document is replaced, not duplicated.
+    apply_changes(&store, &embedder, upsert(&file), &opts)?;
+    let hits = run_query(&store, &embedder, "publishes", 5)?;
+    assert_eq!(hits.len(), 2);
+    assert!(hits[0].chunk.text.contains("publishes"));
+
+    // Removals need no embedder.
+    apply_changes(
+        &store,
+        &FailingEmbedder,
+        vec![(gone, Change::Remove)],
+        &opts,
+    )?;
+    assert_eq!(store.corpus_stats()?.0, 1);
+
+    let _ = fs::remove_dir_all(&tmp_dir);
+    Ok(())
+}
+
+#[test]
+fn reingest_keeps_tags_and_fields_edited_after_ingest() -> Result<()> {
+    let tmp_dir = std::env::temp_dir().join("rag_test_watch_metadata");
+    let _ = fs::remove_dir_all(&tmp_dir);
+    fs::create_dir_all(&tmp_dir)?;
+    let file = tmp_dir.join("notes.md");
+    fs::write(&file, "---\nstatus: draft\n---\nCargo builds Rust crates.")?;
+
+    let store = Store::new(tmp_dir.join("rag.db"))?;
+    let embedder = LocalEmbedder::new(64);
+    let opts = IngestOptions::default();
+    let upsert = || vec![(file.clone(), Change::Upsert)];
+    apply_changes(&store, &embedder, upsert(), &opts)?;
+
+    // As `rag docs tag` would.
+    let (id, _) = store.documents_by_path(&file.to_string_lossy())?[0].clone();
+    store.add_document_tags(id, &["pinned".to_string()])?;
+    let one = |v: &str| FieldValue::One(v.to_string());
+    store.set_document_fields(
+        id,
+        &BTreeMap::from([
+            ("owner".to_string(), one("ann")),
+            ("status".to_string(), one("final")),
+        ]),
+    )?;
+
+    // The edited front matter wins; the other edits survive.
+    fs::write(
+        &file,
+        "---\nstatus: review\n---\nCargo builds and publishes Rust crates.",
+    )?;
+    apply_changes(&store, &embedder, upsert(), &opts)?;
+    let docs = store.documents_by_path(&file.to_string_lossy())?;
+    assert_eq!(docs.len(), 1);
+    assert_ne!(docs[0].0, id);
+    let metadata = &store.document_metadata(&[docs[0].0])?[&docs[0].0];
+    assert_eq!(metadata.tags, ["pinned"]);
+    assert_eq!(metadata.fields["owner"], one("ann"));
+    assert_eq!(metadata.fields["status"], one("review"));
+
+    let _ = fs::remove_dir_all(&tmp_dir);
+    Ok(())
+}